@@ -0,0 +1,200 @@
+//! # Colours
+//!
+//! Colour types and conversions used by the RGB LED example: HSV, colour
+//! temperature, blending and gamma correction.
+//!
+//! Everything in here is plain maths on `f32`/`u8`, so it does not depend on
+//! the HAL and is tested on the host with `cargo test`.
+
+#![no_std]
+
+/// A colour in 8-bit-per-channel RGB space
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+/// A colour in HSV space
+///
+/// `h` is the hue in degrees, `s` and `v` are in the range `0.0..=1.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Hsv {
+    pub h: f32,
+    pub s: f32,
+    pub v: f32,
+}
+
+/// Lowest colour temperature supported by [`Rgb::from_kelvin`]
+pub const MIN_KELVIN: u16 = 1_000;
+
+/// Highest colour temperature supported by [`Rgb::from_kelvin`]
+pub const MAX_KELVIN: u16 = 40_000;
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb::new(0, 0, 0);
+    pub const WHITE: Rgb = Rgb::new(255, 255, 255);
+    pub const RED: Rgb = Rgb::new(255, 0, 0);
+    pub const GREEN: Rgb = Rgb::new(0, 255, 0);
+    pub const BLUE: Rgb = Rgb::new(0, 0, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Approximates the colour of a black body at the given temperature.
+    ///
+    /// Uses Tanner Helland's curve fit, which is good enough for tinting an
+    /// LED between candle light (~1900 K) and overcast sky (~7000 K). The
+    /// temperature is clamped to `MIN_KELVIN..=MAX_KELVIN`.
+    pub fn from_kelvin(kelvin: u16) -> Self {
+        let temp = kelvin.clamp(MIN_KELVIN, MAX_KELVIN) as f32 / 100.0;
+
+        let r = if temp <= 66.0 {
+            255.0
+        } else {
+            329.698_73 * libm::powf(temp - 60.0, -0.133_204_76)
+        };
+
+        let g = if temp <= 66.0 {
+            99.470_8 * libm::logf(temp) - 161.119_57
+        } else {
+            288.122_16 * libm::powf(temp - 60.0, -0.075_514_85)
+        };
+
+        let b = if temp >= 66.0 {
+            255.0
+        } else if temp <= 19.0 {
+            0.0
+        } else {
+            138.517_73 * libm::logf(temp - 10.0) - 305.044_8
+        };
+
+        Self::new(to_u8(r), to_u8(g), to_u8(b))
+    }
+
+    /// Linearly interpolates between `self` and `other`.
+    ///
+    /// `t = 0.0` gives `self`, `t = 1.0` gives `other`.
+    pub fn lerp(self, other: Rgb, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| to_u8(a as f32 + (b as f32 - a as f32) * t);
+        Self::new(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+        )
+    }
+}
+
+impl From<Hsv> for Rgb {
+    fn from(hsv: Hsv) -> Self {
+        hsv.to_rgb()
+    }
+}
+
+impl Hsv {
+    pub const fn new(h: f32, s: f32, v: f32) -> Self {
+        Self { h, s, v }
+    }
+
+    pub fn to_rgb(self) -> Rgb {
+        let h = self.h % 360.0;
+        let h = if h < 0.0 { h + 360.0 } else { h };
+        let s = self.s.clamp(0.0, 1.0);
+        let v = self.v.clamp(0.0, 1.0);
+
+        let chroma = v * s;
+        let sector = h / 60.0;
+        let x = chroma * (1.0 - libm::fabsf(sector % 2.0 - 1.0));
+        let m = v - chroma;
+
+        let (r, g, b) = match sector as u8 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+
+        Rgb::new(
+            to_u8((r + m) * 255.0),
+            to_u8((g + m) * 255.0),
+            to_u8((b + m) * 255.0),
+        )
+    }
+}
+
+/// Maps an 8-bit channel value to a PWM duty cycle between `0` and `max_duty`
+/// using a power-law gamma curve.
+///
+/// The eye is far more sensitive to changes at low brightness, so a linear
+/// duty cycle makes the bottom of the range jump and the top look flat. A
+/// gamma of around `2.2` gives a perceptually even ramp.
+pub fn gamma_correct(value: u8, gamma: f32, max_duty: u16) -> u16 {
+    let normalized = value as f32 / 255.0;
+    let corrected = libm::powf(normalized, gamma);
+    (corrected * max_duty as f32 + 0.5) as u16
+}
+
+fn to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 255.0) + 0.5) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primaries() {
+        assert_eq!(Hsv::new(0.0, 1.0, 1.0).to_rgb(), Rgb::RED);
+        assert_eq!(Hsv::new(120.0, 1.0, 1.0).to_rgb(), Rgb::GREEN);
+        assert_eq!(Hsv::new(240.0, 1.0, 1.0).to_rgb(), Rgb::BLUE);
+        assert_eq!(Hsv::new(60.0, 1.0, 1.0).to_rgb(), Rgb::new(255, 255, 0));
+    }
+
+    #[test]
+    fn grey_has_no_hue() {
+        assert_eq!(Hsv::new(200.0, 0.0, 1.0).to_rgb(), Rgb::WHITE);
+        assert_eq!(Hsv::new(200.0, 1.0, 0.0).to_rgb(), Rgb::BLACK);
+        assert_eq!(Hsv::new(0.0, 0.0, 0.5).to_rgb(), Rgb::new(128, 128, 128));
+    }
+
+    #[test]
+    fn hue_wraps() {
+        assert_eq!(Hsv::new(360.0, 1.0, 1.0).to_rgb(), Rgb::RED);
+        assert_eq!(Hsv::new(480.0, 1.0, 1.0).to_rgb(), Rgb::GREEN);
+        assert_eq!(Hsv::new(-120.0, 1.0, 1.0).to_rgb(), Rgb::BLUE);
+    }
+
+    #[test]
+    fn gamma_end_points() {
+        assert_eq!(gamma_correct(0, 2.2, 1000), 0);
+        assert_eq!(gamma_correct(255, 2.2, 1000), 1000);
+        assert_eq!(gamma_correct(255, 2.2, u16::MAX), u16::MAX);
+        // Linear with a gamma of 1
+        assert_eq!(gamma_correct(51, 1.0, 1000), 200);
+        // Half the input is well under half the duty
+        assert!(gamma_correct(128, 2.2, 1000) < 250);
+    }
+
+    #[test]
+    fn lerp() {
+        assert_eq!(Rgb::BLACK.lerp(Rgb::WHITE, 0.0), Rgb::BLACK);
+        assert_eq!(Rgb::BLACK.lerp(Rgb::WHITE, 1.0), Rgb::WHITE);
+        assert_eq!(Rgb::RED.lerp(Rgb::BLUE, 0.5), Rgb::new(128, 0, 128));
+        assert_eq!(Rgb::BLACK.lerp(Rgb::WHITE, 2.0), Rgb::WHITE);
+    }
+
+    #[test]
+    fn colour_temperature() {
+        assert_eq!(Rgb::from_kelvin(6600), Rgb::new(255, 255, 255));
+        let candle = Rgb::from_kelvin(1900);
+        assert_eq!(candle.r, 255);
+        assert!(candle.b == 0 && candle.g < 150);
+        assert_eq!(Rgb::from_kelvin(0), Rgb::from_kelvin(MIN_KELVIN));
+        assert_eq!(Rgb::from_kelvin(u16::MAX), Rgb::from_kelvin(MAX_KELVIN));
+    }
+}
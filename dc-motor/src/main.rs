@@ -0,0 +1,118 @@
+//! # DC Motor Example
+//!
+//! Drives a brushed DC motor through an H-bridge (DRV8833, L298N, TB6612 or
+//! similar) with IN1 on GPIO 14 and IN2 on GPIO 15, both channels of PWM
+//! slice 7. The motor ramps up forwards, brakes, ramps up in reverse and
+//! coasts to a stop.
+//!
+//! Power the motor from its own supply and connect its ground to the Pico's.
+
+#![no_std]
+#![no_main]
+
+// Ensure we halt the program on panic (if we don't mention this crate it won't
+// be linked)
+use panic_halt as _;
+
+// Alias for our HAL crate
+use rp235x_hal as hal;
+
+// Some things we need
+use embedded_hal::delay::DelayNs;
+use hal::Clock;
+use motor::{DecayMode, Motor, PwmSettings, DEFAULT_PWM_FREQ_HZ};
+
+mod motor;
+
+/// Tell the Boot ROM about our application
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
+
+/// External high-speed crystal on the Raspberry Pi Pico 2 board is 12 MHz.
+/// Adjust if your board has a different frequency
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Lower it if your bridge is an L298N, whose slow transistors get hot
+/// switching at 20 kHz. Anything under about 16 kHz can be heard.
+const PWM_FREQ_HZ: u32 = DEFAULT_PWM_FREQ_HZ;
+
+/// Time to get from standstill to full speed
+const SOFT_START_MS: u32 = 2000;
+
+#[hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // The single-cycle I/O block controls our GPIO pins
+    let sio = hal::Sio::new(pac.SIO);
+
+    // Set the pins up according to their function on this particular board
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let mut timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    // Init PWMs
+    let mut pwm_slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
+
+    let pwm_settings = PwmSettings::for_frequency(clocks.system_clock.freq().to_Hz(), PWM_FREQ_HZ);
+
+    let pwm = &mut pwm_slices.pwm7;
+    pwm.set_div_int(pwm_settings.div_int);
+    pwm.set_top(pwm_settings.top);
+    pwm.enable();
+
+    let in1 = &mut pwm.channel_a;
+    in1.output_to(pins.gpio14);
+    let in2 = &mut pwm.channel_b;
+    in2.output_to(pins.gpio15);
+
+    let mut motor = Motor::new(in1, in2, DecayMode::Slow);
+
+    loop {
+        motor.ramp_to(1.0, SOFT_START_MS, &mut timer);
+        timer.delay_ms(2000);
+        motor.brake();
+        timer.delay_ms(1000);
+
+        motor.ramp_to(-0.6, SOFT_START_MS, &mut timer);
+        timer.delay_ms(2000);
+        motor.coast();
+        timer.delay_ms(3000);
+    }
+}
+
+/// Program metadata for `picotool info`
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"DC Motor Example"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
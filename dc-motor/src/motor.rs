@@ -0,0 +1,165 @@
+use embedded_hal::delay::DelayNs;
+use embedded_hal::pwm::SetDutyCycle;
+
+/// PWM frequency above the range of human hearing, so the motor doesn't
+/// whine
+pub const DEFAULT_PWM_FREQ_HZ: u32 = 20_000;
+
+/// Time between two speed updates while ramping, in milliseconds
+const RAMP_STEP_MS: u32 = 10;
+
+/// Divider and wrap value for one PWM slice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PwmSettings {
+    pub div_int: u8,
+    pub top: u16,
+}
+
+impl PwmSettings {
+    /// Divider and `top` for a slice clocked at `sys_clk_hz` to run at
+    /// `freq_hz`. The divider is kept as small as the 16 bit counter
+    /// allows, since every count of `top` is one step of motor speed.
+    ///
+    /// At 150 MHz and 20 kHz this is a divider of 1 and a `top` of 7499.
+    /// Frequencies out of reach of the slice give the nearest it can do.
+    pub const fn for_frequency(sys_clk_hz: u32, freq_hz: u32) -> Self {
+        let counts = sys_clk_hz / freq_hz;
+        let mut div = counts.div_ceil(1 << 16);
+        if div == 0 {
+            div = 1;
+        }
+        if div > u8::MAX as u32 {
+            div = u8::MAX as u32;
+        }
+        let top = (counts / div).saturating_sub(1);
+        Self {
+            div_int: div as u8,
+            top: if top > u16::MAX as u32 {
+                u16::MAX
+            } else {
+                top as u16
+            },
+        }
+    }
+}
+
+/// What the bridge does during the off part of each PWM period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecayMode {
+    /// Both outputs float, the motor freewheels. Speed is less linear with
+    /// duty cycle but the bridge runs cooler.
+    Fast,
+    /// Both outputs are shorted, the motor brakes. Speed follows the duty
+    /// cycle more closely, especially under load.
+    Slow,
+}
+
+/// A brushed DC motor on an H-bridge with two PWM inputs
+///
+/// Fits bridges driven through IN1/IN2, like the DRV8833. On an L298N keep
+/// the ENA/ENB jumpers fitted, and on a TB6612 tie PWMA/PWMB high, then use
+/// the direction inputs as IN1 and IN2.
+pub struct Motor<A, B> {
+    in1: A,
+    in2: B,
+    decay: DecayMode,
+    speed: f32,
+}
+
+impl<A: SetDutyCycle, B: SetDutyCycle> Motor<A, B> {
+    /// Creates the motor, coasting.
+    pub fn new(in1: A, in2: B, decay: DecayMode) -> Self {
+        let mut motor = Self {
+            in1,
+            in2,
+            decay,
+            speed: 0.0,
+        };
+        motor.coast();
+        motor
+    }
+
+    pub fn decay(&self) -> DecayMode {
+        self.decay
+    }
+
+    pub fn set_decay(&mut self, decay: DecayMode) {
+        self.decay = decay;
+        self.set_speed(self.speed);
+    }
+
+    /// Sets the speed from -1.0 (full reverse) to 1.0 (full forward).
+    pub fn set_speed(&mut self, speed: f32) {
+        let speed = speed.clamp(-1.0, 1.0);
+        self.speed = speed;
+
+        if speed < 0.0 {
+            drive(&mut self.in2, &mut self.in1, -speed, self.decay);
+        } else {
+            drive(&mut self.in1, &mut self.in2, speed, self.decay);
+        }
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Shorts the motor terminals so it stops quickly and resists turning.
+    pub fn brake(&mut self) {
+        self.speed = 0.0;
+        let _ = self.in1.set_duty_cycle_fully_on();
+        let _ = self.in2.set_duty_cycle_fully_on();
+    }
+
+    /// Lets the motor spin down freely.
+    pub fn coast(&mut self) {
+        self.speed = 0.0;
+        let _ = self.in1.set_duty_cycle_fully_off();
+        let _ = self.in2.set_duty_cycle_fully_off();
+    }
+
+    /// Changes speed gradually over `duration_ms`, so the motor doesn't
+    /// draw a current spike or jerk its load.
+    ///
+    /// This blocks until the ramp is complete.
+    pub fn ramp_to(&mut self, target: f32, duration_ms: u32, delay: &mut impl DelayNs) {
+        let start = self.speed;
+        let steps = (duration_ms / RAMP_STEP_MS).max(1);
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            self.set_speed(start + (target - start) * t);
+            delay.delay_ms(RAMP_STEP_MS);
+        }
+    }
+
+    /// Gives back the PWM channels.
+    pub fn release(self) -> (A, B) {
+        (self.in1, self.in2)
+    }
+}
+
+/// Drives current from `forward` to `back` for `magnitude` of each period.
+fn drive<F: SetDutyCycle, R: SetDutyCycle>(
+    forward: &mut F,
+    back: &mut R,
+    magnitude: f32,
+    decay: DecayMode,
+) {
+    match decay {
+        // Drive, then both low
+        DecayMode::Fast => {
+            let _ = back.set_duty_cycle_fully_off();
+            let _ = forward.set_duty_cycle(duty_for(forward.max_duty_cycle(), magnitude));
+        }
+        // Drive, then both high
+        DecayMode::Slow => {
+            let _ = forward.set_duty_cycle_fully_on();
+            let max = back.max_duty_cycle();
+            let _ = back.set_duty_cycle(max - duty_for(max, magnitude));
+        }
+    }
+}
+
+fn duty_for(max_duty: u16, fraction: f32) -> u16 {
+    (max_duty as f32 * fraction + 0.5) as u16
+}
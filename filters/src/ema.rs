@@ -0,0 +1,90 @@
+use crate::{Filter, Sample};
+
+/// Exponential moving average
+///
+/// Each output moves `alpha` of the way from the previous output towards
+/// the new sample. Small values smooth more but follow changes more slowly.
+/// Needs no buffer, which makes it the cheapest way to take the jitter off
+/// an ADC reading.
+#[derive(Debug, Clone)]
+pub struct Ema<T> {
+    alpha: f32,
+    value: Option<f32>,
+    _sample: core::marker::PhantomData<T>,
+}
+
+impl<T: Sample> Ema<T> {
+    /// `alpha` is clamped to 0.0..=1.0, where 1.0 passes samples straight
+    /// through.
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            alpha: alpha.clamp(0.0, 1.0),
+            value: None,
+            _sample: core::marker::PhantomData,
+        }
+    }
+
+    /// Current output, or `None` before the first sample
+    pub fn value(&self) -> Option<T> {
+        self.value.map(T::from_f32)
+    }
+}
+
+impl<T: Sample> Filter<T> for Ema<T> {
+    fn update(&mut self, input: T) -> T {
+        let input = input.to_f32();
+        let value = match self.value {
+            // Start from the first sample rather than ramping up from zero
+            None => input,
+            Some(value) => value + self.alpha * (input - value),
+        };
+        self.value = Some(value);
+        T::from_f32(value)
+    }
+
+    fn reset(&mut self) {
+        self.value = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_response_halves_the_gap() {
+        let mut ema = Ema::<f32>::new(0.5);
+        assert_eq!(ema.update(0.0), 0.0);
+        let outputs: [f32; 4] = [100.0; 4].map(|input| ema.update(input));
+        assert_eq!(outputs, [50.0, 75.0, 87.5, 93.75]);
+    }
+
+    #[test]
+    fn integer_output_rounds_but_state_does_not() {
+        let mut ema = Ema::<u16>::new(0.5);
+        ema.update(0);
+        let outputs = [100; 4].map(|input| ema.update(input));
+        assert_eq!(outputs, [50, 75, 88, 94]);
+    }
+
+    #[test]
+    fn starts_from_the_first_sample() {
+        let mut ema = Ema::<i32>::new(0.1);
+        assert_eq!(ema.value(), None);
+        assert_eq!(ema.update(-40), -40);
+        ema.reset();
+        assert_eq!(ema.value(), None);
+        assert_eq!(ema.update(25), 25);
+    }
+
+    #[test]
+    fn alpha_is_clamped() {
+        let mut ema = Ema::<f32>::new(3.0);
+        ema.update(0.0);
+        assert_eq!(ema.update(10.0), 10.0);
+
+        let mut ema = Ema::<f32>::new(-1.0);
+        ema.update(0.0);
+        assert_eq!(ema.update(10.0), 0.0);
+    }
+}
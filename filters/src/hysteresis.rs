@@ -0,0 +1,189 @@
+use crate::{Filter, Sample};
+
+/// Holds its output until the input moves more than `band` away from it
+///
+/// Stops a value that sits on a boundary from flickering, for example a
+/// joystick axis that is only reported when it really moves.
+#[derive(Debug, Clone)]
+pub struct Deadband<T> {
+    band: T,
+    output: Option<T>,
+}
+
+impl<T: Sample> Deadband<T> {
+    pub fn new(band: T) -> Self {
+        Self { band, output: None }
+    }
+
+    /// Current output, or `None` before the first sample
+    pub fn value(&self) -> Option<T> {
+        self.output
+    }
+}
+
+impl<T: Sample> Filter<T> for Deadband<T> {
+    fn update(&mut self, input: T) -> T {
+        let output = match self.output {
+            Some(output) if (input.to_f32() - output.to_f32()).abs() <= self.band.to_f32() => {
+                output
+            }
+            _ => input,
+        };
+        self.output = Some(output);
+        output
+    }
+
+    fn reset(&mut self) {
+        self.output = None;
+    }
+}
+
+/// Turns a value into on/off with separate switching points (a Schmitt
+/// trigger)
+///
+/// Switches on once the input rises above `high` and only back off once it
+/// falls below `low`, so noise around a single threshold doesn't make it
+/// chatter.
+#[derive(Debug, Clone)]
+pub struct Schmitt<T> {
+    low: T,
+    high: T,
+    state: bool,
+}
+
+impl<T: Sample> Schmitt<T> {
+    pub fn new(low: T, high: T) -> Self {
+        Self {
+            low,
+            high,
+            state: false,
+        }
+    }
+
+    /// Feeds in the next sample and returns whether the output is on.
+    pub fn update(&mut self, input: T) -> bool {
+        if input > self.high {
+            self.state = true;
+        } else if input < self.low {
+            self.state = false;
+        }
+        self.state
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.state
+    }
+
+    pub fn reset(&mut self) {
+        self.state = false;
+    }
+}
+
+/// Only lets a new on/off state through once it has been seen for
+/// `samples` updates in a row
+///
+/// For buttons and switches, whose contacts bounce for a few milliseconds
+/// when they change. Call it at a steady rate; with a 5 ms period, 4
+/// samples make a 20 ms debounce time.
+#[derive(Debug, Clone)]
+pub struct Debounce {
+    samples: u8,
+    count: u8,
+    initial: bool,
+    state: bool,
+}
+
+impl Debounce {
+    /// Starts out in the `initial` state, which [`Filter::reset`] also goes
+    /// back to.
+    pub fn new(samples: u8, initial: bool) -> Self {
+        Self {
+            samples,
+            count: 0,
+            initial,
+            state: initial,
+        }
+    }
+
+    pub fn state(&self) -> bool {
+        self.state
+    }
+}
+
+impl Filter<bool> for Debounce {
+    fn update(&mut self, input: bool) -> bool {
+        if input == self.state {
+            self.count = 0;
+        } else {
+            self.count += 1;
+            if self.count >= self.samples {
+                self.state = input;
+                self.count = 0;
+            }
+        }
+        self.state
+    }
+
+    fn reset(&mut self) {
+        self.count = 0;
+        self.state = self.initial;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadband_holds_small_changes() {
+        let mut deadband = Deadband::new(2);
+        assert_eq!(deadband.update(10), 10);
+        assert_eq!(deadband.update(12), 10);
+        assert_eq!(deadband.update(8), 10);
+        assert_eq!(deadband.update(13), 13);
+        assert_eq!(deadband.update(11), 13);
+    }
+
+    #[test]
+    fn schmitt_switches_at_separate_thresholds() {
+        let mut schmitt = Schmitt::new(10, 20);
+        let inputs = [15, 20, 21, 15, 10, 9, 15, 20, 25];
+        let outputs = [false, false, true, true, true, false, false, false, true];
+        for (input, output) in inputs.into_iter().zip(outputs) {
+            assert_eq!(schmitt.update(input), output, "input {input}");
+        }
+        schmitt.reset();
+        assert!(!schmitt.is_on());
+    }
+
+    #[test]
+    fn debounce_waits_for_samples_in_a_row() {
+        let mut debounce = Debounce::new(3, false);
+        assert!(!debounce.update(true));
+        assert!(!debounce.update(true));
+        // A bounce starts the count again
+        assert!(!debounce.update(false));
+        assert!(!debounce.update(true));
+        assert!(!debounce.update(true));
+        assert!(debounce.update(true));
+
+        assert!(debounce.update(false));
+        assert!(debounce.update(false));
+        assert!(!debounce.update(false));
+    }
+
+    #[test]
+    fn debounce_reset_goes_back_to_the_initial_state() {
+        let mut debounce = Debounce::new(1, true);
+        assert!(!debounce.update(false));
+        debounce.reset();
+        assert!(debounce.state());
+
+        let mut debounce = Debounce::new(2, false);
+        debounce.update(true);
+        debounce.reset();
+        // The sample before the reset no longer counts
+        assert!(!debounce.update(true));
+        assert!(debounce.update(true));
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{Filter, Sample};
+
+/// One-dimensional Kalman filter for a value that stays roughly constant
+/// between samples
+///
+/// Weighs each sample against the current estimate by how much each is
+/// trusted. `measurement_noise` is the variance of the sensor readings and
+/// `process_noise` is how much the real value is expected to drift between
+/// samples. Raising `process_noise` follows changes faster but smooths less.
+#[derive(Debug, Clone)]
+pub struct Kalman<T> {
+    process_noise: f32,
+    measurement_noise: f32,
+    estimate: Option<f32>,
+    /// Variance of the current estimate
+    error: f32,
+    _sample: core::marker::PhantomData<T>,
+}
+
+impl<T: Sample> Kalman<T> {
+    pub fn new(process_noise: f32, measurement_noise: f32) -> Self {
+        Self {
+            process_noise,
+            measurement_noise,
+            estimate: None,
+            error: measurement_noise,
+            _sample: core::marker::PhantomData,
+        }
+    }
+
+    /// Current estimate, or `None` before the first sample
+    pub fn estimate(&self) -> Option<T> {
+        self.estimate.map(T::from_f32)
+    }
+
+    /// How much of each new sample currently goes into the estimate, from
+    /// 0.0 to 1.0
+    pub fn gain(&self) -> f32 {
+        let predicted = self.error + self.process_noise;
+        predicted / (predicted + self.measurement_noise)
+    }
+}
+
+impl<T: Sample> Filter<T> for Kalman<T> {
+    fn update(&mut self, input: T) -> T {
+        let input = input.to_f32();
+        let estimate = match self.estimate {
+            None => input,
+            Some(estimate) => {
+                let gain = self.gain();
+                self.error = (1.0 - gain) * (self.error + self.process_noise);
+                estimate + gain * (input - estimate)
+            }
+        };
+        self.estimate = Some(estimate);
+        T::from_f32(estimate)
+    }
+
+    fn reset(&mut self) {
+        self.estimate = None;
+        self.error = self.measurement_noise;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converges_on_a_constant() {
+        let mut kalman = Kalman::<f32>::new(1e-4, 1.0);
+        let mut gain = kalman.gain();
+        for i in 0..200 {
+            // Noise of +-1 around 10
+            let input = if i % 2 == 0 { 9.0 } else { 11.0 };
+            kalman.update(input);
+            if i > 0 {
+                assert!(kalman.gain() <= gain, "gain went up at sample {i}");
+            }
+            gain = kalman.gain();
+        }
+        let estimate = kalman.estimate().unwrap();
+        assert!((estimate - 10.0).abs() < 0.2, "estimate {estimate}");
+        assert!(gain < 0.1);
+    }
+
+    #[test]
+    fn reset_forgets_the_estimate() {
+        let mut kalman = Kalman::<i32>::new(0.01, 4.0);
+        let gain = kalman.gain();
+        for _ in 0..10 {
+            kalman.update(100);
+        }
+        kalman.reset();
+        assert_eq!(kalman.estimate(), None);
+        assert_eq!(kalman.gain(), gain);
+        assert_eq!(kalman.update(-5), -5);
+    }
+}
@@ -0,0 +1,141 @@
+//! # Measurement Filters
+//!
+//! Small fixed-size filters for cleaning up noisy sensor readings, with no
+//! heap and no hardware dependencies, so any example can use them.
+//!
+//! Every filter takes one sample at a time through [`Filter::update`] and
+//! returns its current output. Filters can be chained with
+//! [`Filter::then`], for example a median to throw away spikes followed by
+//! an average to smooth what is left:
+//!
+//! ```ignore
+//! let mut filter = Median::<f32, 5>::new().then(Ema::new(0.3));
+//! let smoothed = filter.update(raw);
+//! ```
+
+#![no_std]
+
+mod ema;
+mod hysteresis;
+mod kalman;
+mod median;
+mod outlier;
+
+pub use ema::Ema;
+pub use hysteresis::{Deadband, Debounce, Schmitt};
+pub use kalman::Kalman;
+pub use median::Median;
+pub use outlier::OutlierReject;
+
+/// A number a filter can work on
+///
+/// Filters that need to average do their maths in `f32` and convert back,
+/// rounding to the nearest value for integer types.
+pub trait Sample: Copy + PartialOrd {
+    fn to_f32(self) -> f32;
+    fn from_f32(value: f32) -> Self;
+}
+
+macro_rules! impl_sample_float {
+    ($($t:ty),*) => {
+        $(
+            impl Sample for $t {
+                fn to_f32(self) -> f32 {
+                    self as f32
+                }
+
+                fn from_f32(value: f32) -> Self {
+                    value as $t
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_sample_int {
+    ($($t:ty),*) => {
+        $(
+            impl Sample for $t {
+                fn to_f32(self) -> f32 {
+                    self as f32
+                }
+
+                fn from_f32(value: f32) -> Self {
+                    // Float to int casts saturate, so this can't wrap
+                    if value < 0.0 {
+                        (value - 0.5) as $t
+                    } else {
+                        (value + 0.5) as $t
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_sample_float!(f32, f64);
+impl_sample_int!(u8, u16, u32, i8, i16, i32);
+
+/// Something that turns a stream of samples into a cleaner one
+pub trait Filter<T> {
+    /// Feeds in the next sample and returns the filtered value.
+    fn update(&mut self, input: T) -> T;
+
+    /// Forgets all past samples.
+    fn reset(&mut self);
+
+    /// Runs the output of this filter through `next`.
+    fn then<F: Filter<T>>(self, next: F) -> Chain<Self, F>
+    where
+        Self: Sized,
+    {
+        Chain {
+            first: self,
+            second: next,
+        }
+    }
+}
+
+/// Two filters run one after the other, see [`Filter::then`]
+#[derive(Debug, Clone)]
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<T, A: Filter<T>, B: Filter<T>> Filter<T> for Chain<A, B> {
+    fn update(&mut self, input: T) -> T {
+        let middle = self.first.update(input);
+        self.second.update(middle)
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.second.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_samples_round_to_nearest() {
+        assert_eq!(u8::from_f32(2.5), 3);
+        assert_eq!(i16::from_f32(-2.5), -3);
+        assert_eq!(i32::from_f32(-2.4), -2);
+        assert_eq!(u8::from_f32(300.0), u8::MAX);
+        assert_eq!(u16::from_f32(-1.0), 0);
+    }
+
+    #[test]
+    fn chained_filters_run_in_order() {
+        // The median drops the spike before the average sees it
+        let mut filter = Median::<f32, 3>::new().then(Ema::new(0.5));
+        let outputs: [f32; 4] = [10.0, 10.0, 1000.0, 20.0].map(|input| filter.update(input));
+        assert_eq!(outputs, [10.0, 10.0, 10.0, 15.0]);
+
+        filter.reset();
+        assert_eq!(filter.update(40.0), 40.0);
+    }
+}
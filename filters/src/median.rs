@@ -0,0 +1,111 @@
+use crate::Filter;
+
+/// Median of the last `N` samples
+///
+/// Good at removing single bad readings, like an ultrasonic sensor catching
+/// the wrong echo, without blurring real changes the way an average does.
+/// Output lags the input by about `N / 2` samples. Until `N` samples have
+/// arrived, the median is taken over the ones seen so far, using the upper
+/// of the two middle values when there is an even number of them.
+#[derive(Debug, Clone)]
+pub struct Median<T, const N: usize> {
+    window: [Option<T>; N],
+    next: usize,
+}
+
+impl<T: Copy + PartialOrd, const N: usize> Median<T, N> {
+    pub fn new() -> Self {
+        const { assert!(N > 0) };
+        Self {
+            window: [None; N],
+            next: 0,
+        }
+    }
+
+    /// Number of samples currently in the window
+    pub fn len(&self) -> usize {
+        self.window.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.window[0].is_none()
+    }
+}
+
+impl<T: Copy + PartialOrd, const N: usize> Default for Median<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy + PartialOrd, const N: usize> Filter<T> for Median<T, N> {
+    fn update(&mut self, input: T) -> T {
+        self.window[self.next] = Some(input);
+        self.next = (self.next + 1) % N;
+
+        // Insertion sort, N is small
+        let mut sorted = [input; N];
+        let mut len = 0;
+        for value in self.window.iter().flatten() {
+            let mut i = len;
+            while i > 0 && sorted[i - 1] > *value {
+                sorted[i] = sorted[i - 1];
+                i -= 1;
+            }
+            sorted[i] = *value;
+            len += 1;
+        }
+        sorted[len / 2]
+    }
+
+    fn reset(&mut self) {
+        self.window = [None; N];
+        self.next = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn odd_window_removes_a_spike() {
+        let mut median = Median::<u16, 5>::new();
+        for input in [10, 11, 250, 9, 10, 12] {
+            assert!((9..=12).contains(&median.update(input)), "input {input}");
+        }
+    }
+
+    #[test]
+    fn even_window_takes_the_upper_middle() {
+        let mut median = Median::<i32, 4>::new();
+        assert_eq!(median.update(1), 1);
+        assert_eq!(median.update(4), 4);
+        assert_eq!(median.update(2), 2);
+        assert_eq!(median.update(3), 3);
+        // The 1 drops out of the window
+        assert_eq!(median.update(5), 4);
+    }
+
+    #[test]
+    fn follows_a_real_step() {
+        let mut median = Median::<f32, 3>::new();
+        let outputs: [f32; 5] = [0.0, 0.0, 1.0, 1.0, 1.0].map(|input| median.update(input));
+        assert_eq!(outputs, [0.0, 0.0, 0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn counts_samples_until_reset() {
+        let mut median = Median::<u8, 3>::new();
+        assert!(median.is_empty());
+        median.update(1);
+        median.update(2);
+        assert_eq!(median.len(), 2);
+        median.update(3);
+        median.update(4);
+        assert_eq!(median.len(), 3);
+        median.reset();
+        assert!(median.is_empty());
+        assert_eq!(median.update(7), 7);
+    }
+}
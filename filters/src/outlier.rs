@@ -0,0 +1,83 @@
+use crate::{Filter, Sample};
+
+/// Drops samples that jump too far from the last accepted one
+///
+/// A rejected sample is replaced by the last accepted value. If the input
+/// keeps disagreeing for `max_rejects` samples in a row, the jump is taken
+/// to be real and accepted, so the filter can't get stuck on an old value.
+#[derive(Debug, Clone)]
+pub struct OutlierReject<T> {
+    max_jump: T,
+    max_rejects: u8,
+    rejects: u8,
+    last: Option<T>,
+}
+
+impl<T: Sample> OutlierReject<T> {
+    pub fn new(max_jump: T, max_rejects: u8) -> Self {
+        Self {
+            max_jump,
+            max_rejects,
+            rejects: 0,
+            last: None,
+        }
+    }
+
+    /// Whether the most recent sample was rejected
+    pub fn rejected_last(&self) -> bool {
+        self.rejects > 0
+    }
+}
+
+impl<T: Sample> Filter<T> for OutlierReject<T> {
+    fn update(&mut self, input: T) -> T {
+        if let Some(last) = self.last {
+            let jump = (input.to_f32() - last.to_f32()).abs();
+            if jump > self.max_jump.to_f32() && self.rejects < self.max_rejects {
+                self.rejects += 1;
+                return last;
+            }
+        }
+        self.rejects = 0;
+        self.last = Some(input);
+        input
+    }
+
+    fn reset(&mut self) {
+        self.rejects = 0;
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_single_jumps() {
+        let mut filter = OutlierReject::new(5, 2);
+        assert_eq!(filter.update(10), 10);
+        assert_eq!(filter.update(30), 10);
+        assert!(filter.rejected_last());
+        assert_eq!(filter.update(14), 14);
+        assert!(!filter.rejected_last());
+    }
+
+    #[test]
+    fn accepts_a_jump_that_persists() {
+        let mut filter = OutlierReject::new(5.0, 2);
+        filter.update(10.0);
+        assert_eq!(filter.update(30.0), 10.0);
+        assert_eq!(filter.update(30.0), 10.0);
+        assert_eq!(filter.update(30.0), 30.0);
+        assert_eq!(filter.update(31.0), 31.0);
+    }
+
+    #[test]
+    fn reset_takes_any_first_sample() {
+        let mut filter = OutlierReject::new(1, 3);
+        filter.update(0);
+        filter.reset();
+        assert_eq!(filter.update(100), 100);
+    }
+}
@@ -4,6 +4,7 @@
 use core::fmt::Write;
 use embedded_hal::{delay::DelayNs, digital::InputPin};
 use embedded_hal_0_2::adc::OneShot;
+use filters::{Deadband, Debounce, Ema, Filter};
 use hal::block::ImageDef;
 use heapless::String;
 use panic_halt as _;
@@ -73,6 +74,11 @@ fn main() -> ! {
     // VRY pin
     let mut adc_pin_0 = hal::adc::AdcPin::new(pins.gpio26).unwrap();
 
+    // Smooth out ADC noise, then only report moves of more than 100 counts
+    let mut vrx_filter = Ema::new(0.3).then(Deadband::new(100u16));
+    let mut vry_filter = Ema::new(0.3).then(Deadband::new(100u16));
+    let mut btn_filter = Debounce::new(2, false);
+
     let mut prev_vrx: u16 = 0;
     let mut prev_vry: u16 = 0;
     let mut prev_btn_state = false;
@@ -88,17 +94,19 @@ fn main() -> ! {
             continue;
         };
 
-        if vrx.abs_diff(prev_vrx) > 100 {
+        let vrx = vrx_filter.update(vrx);
+        if vrx != prev_vrx {
             prev_vrx = vrx;
             print_vals = true;
         }
 
-        if vry.abs_diff(prev_vry) > 100 {
+        let vry = vry_filter.update(vry);
+        if vry != prev_vry {
             prev_vry = vry;
             print_vals = true;
         }
 
-        let btn_state = btn.is_low().unwrap();
+        let btn_state = btn_filter.update(btn.is_low().unwrap());
         if btn_state && !prev_btn_state {
             let _ = serial.write("Button Pressed\r\n".as_bytes());
             print_vals = true;
@@ -0,0 +1,227 @@
+//! # MAX7219 Driver
+//!
+//! The MAX7219 drives either eight 7-segment digits or one 8x8 LED matrix.
+//! Several chips can be daisy-chained (DOUT of one goes to DIN of the next)
+//! and share a single chip select line. `N` is the number of chips in the
+//! chain; chip `0` is the one wired to the Pico.
+//!
+//! The driver keeps a copy of all digit registers in RAM. Drawing only
+//! touches that buffer, [`Max7219::flush`] sends it to the chips.
+//!
+//! Calls naming a chip or digit past the end of the chain do nothing.
+
+#![no_std]
+
+use embedded_hal::spi::SpiDevice;
+
+mod matrix;
+pub mod seven_segment;
+
+/// The MAX7219 register map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Register {
+    NoOp = 0x00,
+    Digit0 = 0x01,
+    Digit1 = 0x02,
+    Digit2 = 0x03,
+    Digit3 = 0x04,
+    Digit4 = 0x05,
+    Digit5 = 0x06,
+    Digit6 = 0x07,
+    Digit7 = 0x08,
+    DecodeMode = 0x09,
+    Intensity = 0x0A,
+    ScanLimit = 0x0B,
+    Shutdown = 0x0C,
+    DisplayTest = 0x0F,
+}
+
+impl Register {
+    const DIGITS: [Register; 8] = [
+        Register::Digit0,
+        Register::Digit1,
+        Register::Digit2,
+        Register::Digit3,
+        Register::Digit4,
+        Register::Digit5,
+        Register::Digit6,
+        Register::Digit7,
+    ];
+}
+
+/// Number of digit registers (or matrix rows) on each chip
+pub const DIGITS: usize = 8;
+
+/// Highest value accepted by the intensity register
+pub const MAX_INTENSITY: u8 = 0x0F;
+
+pub struct Max7219<SPI, const N: usize> {
+    spi: SPI,
+    buffer: [[u8; DIGITS]; N],
+}
+
+impl<SPI: SpiDevice, const N: usize> Max7219<SPI, N> {
+    /// Wakes up every chip in the chain and blanks the display.
+    ///
+    /// BCD decoding is turned off on all chips, so every digit register is a
+    /// raw segment (or row) bitmap.
+    pub fn new(spi: SPI) -> Result<Self, SPI::Error> {
+        let mut max7219 = Self {
+            spi,
+            buffer: [[0; DIGITS]; N],
+        };
+
+        max7219.write_all(Register::DisplayTest, 0)?;
+        max7219.write_all(Register::ScanLimit, (DIGITS - 1) as u8)?;
+        max7219.write_all(Register::DecodeMode, 0)?;
+        max7219.write_all(Register::Intensity, 0x04)?;
+        max7219.flush()?;
+        max7219.set_power(true)?;
+
+        Ok(max7219)
+    }
+
+    /// Writes one register on a single chip, leaving the others untouched.
+    pub fn write_register(
+        &mut self,
+        device: usize,
+        register: Register,
+        data: u8,
+    ) -> Result<(), SPI::Error> {
+        let Some(position) = Self::chain_position(device) else {
+            return Ok(());
+        };
+        let mut frame = [[Register::NoOp as u8, 0]; N];
+        frame[position] = [register as u8, data];
+        self.spi.write(frame.as_flattened())
+    }
+
+    /// Writes the same value into a register on every chip.
+    pub fn write_all(&mut self, register: Register, data: u8) -> Result<(), SPI::Error> {
+        let frame = [[register as u8, data]; N];
+        self.spi.write(frame.as_flattened())
+    }
+
+    /// Switches all chips between normal operation and shutdown.
+    ///
+    /// Register contents are kept while shut down.
+    pub fn set_power(&mut self, on: bool) -> Result<(), SPI::Error> {
+        self.write_all(Register::Shutdown, on as u8)
+    }
+
+    /// Sets the brightness of a single chip, from `0` to `MAX_INTENSITY`.
+    pub fn set_intensity(&mut self, device: usize, intensity: u8) -> Result<(), SPI::Error> {
+        self.write_register(device, Register::Intensity, intensity.min(MAX_INTENSITY))
+    }
+
+    /// Sets the brightness of every chip, from `0` to `MAX_INTENSITY`.
+    pub fn set_intensity_all(&mut self, intensity: u8) -> Result<(), SPI::Error> {
+        self.write_all(Register::Intensity, intensity.min(MAX_INTENSITY))
+    }
+
+    /// Blanks the buffer. Call [`Max7219::flush`] to update the display.
+    pub fn clear_buffer(&mut self) {
+        self.buffer = [[0; DIGITS]; N];
+    }
+
+    /// Sets the raw bitmap for one digit (or matrix row) in the buffer.
+    pub fn set_digit(&mut self, device: usize, digit: usize, value: u8) {
+        if let Some(bitmap) = self.buffer.get_mut(device).and_then(|d| d.get_mut(digit)) {
+            *bitmap = value;
+        }
+    }
+
+    /// The bitmap of one digit in the buffer, blank if there is no such
+    /// digit.
+    pub fn digit(&self, device: usize, digit: usize) -> u8 {
+        self.buffer
+            .get(device)
+            .and_then(|d| d.get(digit))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Sends the whole buffer to the chips.
+    pub fn flush(&mut self) -> Result<(), SPI::Error> {
+        for (digit, register) in Register::DIGITS.iter().enumerate() {
+            let mut frame = [[0u8; 2]; N];
+            // Back to front, like `chain_position`
+            for (bytes, digits) in frame.iter_mut().rev().zip(&self.buffer) {
+                *bytes = [*register as u8, digits[digit]];
+            }
+            self.spi.write(frame.as_flattened())?;
+        }
+        Ok(())
+    }
+
+    /// Gives back the SPI device.
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    /// Where `device` goes in a frame, or `None` if the chain is shorter.
+    ///
+    /// The first two bytes clocked out end up in the chip furthest down the
+    /// chain, so the frame is built back to front.
+    fn chain_position(device: usize) -> Option<usize> {
+        (device < N).then(|| N - 1 - device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use embedded_hal::spi::{ErrorType, Operation};
+
+    use super::*;
+
+    /// SPI bus that keeps the last frame written
+    #[derive(Default)]
+    struct Bus {
+        frame: [u8; 4],
+        frames: usize,
+    }
+
+    impl ErrorType for Bus {
+        type Error = Infallible;
+    }
+
+    impl SpiDevice for Bus {
+        fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+            for operation in operations {
+                if let Operation::Write(bytes) = operation {
+                    self.frame.copy_from_slice(bytes);
+                    self.frames += 1;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn frames_are_built_back_to_front() {
+        let mut max7219: Max7219<_, 2> = Max7219::new(Bus::default()).unwrap();
+        max7219.write_register(0, Register::Intensity, 3).unwrap();
+        let bus = max7219.release();
+        assert_eq!(bus.frame, [0x00, 0, 0x0A, 3]);
+    }
+
+    #[test]
+    fn devices_past_the_chain_are_ignored() {
+        let mut max7219: Max7219<_, 2> = Max7219::new(Bus::default()).unwrap();
+        let frames = max7219.spi.frames;
+        max7219.write_register(2, Register::Intensity, 3).unwrap();
+        max7219.set_intensity(usize::MAX, 3).unwrap();
+        assert_eq!(max7219.spi.frames, frames);
+
+        max7219.set_digit(2, 0, 0xFF);
+        max7219.set_digit(0, DIGITS, 0xFF);
+        assert_eq!(max7219.digit(2, 0), 0);
+        assert_eq!(max7219.digit(0, DIGITS), 0);
+        assert!(!max7219.write_str(2, "1"));
+        assert!(!max7219.write_integer(2, 1));
+        assert_eq!(max7219.buffer, [[0; DIGITS]; 2]);
+    }
+}
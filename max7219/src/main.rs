@@ -0,0 +1,112 @@
+//! # MAX7219 Matrix Example
+//!
+//! Scrolls a line of text across a chain of four 8x8 LED matrix modules
+//! (the common "FC-16" 32x8 boards).
+//!
+//! Wiring: DIN to GPIO 7, CLK to GPIO 6 and CS to GPIO 5, which is the same
+//! SPI0 pinout as the RFID examples. The modules want 5V on VCC, but accept
+//! the Pico's 3.3V logic levels.
+
+#![no_std]
+#![no_main]
+
+use embedded_graphics::mono_font::ascii::FONT_5X8;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::{Baseline, Text};
+use embedded_hal::delay::DelayNs;
+use embedded_hal_bus::spi::ExclusiveDevice;
+use hal::block::ImageDef;
+use hal::fugit::RateExtU32;
+use max7219::Max7219;
+use panic_halt as _;
+use rp235x_hal::{self as hal, Clock};
+
+/// Tell the Boot ROM about our application
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
+
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Number of 8x8 modules in the chain
+const MODULES: usize = 4;
+
+const MESSAGE: &str = "Hello, Rust!";
+
+/// Time each scroll step stays on screen
+const SCROLL_DELAY_MS: u32 = 60;
+
+#[hal::entry]
+fn main() -> ! {
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+    let sio = hal::Sio::new(pac.SIO);
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    let mut timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    // SPI Setup
+    let spi_mosi = pins.gpio7.into_function::<hal::gpio::FunctionSpi>();
+    let spi_miso = pins.gpio4.into_function::<hal::gpio::FunctionSpi>();
+    let spi_sclk = pins.gpio6.into_function::<hal::gpio::FunctionSpi>();
+    let spi_bus = hal::spi::Spi::<_, _, _, 8>::new(pac.SPI0, (spi_mosi, spi_miso, spi_sclk));
+
+    let spi_cs = pins.gpio5.into_push_pull_output();
+    let spi = spi_bus.init(
+        &mut pac.RESETS,
+        clocks.peripheral_clock.freq(),
+        1_000.kHz(),
+        embedded_hal::spi::MODE_0,
+    );
+
+    let spi = ExclusiveDevice::new(spi, spi_cs, timer).unwrap();
+    let mut display: Max7219<_, MODULES> = Max7219::new(spi).unwrap();
+    display.set_intensity_all(2).unwrap();
+
+    let style = MonoTextStyle::new(&FONT_5X8, BinaryColor::On);
+    let panel_width = display.size().width as i32;
+    let text_width = (MESSAGE.len() as u32 * FONT_5X8.character_size.width) as i32;
+
+    loop {
+        // Start just off the right edge and move left until the text is gone
+        for x in (-text_width..=panel_width).rev() {
+            display.clear(BinaryColor::Off).unwrap();
+            Text::with_baseline(MESSAGE, Point::new(x, 0), style, Baseline::Top)
+                .draw(&mut display)
+                .unwrap();
+            display.flush().unwrap();
+            timer.delay_ms(SCROLL_DELAY_MS);
+        }
+    }
+}
+
+// Program metadata for `picotool info`.
+// This isn't needed, but it's recomended to have these minimal entries.
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"MAX7219 Matrix"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
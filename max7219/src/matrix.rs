@@ -0,0 +1,60 @@
+//! `embedded-graphics` support for 8x8 LED matrix modules.
+//!
+//! The chain is treated as one long panel, 8 pixels high and `8 * N` pixels
+//! wide, with chip `0` on the left. Each digit register is one row, with D7
+//! as the leftmost column. If your modules show the image mirrored or upside
+//! down, rotate the panel or flip the drawing instead.
+
+use core::convert::Infallible;
+
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_hal::spi::SpiDevice;
+
+use crate::{Max7219, DIGITS};
+
+impl<SPI: SpiDevice, const N: usize> OriginDimensions for Max7219<SPI, N> {
+    fn size(&self) -> Size {
+        Size::new((N * 8) as u32, DIGITS as u32)
+    }
+}
+
+impl<SPI: SpiDevice, const N: usize> DrawTarget for Max7219<SPI, N> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let width = (N * 8) as i32;
+        for Pixel(point, color) in pixels {
+            if point.x < 0 || point.x >= width || point.y < 0 || point.y >= DIGITS as i32 {
+                continue;
+            }
+            let device = point.x as usize / 8;
+            let mask = 0x80 >> (point.x as usize % 8);
+            let Some(row) = self
+                .buffer
+                .get_mut(device)
+                .and_then(|rows| rows.get_mut(point.y as usize))
+            else {
+                continue;
+            };
+            match color {
+                BinaryColor::On => *row |= mask,
+                BinaryColor::Off => *row &= !mask,
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let fill = match color {
+            BinaryColor::On => 0xFF,
+            BinaryColor::Off => 0x00,
+        };
+        self.buffer = [[fill; DIGITS]; N];
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+//! Segment encoding for 7-segment displays.
+//!
+//! With BCD decoding turned off each digit register holds one bit per
+//! segment:
+//!
+//! ```text
+//!  D7 D6 D5 D4 D3 D2 D1 D0
+//!  DP  A  B  C  D  E  F  G
+//! ```
+//!
+//! Digit `0` is the rightmost digit on the common 8-digit modules.
+
+use embedded_hal::spi::SpiDevice;
+
+use crate::{Max7219, DIGITS};
+
+/// The decimal point segment
+pub const DP: u8 = 0b1000_0000;
+
+const DIGIT_SEGMENTS: [u8; 16] = [
+    0b0111_1110, // 0
+    0b0011_0000, // 1
+    0b0110_1101, // 2
+    0b0111_1001, // 3
+    0b0011_0011, // 4
+    0b0101_1011, // 5
+    0b0101_1111, // 6
+    0b0111_0000, // 7
+    0b0111_1111, // 8
+    0b0111_1011, // 9
+    0b0111_0111, // A
+    0b0001_1111, // b
+    0b0100_1110, // C
+    0b0011_1101, // d
+    0b0100_1111, // E
+    0b0100_0111, // F
+];
+
+/// Segments for a single hexadecimal digit
+pub const fn encode_digit(value: u8) -> u8 {
+    DIGIT_SEGMENTS[(value & 0x0F) as usize]
+}
+
+/// Segments for a character, or a blank digit if it can't be shown
+pub const fn encode_char(c: char) -> u8 {
+    match c {
+        '0'..='9' => encode_digit(c as u8 - b'0'),
+        'a'..='f' => encode_digit(c as u8 - b'a' + 10),
+        'A'..='F' => encode_digit(c as u8 - b'A' + 10),
+        'H' | 'h' => 0b0011_0111,
+        'O' => encode_digit(0),
+        'L' | 'l' => 0b0000_1110,
+        'n' => 0b0001_0101,
+        'o' => 0b0001_1101,
+        'P' | 'p' => 0b0110_0111,
+        'r' => 0b0000_0101,
+        't' => 0b0000_1111,
+        'U' => 0b0011_1110,
+        'u' => 0b0001_1100,
+        '-' => 0b0000_0001,
+        '_' => 0b0000_1000,
+        _ => 0,
+    }
+}
+
+/// Encodes `text` right-aligned into `digits`, where `digits[0]` is the
+/// rightmost digit.
+///
+/// A `.` is merged into the decimal point of the character before it rather
+/// than taking up a digit of its own. Returns `false` if the text did not
+/// fit and was cut off on the left.
+pub fn encode_str(text: &str, digits: &mut [u8; DIGITS]) -> bool {
+    *digits = [0; DIGITS];
+    let mut pos = 0;
+    let mut pending_dp = false;
+    for c in text.chars().rev() {
+        if c == '.' {
+            if pending_dp {
+                // Two dots in a row, give the first one a blank digit
+                if pos == DIGITS {
+                    return false;
+                }
+                digits[pos] = DP;
+                pos += 1;
+            }
+            pending_dp = true;
+            continue;
+        }
+        if pos == DIGITS {
+            return false;
+        }
+        digits[pos] = encode_char(c) | if pending_dp { DP } else { 0 };
+        pending_dp = false;
+        pos += 1;
+    }
+    if pending_dp {
+        if pos == DIGITS {
+            return false;
+        }
+        digits[pos] = DP;
+    }
+    true
+}
+
+/// Encodes a fixed-point number right-aligned into `digits`.
+///
+/// `value` is the number scaled by `10^decimals`, so `1234` with two decimals
+/// shows `12.34`. Leading zeros are blanked, apart from the one in front of
+/// the decimal point. Returns `false` if the number does not fit, in which
+/// case `digits` shows dashes.
+pub fn encode_fixed(value: i32, decimals: u8, digits: &mut [u8; DIGITS]) -> bool {
+    *digits = [0; DIGITS];
+    let negative = value < 0;
+    let mut remaining = value.unsigned_abs();
+    let mut pos = 0;
+
+    loop {
+        if pos == DIGITS {
+            *digits = [encode_char('-'); DIGITS];
+            return false;
+        }
+        digits[pos] = encode_digit((remaining % 10) as u8);
+        remaining /= 10;
+        pos += 1;
+        if remaining == 0 && pos > decimals as usize {
+            break;
+        }
+    }
+
+    if decimals > 0 {
+        digits[decimals as usize] |= DP;
+    }
+
+    if negative {
+        if pos == DIGITS {
+            *digits = [encode_char('-'); DIGITS];
+            return false;
+        }
+        digits[pos] = encode_char('-');
+    }
+    true
+}
+
+/// These return `false` when the value didn't fit, like [`encode_str`] and
+/// [`encode_fixed`], and when `device` is past the end of the chain.
+impl<SPI: SpiDevice, const N: usize> Max7219<SPI, N> {
+    /// Puts `text` on one chip's digits, see [`encode_str`].
+    pub fn write_str(&mut self, device: usize, text: &str) -> bool {
+        self.buffer
+            .get_mut(device)
+            .is_some_and(|digits| encode_str(text, digits))
+    }
+
+    /// Puts an integer on one chip's digits.
+    pub fn write_integer(&mut self, device: usize, value: i32) -> bool {
+        self.write_fixed(device, value, 0)
+    }
+
+    /// Puts a fixed-point number on one chip's digits, see [`encode_fixed`].
+    pub fn write_fixed(&mut self, device: usize, value: i32, decimals: u8) -> bool {
+        self.buffer
+            .get_mut(device)
+            .is_some_and(|digits| encode_fixed(value, decimals, digits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLANK: u8 = 0;
+
+    fn chars(text: &str) -> [u8; DIGITS] {
+        let mut digits = [BLANK; DIGITS];
+        for (digit, c) in digits.iter_mut().zip(text.chars().rev()) {
+            *digit = encode_char(c);
+        }
+        digits
+    }
+
+    #[test]
+    fn strings_are_right_aligned() {
+        let mut digits = [0xFF; DIGITS];
+        assert!(encode_str("12", &mut digits));
+        assert_eq!(digits, chars("12"));
+        assert_eq!(digits[2..], [BLANK; DIGITS - 2]);
+    }
+
+    #[test]
+    fn dots_join_the_character_before() {
+        let mut digits = [0; DIGITS];
+        assert!(encode_str("1.5", &mut digits));
+        assert_eq!(
+            digits[..3],
+            [encode_char('5'), encode_char('1') | DP, BLANK]
+        );
+
+        assert!(encode_str("1..5", &mut digits));
+        assert_eq!(digits[..3], [encode_char('5'), DP, encode_char('1') | DP]);
+
+        assert!(encode_str(".5", &mut digits));
+        assert_eq!(digits[..2], [encode_char('5'), DP]);
+
+        assert!(encode_str("12345678.", &mut digits));
+        assert_eq!(digits[0], encode_char('8') | DP);
+    }
+
+    #[test]
+    fn long_strings_are_cut_on_the_left() {
+        let mut digits = [0; DIGITS];
+        assert!(!encode_str("123456789", &mut digits));
+        assert_eq!(digits, chars("23456789"));
+        // A leading dot needs a digit of its own
+        assert!(!encode_str(".12345678", &mut digits));
+    }
+
+    #[test]
+    fn fixed_point_numbers() {
+        let mut digits = [0; DIGITS];
+        assert!(encode_fixed(1234, 2, &mut digits));
+        let mut expected = chars("1234");
+        expected[2] |= DP;
+        assert_eq!(digits, expected);
+
+        // Leading zeros up to the decimal point stay
+        assert!(encode_fixed(5, 2, &mut digits));
+        let mut expected = chars("005");
+        expected[2] |= DP;
+        assert_eq!(digits, expected);
+
+        assert!(encode_fixed(0, 0, &mut digits));
+        assert_eq!(digits, chars("0"));
+    }
+
+    #[test]
+    fn negative_numbers_get_a_minus() {
+        let mut digits = [0; DIGITS];
+        assert!(encode_fixed(-42, 0, &mut digits));
+        assert_eq!(digits, chars("-42"));
+
+        assert!(encode_fixed(-5, 1, &mut digits));
+        let mut expected = chars("-05");
+        expected[1] |= DP;
+        assert_eq!(digits, expected);
+    }
+
+    #[test]
+    fn numbers_that_dont_fit_show_dashes() {
+        let mut digits = [0; DIGITS];
+        assert!(encode_fixed(99_999_999, 0, &mut digits));
+        assert!(encode_fixed(-9_999_999, 0, &mut digits));
+
+        for (value, decimals) in [(100_000_000, 0), (-10_000_000, 0), (i32::MIN, 3), (1, 8)] {
+            assert!(!encode_fixed(value, decimals, &mut digits), "{value}");
+            assert_eq!(digits, chars("--------"));
+        }
+    }
+}
@@ -0,0 +1,375 @@
+//! Sector trailers and their access bits.
+//!
+//! The last block of every sector holds key A, three bytes of access bits,
+//! one free byte and key B. The access bits give each block three bits,
+//! C1 C2 C3, and store them twice, once inverted. A trailer whose copies
+//! don't match locks the sector for good, as does one that stops every key
+//! from changing the trailer again, so trailers are checked here before
+//! they are written.
+//!
+//! The three data block conditions cover one block each in a 4 block
+//! sector, and five blocks each in the 16 block sectors of a 4K card.
+
+use core::fmt;
+
+use crate::{Key, BLOCK_SIZE};
+
+/// Which keys are allowed to do something
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Access {
+    Never,
+    KeyA,
+    KeyB,
+    KeyAOrB,
+}
+
+impl Access {
+    /// Whether key B is one of the allowed keys
+    pub fn allows_key_b(self) -> bool {
+        matches!(self, Access::KeyB | Access::KeyAOrB)
+    }
+
+    pub fn allows_key_a(self) -> bool {
+        matches!(self, Access::KeyA | Access::KeyAOrB)
+    }
+
+    /// The same with key B taken out
+    fn without_key_b(self) -> Self {
+        match self {
+            Access::KeyAOrB | Access::KeyA => Access::KeyA,
+            Access::KeyB | Access::Never => Access::Never,
+        }
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Access::Never => "never",
+            Access::KeyA => "A",
+            Access::KeyB => "B",
+            Access::KeyAOrB => "A|B",
+        })
+    }
+}
+
+/// What each key may do with a data block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DataPermissions {
+    pub read: Access,
+    pub write: Access,
+    pub increment: Access,
+    /// Also covers transfer and restore
+    pub decrement: Access,
+}
+
+/// What each key may do with the sector trailer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TrailerPermissions {
+    /// Key A itself can never be read back
+    pub key_a_write: Access,
+    pub access_bits_read: Access,
+    pub access_bits_write: Access,
+    pub key_b_read: Access,
+    pub key_b_write: Access,
+}
+
+/// The C1 C2 C3 bits of one block, as `0b{C1}{C2}{C3}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Condition(u8);
+
+impl Condition {
+    pub const fn new(c1: bool, c2: bool, c3: bool) -> Self {
+        Condition((c1 as u8) << 2 | (c2 as u8) << 1 | c3 as u8)
+    }
+
+    pub const fn c1(self) -> bool {
+        self.0 & 0b100 != 0
+    }
+
+    pub const fn c2(self) -> bool {
+        self.0 & 0b010 != 0
+    }
+
+    pub const fn c3(self) -> bool {
+        self.0 & 0b001 != 0
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:03b}", self.0)
+    }
+}
+
+/// The access bits were not stored with a matching inverted copy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InvalidAccessBits;
+
+/// Decoded access bits of one sector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AccessConditions {
+    /// Conditions of the three data block groups
+    pub data: [Condition; 3],
+    pub trailer: Condition,
+}
+
+impl AccessConditions {
+    /// What blank cards ship with, `FF 07 80`: key A does everything, and
+    /// key B is readable, which means it can't be used as a key.
+    pub const TRANSPORT: Self = Self {
+        data: [Condition(0b000); 3],
+        trailer: Condition(0b001),
+    };
+
+    /// Decodes access bytes 6 to 8 of a trailer, checking them against
+    /// their inverted copies.
+    pub fn from_bytes(bytes: [u8; 3]) -> Result<Self, InvalidAccessBits> {
+        let [b6, b7, b8] = bytes;
+        let c1 = b7 >> 4;
+        let c2 = b8 & 0x0F;
+        let c3 = b8 >> 4;
+        if !b6 & 0x0F != c1 || !b6 >> 4 != c2 || !b7 & 0x0F != c3 {
+            return Err(InvalidAccessBits);
+        }
+
+        let condition = |block: u8| {
+            let bit = |nibble: u8| nibble >> block & 1 != 0;
+            Condition::new(bit(c1), bit(c2), bit(c3))
+        };
+        Ok(Self {
+            data: [condition(0), condition(1), condition(2)],
+            trailer: condition(3),
+        })
+    }
+
+    /// Encodes the conditions with their inverted copies, for bytes 6 to 8
+    /// of a trailer.
+    pub fn to_bytes(&self) -> [u8; 3] {
+        let conditions = [self.data[0], self.data[1], self.data[2], self.trailer];
+        let nibble = |bit: fn(Condition) -> bool| {
+            conditions
+                .iter()
+                .enumerate()
+                .fold(0u8, |nibble, (block, &condition)| {
+                    nibble | (bit(condition) as u8) << block
+                })
+        };
+        let c1 = nibble(Condition::c1);
+        let c2 = nibble(Condition::c2);
+        let c3 = nibble(Condition::c3);
+        [
+            (!c2 & 0x0F) << 4 | (!c1 & 0x0F),
+            c1 << 4 | (!c3 & 0x0F),
+            c3 << 4 | c2,
+        ]
+    }
+
+    /// Whether key B can be read from the trailer. Key B is then just
+    /// stored data and the card refuses to work with it as a key.
+    pub fn key_b_readable(&self) -> bool {
+        self.trailer_permissions().key_b_read != Access::Never
+    }
+
+    /// Permissions of data block group `group`, 0 to 2
+    pub fn data_permissions(&self, group: usize) -> DataPermissions {
+        use Access::*;
+        let (read, write, increment, decrement) = match self.data[group.min(2)].0 {
+            0b000 => (KeyAOrB, KeyAOrB, KeyAOrB, KeyAOrB),
+            0b010 => (KeyAOrB, Never, Never, Never),
+            0b100 => (KeyAOrB, KeyB, Never, Never),
+            0b110 => (KeyAOrB, KeyB, KeyB, KeyAOrB),
+            0b001 => (KeyAOrB, Never, Never, KeyAOrB),
+            0b011 => (KeyB, KeyB, Never, Never),
+            0b101 => (KeyB, Never, Never, Never),
+            _ => (Never, Never, Never, Never),
+        };
+        let permissions = DataPermissions {
+            read,
+            write,
+            increment,
+            decrement,
+        };
+        if self.key_b_readable() {
+            DataPermissions {
+                read: read.without_key_b(),
+                write: write.without_key_b(),
+                increment: increment.without_key_b(),
+                decrement: decrement.without_key_b(),
+            }
+        } else {
+            permissions
+        }
+    }
+
+    pub fn trailer_permissions(&self) -> TrailerPermissions {
+        use Access::*;
+        let (key_a_write, access_bits_read, access_bits_write, key_b_read, key_b_write) =
+            match self.trailer.0 {
+                0b000 => (KeyA, KeyA, Never, KeyA, KeyA),
+                0b010 => (Never, KeyA, Never, KeyA, Never),
+                0b100 => (KeyB, KeyAOrB, Never, Never, KeyB),
+                0b110 => (Never, KeyAOrB, Never, Never, Never),
+                0b001 => (KeyA, KeyA, KeyA, KeyA, KeyA),
+                0b011 => (KeyB, KeyAOrB, KeyB, Never, KeyB),
+                0b101 => (Never, KeyAOrB, KeyB, Never, Never),
+                _ => (Never, KeyAOrB, Never, Never, Never),
+            };
+        TrailerPermissions {
+            key_a_write,
+            access_bits_read,
+            access_bits_write,
+            key_b_read,
+            key_b_write,
+        }
+    }
+
+    /// Whether the trailer could never be changed again: no key may write
+    /// the access bits and neither key can be replaced.
+    pub fn is_permanent(&self) -> bool {
+        let trailer = self.trailer_permissions();
+        trailer.access_bits_write == Access::Never
+            && trailer.key_a_write == Access::Never
+            && trailer.key_b_write == Access::Never
+    }
+}
+
+impl fmt::Display for AccessConditions {
+    /// One line per data block group and one for the trailer
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for group in 0..3 {
+            writeln!(
+                f,
+                "data {} ({}): {}",
+                group,
+                self.data[group],
+                self.data_permissions(group)
+            )?;
+        }
+        write!(
+            f,
+            "trailer ({}): {}",
+            self.trailer,
+            self.trailer_permissions()
+        )
+    }
+}
+
+impl fmt::Display for DataPermissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "read {}, write {}, increment {}, decrement {}",
+            self.read, self.write, self.increment, self.decrement
+        )
+    }
+}
+
+impl fmt::Display for TrailerPermissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "key A write {}, access bits read {} write {}, key B read {} write {}",
+            self.key_a_write,
+            self.access_bits_read,
+            self.access_bits_write,
+            self.key_b_read,
+            self.key_b_write
+        )
+    }
+}
+
+/// Contents of a sector trailer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Trailer {
+    /// Reads back as zeros, the card never gives key A out
+    pub key_a: Key,
+    pub access: AccessConditions,
+    /// Free for the application, `0x69` on blank cards
+    pub user_byte: u8,
+    pub key_b: Key,
+}
+
+impl Trailer {
+    pub fn from_block(block: &[u8; BLOCK_SIZE]) -> Result<Self, InvalidAccessBits> {
+        let mut key_a = [0; 6];
+        let mut key_b = [0; 6];
+        key_a.copy_from_slice(&block[0..6]);
+        key_b.copy_from_slice(&block[10..16]);
+        Ok(Self {
+            key_a,
+            access: AccessConditions::from_bytes([block[6], block[7], block[8]])?,
+            user_byte: block[9],
+            key_b,
+        })
+    }
+
+    pub fn to_block(&self) -> [u8; BLOCK_SIZE] {
+        let mut block = [0; BLOCK_SIZE];
+        block[0..6].copy_from_slice(&self.key_a);
+        block[6..9].copy_from_slice(&self.access.to_bytes());
+        block[9] = self.user_byte;
+        block[10..16].copy_from_slice(&self.key_b);
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_common_access_bytes() {
+        for bytes in [[0xFF, 0x07, 0x80], [0x78, 0x77, 0x88], [0x7F, 0x07, 0x88]] {
+            let access = AccessConditions::from_bytes(bytes).unwrap();
+            assert_eq!(access.to_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn decodes_transport_configuration() {
+        assert_eq!(
+            AccessConditions::from_bytes([0xFF, 0x07, 0x80]),
+            Ok(AccessConditions::TRANSPORT)
+        );
+        let access = AccessConditions::from_bytes([0x7F, 0x07, 0x88]).unwrap();
+        assert_eq!(access.trailer, Condition::new(false, true, true));
+    }
+
+    #[test]
+    fn rejects_any_mismatched_inverted_nibble() {
+        let bytes = [0xFF, 0x07, 0x80];
+        // Byte 6 holds ~C2 and ~C1, byte 7 ~C3 and C1, and byte 8 C3 and C2,
+        // so flipping any one nibble breaks its inverted copy
+        for byte in 0..3 {
+            for mask in [0x0F, 0xF0] {
+                let mut bytes = bytes;
+                bytes[byte] ^= mask;
+                assert_eq!(
+                    AccessConditions::from_bytes(bytes),
+                    Err(InvalidAccessBits),
+                    "byte {byte}, mask {mask:#04x}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn only_locked_trailers_are_permanent() {
+        let with_trailer = |trailer| AccessConditions {
+            trailer,
+            ..AccessConditions::TRANSPORT
+        };
+        assert!(with_trailer(Condition::new(true, true, false)).is_permanent());
+        assert!(with_trailer(Condition::new(true, true, true)).is_permanent());
+        assert!(!with_trailer(Condition::new(false, false, true)).is_permanent());
+        assert!(!with_trailer(Condition::new(false, true, true)).is_permanent());
+    }
+}
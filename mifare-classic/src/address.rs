@@ -0,0 +1,223 @@
+//! Sector and block numbers.
+//!
+//! A MIFARE Classic card is addressed by absolute block number, but keys
+//! and access rights belong to sectors. On 1K cards every sector has 4
+//! blocks. 4K cards have 32 of those followed by 8 big sectors of 16
+//! blocks, so `sector * 4` only works for the first 128 blocks.
+
+/// Size of one block in bytes
+pub const BLOCK_SIZE: usize = 16;
+
+/// Sectors below this have 4 blocks, the rest 16
+const SMALL_SECTORS: u8 = 32;
+
+/// Memory layout of a card
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// MIFARE Mini, 320 bytes
+    Mini,
+    /// MIFARE Classic 1K
+    Classic1K,
+    /// MIFARE Classic 4K
+    Classic4K,
+}
+
+impl Layout {
+    /// Works out the layout from the SAK byte the card answered `select`
+    /// with.
+    pub const fn from_sak(sak: u8) -> Option<Self> {
+        match sak {
+            0x09 => Some(Layout::Mini),
+            0x08 | 0x88 => Some(Layout::Classic1K),
+            0x18 | 0x98 | 0xB8 => Some(Layout::Classic4K),
+            _ => None,
+        }
+    }
+
+    pub const fn sector_count(self) -> u8 {
+        match self {
+            Layout::Mini => 5,
+            Layout::Classic1K => 16,
+            Layout::Classic4K => 40,
+        }
+    }
+
+    pub const fn block_count(self) -> u16 {
+        match self {
+            Layout::Mini => 20,
+            Layout::Classic1K => 64,
+            Layout::Classic4K => 256,
+        }
+    }
+
+    /// Card size in bytes, the size of a raw dump
+    pub const fn size(self) -> usize {
+        self.block_count() as usize * BLOCK_SIZE
+    }
+
+    /// Every sector of the card, in order
+    pub fn sectors(self) -> impl Iterator<Item = Sector> {
+        (0..self.sector_count()).map(Sector)
+    }
+
+    /// Every block of the card, in order
+    pub fn blocks(self) -> impl Iterator<Item = Block> {
+        (0..self.block_count()).map(|index| Block(index as u8))
+    }
+}
+
+/// A sector number that exists on the card it was made for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Sector(u8);
+
+impl Sector {
+    pub const fn new(index: u8, layout: Layout) -> Option<Self> {
+        if index < layout.sector_count() {
+            Some(Sector(index))
+        } else {
+            None
+        }
+    }
+
+    pub const fn index(self) -> u8 {
+        self.0
+    }
+
+    /// Number of blocks in the sector, trailer included
+    pub const fn block_count(self) -> u8 {
+        if self.0 < SMALL_SECTORS {
+            4
+        } else {
+            16
+        }
+    }
+
+    pub const fn first_block(self) -> Block {
+        if self.0 < SMALL_SECTORS {
+            Block(self.0 * 4)
+        } else {
+            Block(SMALL_SECTORS * 4 + (self.0 - SMALL_SECTORS) * 16)
+        }
+    }
+
+    /// Last block of the sector, which holds its keys and access bits
+    pub const fn trailer(self) -> Block {
+        Block(self.first_block().0 + (self.block_count() - 1))
+    }
+
+    /// Block at `offset` within the sector
+    pub const fn block(self, offset: u8) -> Option<Block> {
+        if offset < self.block_count() {
+            Some(Block(self.first_block().0 + offset))
+        } else {
+            None
+        }
+    }
+
+    /// Every block of the sector, trailer last
+    pub fn blocks(self) -> impl Iterator<Item = Block> {
+        let first = self.first_block().0;
+        (first..=self.trailer().0).map(Block)
+    }
+
+    /// Blocks that can hold data, leaving out the trailer and the
+    /// manufacturer block
+    pub fn data_blocks(self) -> impl Iterator<Item = Block> {
+        self.blocks()
+            .filter(|block| !block.is_trailer() && !block.is_manufacturer())
+    }
+}
+
+/// An absolute block number that exists on the card it was made for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Block(u8);
+
+impl Block {
+    pub const fn new(index: u8, layout: Layout) -> Option<Self> {
+        if (index as u16) < layout.block_count() {
+            Some(Block(index))
+        } else {
+            None
+        }
+    }
+
+    /// Absolute block number, as sent to the card
+    pub const fn index(self) -> u8 {
+        self.0
+    }
+
+    pub const fn sector(self) -> Sector {
+        if self.0 < SMALL_SECTORS * 4 {
+            Sector(self.0 / 4)
+        } else {
+            Sector(SMALL_SECTORS + (self.0 - SMALL_SECTORS * 4) / 16)
+        }
+    }
+
+    /// Position within the sector
+    pub const fn offset(self) -> u8 {
+        self.0 - self.sector().first_block().0
+    }
+
+    pub const fn is_trailer(self) -> bool {
+        self.0 == self.sector().trailer().0
+    }
+
+    /// Block 0, holding the UID and manufacturer data. It is read-only on
+    /// genuine cards.
+    pub const fn is_manufacturer(self) -> bool {
+        self.0 == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sector(index: u8, layout: Layout) -> Sector {
+        Sector::new(index, layout).unwrap()
+    }
+
+    #[test]
+    fn last_sector_of_1k() {
+        let last = sector(15, Layout::Classic1K);
+        assert_eq!(last.first_block().index(), 60);
+        assert_eq!(last.trailer().index(), 63);
+        assert_eq!(Sector::new(16, Layout::Classic1K), None);
+        assert_eq!(Block::new(64, Layout::Classic1K), None);
+    }
+
+    #[test]
+    fn big_sectors_of_4k() {
+        let layout = Layout::Classic4K;
+        for (index, first, trailer) in [(31, 124, 127), (32, 128, 143), (39, 240, 255)] {
+            let sector = sector(index, layout);
+            assert_eq!(sector.first_block().index(), first, "sector {index}");
+            assert_eq!(sector.trailer().index(), trailer, "sector {index}");
+            assert_eq!(sector.blocks().count(), sector.block_count() as usize);
+        }
+        assert_eq!(Sector::new(40, layout), None);
+    }
+
+    #[test]
+    fn blocks_know_their_sector() {
+        let layout = Layout::Classic4K;
+        for (block, index, offset) in [(127, 31, 3), (128, 32, 0), (240, 39, 0), (255, 39, 15)] {
+            let block = Block::new(block, layout).unwrap();
+            assert_eq!(block.sector(), sector(index, layout));
+            assert_eq!(block.offset(), offset);
+        }
+        assert!(Block::new(255, layout).unwrap().is_trailer());
+        assert!(!Block::new(254, layout).unwrap().is_trailer());
+    }
+
+    #[test]
+    fn every_block_is_in_one_sector() {
+        for layout in [Layout::Mini, Layout::Classic1K, Layout::Classic4K] {
+            assert!(layout
+                .sectors()
+                .flat_map(Sector::blocks)
+                .eq(layout.blocks()));
+        }
+    }
+}
@@ -0,0 +1,444 @@
+//! Card dumps.
+//!
+//! A [`Dump`] holds what could be read of a card, which blocks that was,
+//! and the keys that opened each sector. Its raw bytes are the `.mfd`
+//! format most MIFARE tools use: every block in order, with the blocks that
+//! couldn't be read left as zeros. [`Dump::json`] describes the same card
+//! in the layout of the Proxmark client's JSON dumps, leaving unread
+//! blocks out. Both can be read back to restore a card.
+
+use core::fmt;
+
+use crate::hex::parse_hex;
+use crate::json::{for_each_string, BadJson};
+use crate::{Block, FoundKey, Key, KeyMap, KeyType, Layout, Sector, Trailer, BLOCK_SIZE};
+
+/// Longest UID, that of a triple size card
+const MAX_UID: usize = 10;
+
+/// What a dump holds in place of a key that couldn't be read back
+const UNKNOWN_KEY: Key = [0; 6];
+
+/// Why a dump file couldn't be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DumpError {
+    /// A raw dump is not the size of any card
+    Size,
+    /// The file is not JSON
+    Json,
+    /// A UID, block or key in a JSON dump is not hex of the right length,
+    /// or a block number doesn't exist on the card
+    Field,
+    /// A JSON dump has no UID
+    NoUid,
+}
+
+impl From<BadJson> for DumpError {
+    fn from(_: BadJson) -> Self {
+        DumpError::Json
+    }
+}
+
+/// Everything read from one card
+#[derive(Clone)]
+pub struct Dump {
+    uid: [u8; MAX_UID],
+    uid_len: usize,
+    data: [u8; Layout::Classic4K.size()],
+    read: [bool; Layout::Classic4K.block_count() as usize],
+    keys: KeyMap,
+    atqa: Option<[u8; 2]>,
+    sak: Option<u8>,
+}
+
+impl Dump {
+    /// An empty dump of the card `uid`, with no blocks read yet
+    pub fn new(uid: &[u8], layout: Layout) -> Self {
+        let uid_len = uid.len().min(MAX_UID);
+        let mut dump = Self {
+            uid: [0; MAX_UID],
+            uid_len,
+            data: [0; Layout::Classic4K.size()],
+            read: [false; Layout::Classic4K.block_count() as usize],
+            keys: KeyMap::new(layout),
+            atqa: None,
+            sak: None,
+        };
+        dump.uid[..uid_len].copy_from_slice(&uid[..uid_len]);
+        dump
+    }
+
+    /// Reads a raw `.mfd` dump, where every block counts as read.
+    ///
+    /// Raw dumps don't say how long the UID is. It is taken as 4 bytes if
+    /// the check byte after them matches, as on most cards, and 7 if not.
+    /// The key A of every trailer with valid access bits goes into the key
+    /// map, unless it is all zeros: readers can't read key A back, so that
+    /// is what a dump shows for a sector opened with key B only.
+    pub fn from_mfd(bytes: &[u8]) -> Result<Self, DumpError> {
+        let layout = [Layout::Mini, Layout::Classic1K, Layout::Classic4K]
+            .into_iter()
+            .find(|layout| layout.size() == bytes.len())
+            .ok_or(DumpError::Size)?;
+        let bcc = bytes[..4].iter().fold(0, |bcc, byte| bcc ^ byte);
+        let uid_len = if bytes[4] == bcc { 4 } else { 7 };
+
+        let mut dump = Self::new(&bytes[..uid_len], layout);
+        dump.data[..bytes.len()].copy_from_slice(bytes);
+        dump.read = [true; Layout::Classic4K.block_count() as usize];
+        for sector in layout.sectors() {
+            if let Some(trailer) = dump.trailer(sector).filter(|t| t.key_a != UNKNOWN_KEY) {
+                let key = FoundKey {
+                    key_type: KeyType::A,
+                    key: trailer.key_a,
+                };
+                dump.keys.set(sector, Some(key));
+            }
+        }
+        Ok(dump)
+    }
+
+    /// Reads a JSON dump as written by [`Dump::json`].
+    ///
+    /// The card type comes from the SAK if the dump has one, and otherwise
+    /// from the highest block number.
+    pub fn from_json(text: &str) -> Result<Self, DumpError> {
+        let mut uid = [0; MAX_UID];
+        let mut uid_len = None;
+        let mut atqa = None;
+        let mut sak = None;
+        let mut data = [0; Layout::Classic4K.size()];
+        let mut read = [false; Layout::Classic4K.block_count() as usize];
+        let mut keys = [None; Layout::Classic4K.sector_count() as usize];
+
+        for_each_string(text, |path, value| {
+            match path {
+                ["Card", "UID"] => {
+                    let len = value.len() / 2;
+                    if !(1..=MAX_UID).contains(&len) {
+                        return Err(DumpError::Field);
+                    }
+                    parse_hex(value, &mut uid[..len]).ok_or(DumpError::Field)?;
+                    uid_len = Some(len);
+                }
+                ["Card", "ATQA"] => {
+                    let mut bytes = [0; 2];
+                    parse_hex(value, &mut bytes).ok_or(DumpError::Field)?;
+                    atqa = Some(bytes);
+                }
+                ["Card", "SAK"] => {
+                    let mut byte = [0];
+                    parse_hex(value, &mut byte).ok_or(DumpError::Field)?;
+                    sak = Some(byte[0]);
+                }
+                ["blocks", index] => {
+                    let index: usize = index.parse().map_err(|_| DumpError::Field)?;
+                    // Checked first, so the block's offset can't overflow
+                    let read = read.get_mut(index).ok_or(DumpError::Field)?;
+                    let start = index * BLOCK_SIZE;
+                    parse_hex(value, &mut data[start..start + BLOCK_SIZE])
+                        .ok_or(DumpError::Field)?;
+                    *read = true;
+                }
+                ["SectorKeys", sector, key_name @ ("KeyA" | "KeyB")] => {
+                    let sector: usize = sector.parse().map_err(|_| DumpError::Field)?;
+                    let found: &mut Option<FoundKey> =
+                        keys.get_mut(sector).ok_or(DumpError::Field)?;
+                    let mut key = [0; 6];
+                    parse_hex(value, &mut key).ok_or(DumpError::Field)?;
+                    // Key A is what the restore writes into the trailer
+                    if *key_name == "KeyA" {
+                        *found = Some(FoundKey {
+                            key_type: KeyType::A,
+                            key,
+                        });
+                    } else if found.is_none() {
+                        *found = Some(FoundKey {
+                            key_type: KeyType::B,
+                            key,
+                        });
+                    }
+                }
+                _ => {}
+            }
+            Ok(())
+        })?;
+
+        let last_block = read.iter().rposition(|&read| read).unwrap_or(0);
+        let layout = sak.and_then(Layout::from_sak).unwrap_or(match last_block {
+            0..=19 => Layout::Mini,
+            20..=63 => Layout::Classic1K,
+            _ => Layout::Classic4K,
+        });
+        if last_block >= layout.block_count() as usize {
+            return Err(DumpError::Field);
+        }
+
+        let uid_len = uid_len.ok_or(DumpError::NoUid)?;
+        let mut dump = Self::new(&uid[..uid_len], layout);
+        dump.data = data;
+        dump.read = read;
+        dump.atqa = atqa;
+        dump.sak = sak;
+        for sector in layout.sectors() {
+            dump.keys.set(sector, keys[sector.index() as usize]);
+        }
+        Ok(dump)
+    }
+
+    pub fn uid(&self) -> &[u8] {
+        &self.uid[..self.uid_len]
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.keys.layout()
+    }
+
+    /// Keys that opened each sector
+    pub fn keys(&self) -> &KeyMap {
+        &self.keys
+    }
+
+    pub fn keys_mut(&mut self) -> &mut KeyMap {
+        &mut self.keys
+    }
+
+    /// Contents of `block`, if it was read
+    pub fn block(&self, block: Block) -> Option<&[u8; BLOCK_SIZE]> {
+        if !self.read[block.index() as usize] {
+            return None;
+        }
+        let start = block.index() as usize * BLOCK_SIZE;
+        self.data[start..start + BLOCK_SIZE].try_into().ok()
+    }
+
+    pub fn set_block(&mut self, block: Block, data: &[u8; BLOCK_SIZE]) {
+        let start = block.index() as usize * BLOCK_SIZE;
+        self.data[start..start + BLOCK_SIZE].copy_from_slice(data);
+        self.read[block.index() as usize] = true;
+    }
+
+    /// Number of blocks that were read
+    pub fn read_count(&self) -> usize {
+        self.layout()
+            .blocks()
+            .filter(|block| self.read[block.index() as usize])
+            .count()
+    }
+
+    /// Trailer of `sector`, if it was read and its access bits are valid
+    pub fn trailer(&self, sector: Sector) -> Option<Trailer> {
+        Trailer::from_block(self.block(sector.trailer())?).ok()
+    }
+
+    /// The dump as a raw `.mfd` file
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.layout().size()]
+    }
+
+    /// SAK the card answered its select with, if it was recorded
+    pub fn sak(&self) -> Option<u8> {
+        self.sak
+    }
+
+    pub fn set_sak(&mut self, sak: u8) {
+        self.sak = Some(sak);
+    }
+
+    /// ATQA the card answered its request with, in the order it was sent,
+    /// if it was recorded
+    pub fn atqa(&self) -> Option<[u8; 2]> {
+        self.atqa
+    }
+
+    pub fn set_atqa(&mut self, atqa: [u8; 2]) {
+        self.atqa = Some(atqa);
+    }
+
+    /// The dump as JSON, for writing with `write!`
+    pub fn json(&self) -> Json<'_> {
+        Json(self)
+    }
+}
+
+/// JSON description of a [`Dump`], made by [`Dump::json`]
+pub struct Json<'a>(&'a Dump);
+
+impl fmt::Display for Json<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dump = self.0;
+        f.write_str("{\n  \"Created\": \"mifare-classic\",\n")?;
+        f.write_str("  \"FileType\": \"mfcard\",\n")?;
+        f.write_str("  \"Card\": {\n    \"UID\": \"")?;
+        write_hex(f, dump.uid())?;
+        f.write_str("\"")?;
+        if let Some(atqa) = dump.atqa() {
+            f.write_str(",\n    \"ATQA\": \"")?;
+            write_hex(f, &atqa)?;
+            f.write_str("\"")?;
+        }
+        if let Some(sak) = dump.sak() {
+            f.write_str(",\n    \"SAK\": \"")?;
+            write_hex(f, &[sak])?;
+            f.write_str("\"")?;
+        }
+        f.write_str("\n  },\n  \"blocks\": {")?;
+
+        let mut separator = "\n";
+        for block in dump.layout().blocks() {
+            if let Some(data) = dump.block(block) {
+                write!(f, "{}    \"{}\": \"", separator, block.index())?;
+                write_hex(f, data)?;
+                f.write_str("\"")?;
+                separator = ",\n";
+            }
+        }
+        f.write_str("\n  },\n  \"SectorKeys\": {")?;
+
+        separator = "\n";
+        for (sector, found) in dump.keys().iter() {
+            write!(f, "{}    \"{}\": {{", separator, sector.index())?;
+            separator = ",\n";
+
+            let trailer = dump.trailer(sector);
+            let key_a = found
+                .filter(|found| found.key_type == KeyType::A)
+                .map(|found| found.key);
+            // Key B reads back as itself when it isn't used as a key
+            let key_b = found
+                .filter(|found| found.key_type == KeyType::B)
+                .map(|found| found.key)
+                .or_else(|| {
+                    trailer
+                        .filter(|trailer| trailer.access.key_b_readable())
+                        .map(|trailer| trailer.key_b)
+                });
+
+            let mut field = "\n";
+            if let Some(key) = key_a {
+                write!(f, "{}      \"KeyA\": \"", field)?;
+                write_hex(f, &key)?;
+                f.write_str("\"")?;
+                field = ",\n";
+            }
+            if let Some(key) = key_b {
+                write!(f, "{}      \"KeyB\": \"", field)?;
+                write_hex(f, &key)?;
+                f.write_str("\"")?;
+                field = ",\n";
+            }
+            if let Some(trailer) = trailer {
+                write!(f, "{}      \"AccessConditions\": \"", field)?;
+                write_hex(f, &trailer.access.to_bytes())?;
+                write_hex(f, &[trailer.user_byte])?;
+                f.write_str("\"")?;
+                field = ",\n";
+            }
+            if field == "\n" {
+                f.write_str("}")?;
+            } else {
+                f.write_str("\n    }")?;
+            }
+        }
+        f.write_str("\n  }\n}\n")
+    }
+}
+
+/// Uppercase hex without separators, as JSON dumps have it
+fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    for byte in bytes {
+        write!(f, "{:02X}", byte)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_block_numbers_past_the_largest_card() {
+        for json in [
+            r#"{"Card": {"UID": "DEADBEEF"}, "blocks": {"256": "00000000000000000000000000000000"}}"#,
+            r#"{"Card": {"UID": "DEADBEEF"}, "blocks": {"1152921504606846976": "00000000000000000000000000000000"}}"#,
+            r#"{"Card": {"UID": "DEADBEEF"}, "blocks": {"18446744073709551615": "00000000000000000000000000000000"}}"#,
+        ] {
+            assert_eq!(Dump::from_json(json).err(), Some(DumpError::Field));
+        }
+    }
+
+    #[test]
+    fn leaves_unknown_key_a_out_of_the_key_map() {
+        let layout = Layout::Classic1K;
+        let mut mfd = [0u8; Layout::Classic1K.size()];
+        mfd[..5].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF, 0x22]);
+        for sector in layout.sectors() {
+            let trailer = Trailer {
+                // Sector 2 was opened with key B
+                key_a: if sector.index() == 2 {
+                    UNKNOWN_KEY
+                } else {
+                    [0xA0 + sector.index(); 6]
+                },
+                access: crate::AccessConditions::TRANSPORT,
+                user_byte: 0x69,
+                key_b: [0xFF; 6],
+            };
+            let start = sector.trailer().index() as usize * BLOCK_SIZE;
+            mfd[start..start + BLOCK_SIZE].copy_from_slice(&trailer.to_block());
+        }
+
+        let dump = Dump::from_mfd(&mfd).unwrap();
+        assert_eq!(dump.uid(), &[0xDE, 0xAD, 0xBE, 0xEF]);
+        for sector in layout.sectors() {
+            let key = dump.keys().get(sector);
+            if sector.index() == 2 {
+                assert_eq!(key, None);
+            } else {
+                assert_eq!(key.map(|found| found.key), Some([0xA0 + sector.index(); 6]));
+            }
+        }
+    }
+
+    #[test]
+    fn reads_back_its_own_json() {
+        let layout = Layout::Classic1K;
+        let mut dump = Dump::new(&[0xDE, 0xAD, 0xBE, 0xEF], layout);
+        dump.set_atqa([0x04, 0x00]);
+        dump.set_sak(0x08);
+        dump.set_block(Block::new(5, layout).unwrap(), &[7; BLOCK_SIZE]);
+
+        let mut json = [0u8; 1024];
+        let mut writer = Buffer {
+            bytes: &mut json,
+            len: 0,
+        };
+        fmt::Write::write_fmt(&mut writer, format_args!("{}", dump.json())).unwrap();
+        let len = writer.len;
+        let back = Dump::from_json(core::str::from_utf8(&json[..len]).unwrap()).unwrap();
+
+        assert_eq!(back.uid(), dump.uid());
+        assert_eq!(back.layout(), layout);
+        assert_eq!((back.atqa(), back.sak()), (Some([0x04, 0x00]), Some(0x08)));
+        assert_eq!(back.as_bytes(), dump.as_bytes());
+        assert_eq!(back.read_count(), 1);
+    }
+
+    struct Buffer<'a> {
+        bytes: &'a mut [u8],
+        len: usize,
+    }
+
+    impl fmt::Write for Buffer<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.len + s.len();
+            self.bytes
+                .get_mut(self.len..end)
+                .ok_or(fmt::Error)?
+                .copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+}
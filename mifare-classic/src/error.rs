@@ -0,0 +1,95 @@
+use core::fmt;
+
+use crate::NdefError;
+
+/// Why a card operation failed
+///
+/// `E` is the error of the bus the MFRC522 is connected to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// The card didn't answer in time, usually because it was taken away
+    Timeout,
+    /// An answer from the card failed its CRC check
+    Crc,
+    /// More than one card answered at once
+    Collision,
+    /// The card refused the command. For a write this usually means the
+    /// access bits don't allow it.
+    Nak,
+    /// The key was wrong, or the access bits don't allow this key
+    Auth,
+    /// Any other framing or protocol error reported by the MFRC522
+    Protocol,
+    /// Talking to the MFRC522 itself failed
+    Transport(E),
+    /// The MFRC522 driver can only authenticate with key A
+    KeyBUnsupported,
+    /// A different card answered when the session selected its card again
+    WrongCard,
+    /// A trailer's access bits don't match their inverted copy. Writing it
+    /// would lock the sector for good.
+    InvalidAccessBits,
+    /// The trailer would stop every key from changing it again, or the
+    /// write would set lock or one time bits of an Ultralight tag
+    WouldLock,
+    /// The check byte after the UID in a manufacturer block doesn't
+    /// match. A card with such a block no longer answers.
+    BadBcc,
+    /// The block doesn't hold a value in the value block format
+    InvalidValueBlock,
+    /// Sector 0 doesn't hold an application directory
+    NoMad,
+    /// The application directory's CRC doesn't match
+    InvalidMad,
+    /// The NDEF data on the card can't be read, or the message doesn't fit
+    Ndef(NdefError),
+    /// The tag doesn't have this feature
+    Unsupported,
+}
+
+impl<E> Error<E> {
+    /// Whether the card has to be selected again before it answers. It
+    /// drops back to idle after a failed authentication or a refused
+    /// command.
+    pub fn needs_reselect(&self) -> bool {
+        matches!(self, Error::Nak | Error::Auth)
+    }
+}
+
+impl<E> From<mfrc522::Error<E>> for Error<E> {
+    fn from(error: mfrc522::Error<E>) -> Self {
+        match error {
+            mfrc522::Error::Comm(error) => Error::Transport(error),
+            mfrc522::Error::Timeout => Error::Timeout,
+            mfrc522::Error::Crc => Error::Crc,
+            mfrc522::Error::Collision => Error::Collision,
+            mfrc522::Error::Nak => Error::Nak,
+            _ => Error::Protocol,
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Timeout => f.write_str("card did not answer"),
+            Error::Crc => f.write_str("CRC error"),
+            Error::Collision => f.write_str("more than one card in the field"),
+            Error::Nak => f.write_str("card refused the command"),
+            Error::Auth => f.write_str("authentication failed"),
+            Error::Protocol => f.write_str("protocol error"),
+            Error::Transport(error) => write!(f, "reader error: {:?}", error),
+            Error::KeyBUnsupported => f.write_str("key B authentication not supported"),
+            Error::WrongCard => f.write_str("a different card answered"),
+            Error::InvalidAccessBits => f.write_str("access bits are not valid"),
+            Error::WouldLock => f.write_str("write would lock the card for good"),
+            Error::BadBcc => f.write_str("UID check byte doesn't match"),
+            Error::InvalidValueBlock => f.write_str("not a value block"),
+            Error::NoMad => f.write_str("no application directory"),
+            Error::InvalidMad => f.write_str("application directory CRC doesn't match"),
+            Error::Ndef(error) => write!(f, "{}", error),
+            Error::Unsupported => f.write_str("not supported by this tag"),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Raw ISO 14443-3 frames, for commands the MFRC522 driver doesn't send
+//! itself.
+
+use mfrc522::comm::Interface;
+use mfrc522::{GenericUid, Initialized, Mfrc522, Uid};
+
+use crate::Selected;
+
+/// Lower nibble of the 4 bit answer accepting a command
+pub(crate) const ACK: u8 = 0x0A;
+
+/// Longest command sent with [`exchange`], a select with the UID
+const MAX_COMMAND: usize = 7;
+
+/// MIFARE write command, followed by the block number
+pub(crate) const WRITE: u8 = 0xA0;
+
+/// Value block commands, each followed by the block number. All but
+/// transfer then take a 4 byte operand.
+pub(crate) const DECREMENT: u8 = 0xC0;
+pub(crate) const INCREMENT: u8 = 0xC1;
+pub(crate) const RESTORE: u8 = 0xC2;
+pub(crate) const TRANSFER: u8 = 0xB0;
+
+/// Request of idle cards, sent as a short frame of 7 bits
+pub(crate) const REQA: u8 = 0x26;
+
+/// Select commands of the three cascade levels, followed by
+/// [`ANTICOLLISION`] to ask for the UID or [`SELECT_UID`] and the UID
+pub(crate) const SELECT: [u8; 3] = [0x93, 0x95, 0x97];
+pub(crate) const ANTICOLLISION: u8 = 0x20;
+pub(crate) const SELECT_UID: u8 = 0x70;
+
+/// First UID byte of a cascade level that isn't the last
+pub(crate) const CASCADE_TAG: u8 = 0x88;
+
+/// SAK bit saying the UID goes on in the next cascade level
+pub(crate) const UID_INCOMPLETE: u8 = 0x04;
+
+/// Ultralight and NTAG commands
+pub(crate) const READ_PAGES: u8 = 0x30;
+pub(crate) const WRITE_PAGE: u8 = 0xA2;
+pub(crate) const GET_VERSION: u8 = 0x60;
+pub(crate) const PWD_AUTH: u8 = 0x1B;
+pub(crate) const READ_CNT: u8 = 0x39;
+
+/// Authenticates for `block` with `key` as key A.
+///
+/// The driver's `mf_authenticate` sends the first four bytes of the `Uid`
+/// it is given, but cards with a 7 or 10 byte UID authenticate with their
+/// last four, so it is given just those.
+pub(crate) fn authenticate<E, COMM: Interface<Error = E>>(
+    rfid: &mut Mfrc522<COMM, Initialized>,
+    card: &Selected,
+    block: u8,
+    key: &[u8; 6],
+) -> Result<(), mfrc522::Error<E>> {
+    let uid = card.uid();
+    let mut nuid = [0; 4];
+    nuid.copy_from_slice(&uid[uid.len() - 4..]);
+    let uid = Uid::Single(GenericUid::new(nuid, card.sak()));
+    rfid.mf_authenticate(&uid, block, key)
+}
+
+/// Fills the last two bytes of `frame` with the CRC_A of the rest.
+pub(crate) fn append_crc(frame: &mut [u8]) {
+    let (data, crc) = frame.split_at_mut(frame.len() - 2);
+    crc.copy_from_slice(&crc_a(data));
+}
+
+/// Whether the last two bytes of `frame` are the CRC_A of the rest.
+pub(crate) fn check_crc(frame: &[u8]) -> bool {
+    let Some(len) = frame.len().checked_sub(2) else {
+        return false;
+    };
+    crc_a(&frame[..len]) == frame[len..]
+}
+
+/// CRC of ISO 14443-3 type A frames, low byte first
+pub(crate) fn crc_a(data: &[u8]) -> [u8; 2] {
+    let crc = data.iter().fold(0x6363u16, |crc, &byte| {
+        let byte = byte ^ crc as u8;
+        let byte = byte ^ byte << 4;
+        crc >> 8 ^ (byte as u16) << 8 ^ (byte as u16) << 3 ^ (byte as u16) >> 4
+    });
+    crc.to_le_bytes()
+}
+
+/// Sends a raw frame, of which `last_bits` bits of the last byte if not
+/// 0, and checks that the card accepts it.
+pub(crate) fn expect_ack<E, COMM: Interface<Error = E>>(
+    rfid: &mut Mfrc522<COMM, Initialized>,
+    frame: &[u8],
+    last_bits: u8,
+) -> Result<(), mfrc522::Error<E>> {
+    let answer = rfid.transceive::<1>(frame, last_bits, 0)?;
+    // Both the acknowledgement and a refusal are a lone 4 bit answer
+    if answer.valid_bytes != 1 || answer.valid_bits != 4 {
+        return Err(mfrc522::Error::IncompleteFrame);
+    }
+    if answer.buffer[0] & 0x0F != ACK {
+        return Err(mfrc522::Error::Nak);
+    }
+    Ok(())
+}
+
+/// Sends `command` followed by its CRC and gives the `RX` byte answer,
+/// whose last two bytes are checked as its CRC.
+pub(crate) fn exchange<const RX: usize, E, COMM: Interface<Error = E>>(
+    rfid: &mut Mfrc522<COMM, Initialized>,
+    command: &[u8],
+) -> Result<[u8; RX], mfrc522::Error<E>> {
+    let mut frame = [0; MAX_COMMAND + 2];
+    let len = command.len() + 2;
+    frame[..command.len()].copy_from_slice(command);
+    append_crc(&mut frame[..len]);
+
+    let answer = rfid.transceive::<RX>(&frame[..len], 0, 0)?;
+    // A refusal is a lone 4 bit answer, without CRC
+    if answer.valid_bytes == 1 && answer.valid_bits == 4 {
+        return Err(mfrc522::Error::Nak);
+    }
+    if answer.valid_bytes != RX || answer.valid_bits != 0 {
+        return Err(mfrc522::Error::IncompleteFrame);
+    }
+    if !check_crc(&answer.buffer) {
+        return Err(mfrc522::Error::Crc);
+    }
+    Ok(answer.buffer)
+}
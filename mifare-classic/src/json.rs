@@ -0,0 +1,161 @@
+//! Just enough JSON to read dumps back.
+//!
+//! Dumps only hold objects of strings, so the reader hands every string
+//! value to a callback together with the keys leading to it, and checks
+//! the rest only for being well formed. Escapes in strings are left as
+//! they are.
+
+/// Deepest nesting of objects and arrays the reader follows
+const MAX_DEPTH: usize = 8;
+
+/// The text is not JSON, or nested too deep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BadJson;
+
+/// Calls `visit` with the object keys leading to each string value in
+/// `text`, and the value.
+pub(crate) fn for_each_string<'t, E: From<BadJson>>(
+    text: &'t str,
+    mut visit: impl FnMut(&[&'t str], &'t str) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut reader = Reader { text, pos: 0 };
+    let mut path = [""; MAX_DEPTH];
+    reader.value(&mut path, 0, &mut visit)?;
+    reader.skip_whitespace();
+    if reader.pos != text.len() {
+        return Err(BadJson.into());
+    }
+    Ok(())
+}
+
+struct Reader<'t> {
+    text: &'t str,
+    pos: usize,
+}
+
+impl<'t> Reader<'t> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Next byte that isn't whitespace, without taking it
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), BadJson> {
+        if self.peek() != Some(byte) {
+            return Err(BadJson);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<&'t str, BadJson> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        let bytes = self.text.as_bytes();
+        while let Some(&byte) = bytes.get(self.pos) {
+            match byte {
+                b'"' => {
+                    self.pos += 1;
+                    return Ok(&self.text[start..self.pos - 1]);
+                }
+                // Skips the escaped character, which may be a quote
+                b'\\' => self.pos += 2,
+                _ => self.pos += 1,
+            }
+        }
+        Err(BadJson)
+    }
+
+    fn value<E: From<BadJson>>(
+        &mut self,
+        path: &mut [&'t str; MAX_DEPTH],
+        depth: usize,
+        visit: &mut impl FnMut(&[&'t str], &'t str) -> Result<(), E>,
+    ) -> Result<(), E> {
+        match self.peek().ok_or(BadJson)? {
+            b'"' => {
+                let value = self.string()?;
+                visit(&path[..depth], value)
+            }
+            b'{' => self.object(path, depth, visit),
+            b'[' => self.array(path, depth, visit),
+            _ => self.scalar().map_err(E::from),
+        }
+    }
+
+    fn object<E: From<BadJson>>(
+        &mut self,
+        path: &mut [&'t str; MAX_DEPTH],
+        depth: usize,
+        visit: &mut impl FnMut(&[&'t str], &'t str) -> Result<(), E>,
+    ) -> Result<(), E> {
+        self.expect(b'{')?;
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(());
+        }
+        if depth == MAX_DEPTH {
+            return Err(BadJson.into());
+        }
+        loop {
+            path[depth] = self.string()?;
+            self.expect(b':')?;
+            self.value(path, depth + 1, visit)?;
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => return Err(BadJson.into()),
+            }
+        }
+    }
+
+    /// Array items get the path of the array itself
+    fn array<E: From<BadJson>>(
+        &mut self,
+        path: &mut [&'t str; MAX_DEPTH],
+        depth: usize,
+        visit: &mut impl FnMut(&[&'t str], &'t str) -> Result<(), E>,
+    ) -> Result<(), E> {
+        self.expect(b'[')?;
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(());
+        }
+        loop {
+            self.value(path, depth, visit)?;
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => return Err(BadJson.into()),
+            }
+        }
+    }
+
+    /// A number, `true`, `false` or `null`
+    fn scalar(&mut self) -> Result<(), BadJson> {
+        let rest = &self.text[self.pos..];
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, ',' | '}' | ']'))
+            .unwrap_or(rest.len());
+        let scalar = &rest[..len];
+        let is_number = scalar
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.' | b'e' | b'E'));
+        if len == 0 || !(is_number || matches!(scalar, "true" | "false" | "null")) {
+            return Err(BadJson);
+        }
+        self.pos += len;
+        Ok(())
+    }
+}
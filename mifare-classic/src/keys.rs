@@ -0,0 +1,115 @@
+//! Key dictionaries.
+//!
+//! Cards set up by someone else rarely keep the default key, but many use
+//! one of a handful of published keys. [`Session::find_key`] tries a list
+//! of keys on a sector and a [`KeyMap`] remembers which one worked, so a
+//! dump can go on past the sectors no key opens.
+//!
+//! Key files hold one key per line as 12 hex digits, the format most
+//! MIFARE tools use for their `.dic` and `.keys` files.
+//!
+//! [`Session::find_key`]: crate::Session::find_key
+
+use crate::{Key, KeyType, Layout, Sector, DEFAULT_KEY};
+
+/// Published keys worth trying before giving up on a sector
+pub const KNOWN_KEYS: [Key; 13] = [
+    DEFAULT_KEY,
+    // MIFARE Application Directory, key A and B
+    [0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5],
+    [0xB0, 0xB1, 0xB2, 0xB3, 0xB4, 0xB5],
+    // NFC Forum key A of NDEF sectors
+    [0xD3, 0xF7, 0xD3, 0xF7, 0xD3, 0xF7],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF],
+    [0x4D, 0x3A, 0x99, 0xC3, 0x51, 0xDD],
+    [0x1A, 0x98, 0x2C, 0x7E, 0x45, 0x9A],
+    [0x71, 0x4C, 0x5C, 0x88, 0x6E, 0x97],
+    [0x58, 0x7E, 0xE5, 0xF9, 0x35, 0x0F],
+    [0xA0, 0x47, 0x8C, 0xC3, 0x90, 0x91],
+    [0x53, 0x3C, 0xB6, 0xC7, 0x23, 0xF6],
+    [0x8F, 0xD0, 0xA4, 0xF2, 0x56, 0xE9],
+];
+
+/// A line of a key file that is neither a key nor a comment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InvalidKey;
+
+/// Parses one line of a key file: 12 hex digits, which may be separated
+/// by spaces or colons. Blank lines and `#` comments give `Ok(None)`.
+pub fn parse_key_line(line: &str) -> Result<Option<Key>, InvalidKey> {
+    let line = match line.split_once('#') {
+        Some((before, _)) => before,
+        None => line,
+    };
+    let mut digits = line
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .peekable();
+    if digits.peek().is_none() {
+        return Ok(None);
+    }
+
+    let mut key = [0; 6];
+    for byte in key.iter_mut() {
+        let high = digits.next().and_then(|c| c.to_digit(16));
+        let low = digits.next().and_then(|c| c.to_digit(16));
+        match (high, low) {
+            (Some(high), Some(low)) => *byte = (high << 4 | low) as u8,
+            _ => return Err(InvalidKey),
+        }
+    }
+    if digits.next().is_some() {
+        return Err(InvalidKey);
+    }
+    Ok(Some(key))
+}
+
+/// A key that opened a sector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoundKey {
+    pub key_type: KeyType,
+    pub key: Key,
+}
+
+/// Which key opened each sector of a card
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    layout: Layout,
+    keys: [Option<FoundKey>; Layout::Classic4K.sector_count() as usize],
+}
+
+impl KeyMap {
+    /// A map with no keys found yet
+    pub fn new(layout: Layout) -> Self {
+        Self {
+            layout,
+            keys: [None; Layout::Classic4K.sector_count() as usize],
+        }
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn get(&self, sector: Sector) -> Option<FoundKey> {
+        self.keys[sector.index() as usize]
+    }
+
+    pub fn set(&mut self, sector: Sector, key: Option<FoundKey>) {
+        self.keys[sector.index() as usize] = key;
+    }
+
+    /// Every sector of the card with the key that opened it, if any
+    pub fn iter(&self) -> impl Iterator<Item = (Sector, Option<FoundKey>)> + '_ {
+        self.layout
+            .sectors()
+            .map(|sector| (sector, self.get(sector)))
+    }
+
+    /// Number of sectors a key was found for
+    pub fn found(&self) -> usize {
+        self.iter().filter(|(_, key)| key.is_some()).count()
+    }
+}
@@ -0,0 +1,57 @@
+//! # MIFARE Classic
+//!
+//! Shared helpers for the RFID examples: typed sector and block numbers
+//! for 1K and 4K cards, a [`Session`] that takes care of authenticating
+//! before each read or write, [`AccessConditions`] for making sense of
+//! sector trailers, [`KNOWN_KEYS`] and a [`KeyMap`] for cards that don't
+//! use the default key, a [`Dump`] of a whole card, [`ValueBlock`]s for
+//! counters and balances, and [`Hex`] for printing card data.
+//!
+//! Cards formatted with [`Session::format_ndef`] hold an NDEF message of
+//! [`Record`]s that phones read, found through the application directory,
+//! [`Mad`].
+//!
+//! [`select_card`] selects a card whatever the length of its UID and tells
+//! from its SAK what it is: a Classic card of some size, opened with a
+//! [`Session`], or a MIFARE Ultralight or NTAG tag, read and written page
+//! by page with [`Ultralight`], NDEF included.
+//!
+//! Failures come back as an [`Error`] saying what went wrong. The session
+//! selects its card again after the errors that leave the card idle, so a
+//! reader can keep going after a wrong key or a refused write. Enable the
+//! `defmt` feature to log errors with `defmt`.
+
+#![no_std]
+
+mod access;
+mod address;
+mod dump;
+mod error;
+mod frame;
+mod hex;
+mod json;
+mod keys;
+mod mad;
+mod ndef;
+mod nfc;
+mod select;
+mod session;
+mod ultralight;
+mod value;
+
+pub use access::{
+    Access, AccessConditions, Condition, DataPermissions, InvalidAccessBits, Trailer,
+    TrailerPermissions,
+};
+pub use address::{Block, Layout, Sector, BLOCK_SIZE};
+pub use dump::{Dump, DumpError, Json};
+pub use error::Error;
+pub use hex::Hex;
+pub use keys::{parse_key_line, FoundKey, InvalidKey, KeyMap, KNOWN_KEYS};
+pub use mad::{InvalidMad, Mad, FREE_AID, MAD1_SIZE, MAD2_SIZE, NDEF_AID};
+pub use ndef::{encode_ndef, parse_ndef, NdefError, Record, Records};
+pub use nfc::{MAD_KEY_A, NFC_KEY_A};
+pub use select::{request, select_card, Selected};
+pub use session::{Key, KeyType, Magic, Session, DEFAULT_KEY};
+pub use ultralight::{Pack, Password, TagType, Ultralight, PAGE_SIZE};
+pub use value::{InvalidValueBlock, ValueBlock};
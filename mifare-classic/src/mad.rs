@@ -0,0 +1,222 @@
+//! MIFARE Application Directory.
+//!
+//! Cards shared by several applications say which sector belongs to which
+//! in a directory: a two byte application ID per sector, stored in blocks
+//! 1 and 2 of sector 0 and, on 4K cards, in sector 16 for the sectors
+//! after it. A CRC byte guards each of the two parts. Phones look up the
+//! sectors holding NDEF data here.
+
+use crate::{Layout, Sector, BLOCK_SIZE};
+
+/// Application ID of sectors holding NDEF data
+pub const NDEF_AID: u16 = 0xE103;
+
+/// Application ID of unused sectors
+pub const FREE_AID: u16 = 0x0000;
+
+/// Size of the directory in sector 0: CRC, info byte and 15 IDs
+pub const MAD1_SIZE: usize = 2 * BLOCK_SIZE;
+
+/// Size of the directory in sector 16: CRC, info byte and 23 IDs
+pub const MAD2_SIZE: usize = 3 * BLOCK_SIZE;
+
+/// Sector holding the second part of the directory on 4K cards
+const MAD2_SECTOR: u8 = 16;
+
+/// A part of the directory whose CRC doesn't match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InvalidMad;
+
+/// Which application each sector of a card belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mad {
+    layout: Layout,
+    /// Info bytes of the two parts, pointing to a card publisher sector
+    info: [u8; 2],
+    /// Application ID by sector number
+    aids: [u16; 40],
+}
+
+impl Mad {
+    /// A directory with every sector free
+    pub fn new(layout: Layout) -> Self {
+        Self {
+            layout,
+            info: [0; 2],
+            aids: [FREE_AID; 40],
+        }
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Whether `sector` holds the directory itself
+    pub fn is_directory(sector: Sector) -> bool {
+        sector.index() == 0 || sector.index() == MAD2_SECTOR
+    }
+
+    /// Whether the card has the second part of the directory in sector 16
+    pub fn has_mad2(&self) -> bool {
+        self.layout.sector_count() > MAD2_SECTOR
+    }
+
+    pub fn aid(&self, sector: Sector) -> u16 {
+        self.aids[sector.index() as usize]
+    }
+
+    /// Gives `sector` to the application `aid`. The directory sectors
+    /// can't be given away and stay as they are.
+    pub fn set_aid(&mut self, sector: Sector, aid: u16) {
+        if !Self::is_directory(sector) {
+            self.aids[sector.index() as usize] = aid;
+        }
+    }
+
+    /// The sectors of the application `aid`, in order
+    pub fn sectors(&self, aid: u16) -> impl Iterator<Item = Sector> + '_ {
+        self.layout
+            .sectors()
+            .filter(move |&sector| !Self::is_directory(sector) && self.aid(sector) == aid)
+    }
+
+    /// Decodes the directory from blocks 1 and 2 of sector 0 and, if the
+    /// card has it, the data blocks of sector 16.
+    pub fn from_bytes(
+        layout: Layout,
+        mad1: &[u8; MAD1_SIZE],
+        mad2: Option<&[u8; MAD2_SIZE]>,
+    ) -> Result<Self, InvalidMad> {
+        let mut mad = Self::new(layout);
+        mad.info[0] = decode_part(mad1, &mut mad.aids[1..MAD2_SECTOR as usize])?;
+        if let Some(mad2) = mad2 {
+            mad.info[1] = decode_part(mad2, &mut mad.aids[MAD2_SECTOR as usize + 1..])?;
+        }
+        Ok(mad)
+    }
+
+    /// Encodes the part of the directory in blocks 1 and 2 of sector 0.
+    pub fn mad1_bytes(&self) -> [u8; MAD1_SIZE] {
+        let mut bytes = [0; MAD1_SIZE];
+        encode_part(
+            self.info[0],
+            &self.aids[1..MAD2_SECTOR as usize],
+            &mut bytes,
+        );
+        bytes
+    }
+
+    /// Encodes the part of the directory in the data blocks of sector 16.
+    pub fn mad2_bytes(&self) -> [u8; MAD2_SIZE] {
+        let mut bytes = [0; MAD2_SIZE];
+        encode_part(
+            self.info[1],
+            &self.aids[MAD2_SECTOR as usize + 1..],
+            &mut bytes,
+        );
+        bytes
+    }
+}
+
+/// Fills `bytes` with the CRC, the info byte and the application IDs.
+fn encode_part(info: u8, aids: &[u16], bytes: &mut [u8]) {
+    bytes[1] = info;
+    for (pair, aid) in bytes[2..].chunks_exact_mut(2).zip(aids) {
+        pair.copy_from_slice(&aid.to_le_bytes());
+    }
+    bytes[0] = crc8(&bytes[1..]);
+}
+
+/// Checks the CRC and reads the application IDs, giving the info byte.
+fn decode_part(bytes: &[u8], aids: &mut [u16]) -> Result<u8, InvalidMad> {
+    if crc8(&bytes[1..]) != bytes[0] {
+        return Err(InvalidMad);
+    }
+    for (aid, pair) in aids.iter_mut().zip(bytes[2..].chunks_exact(2)) {
+        *aid = u16::from_le_bytes([pair[0], pair[1]]);
+    }
+    Ok(bytes[1])
+}
+
+/// CRC-8 of the directory, polynomial `x^8 + x^4 + x^3 + x^2 + 1` starting
+/// from `0xC7`
+fn crc8(bytes: &[u8]) -> u8 {
+    let mut crc: u8 = 0xC7;
+    for &byte in bytes {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                crc << 1 ^ 0x1D
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Blocks 1 and 2 of a 1K card formatted for NDEF by a phone, with
+    /// the card publisher in sector 1 and every sector given to NDEF
+    const PHONE_MAD1: [u8; MAD1_SIZE] = [
+        0x14, 0x01, 0x03, 0xE1, 0x03, 0xE1, 0x03, 0xE1, 0x03, 0xE1, 0x03, 0xE1, 0x03, 0xE1, 0x03,
+        0xE1, 0x03, 0xE1, 0x03, 0xE1, 0x03, 0xE1, 0x03, 0xE1, 0x03, 0xE1, 0x03, 0xE1, 0x03, 0xE1,
+        0x03, 0xE1,
+    ];
+
+    #[test]
+    fn computes_the_directory_crc() {
+        assert_eq!(crc8(&PHONE_MAD1[1..]), 0x14);
+        assert_eq!(crc8(&[]), 0xC7);
+    }
+
+    #[test]
+    fn reads_a_phone_formatted_directory() {
+        let mad = Mad::from_bytes(Layout::Classic1K, &PHONE_MAD1, None).unwrap();
+        assert!(mad.sectors(NDEF_AID).map(Sector::index).eq(1..16));
+        assert_eq!(mad.mad1_bytes(), PHONE_MAD1);
+    }
+
+    #[test]
+    fn rejects_a_wrong_crc() {
+        let mut bytes = PHONE_MAD1;
+        bytes[7] ^= 0x01;
+        assert_eq!(
+            Mad::from_bytes(Layout::Classic1K, &bytes, None),
+            Err(InvalidMad)
+        );
+    }
+
+    #[test]
+    fn round_trips_both_parts() {
+        let layout = Layout::Classic4K;
+        let mut mad = Mad::new(layout);
+        for (sector, aid) in [(1, NDEF_AID), (15, 0x4812), (17, NDEF_AID), (39, 0xABCD)] {
+            mad.set_aid(Sector::new(sector, layout).unwrap(), aid);
+        }
+        assert!(mad.has_mad2());
+
+        let (mad1, mad2) = (mad.mad1_bytes(), mad.mad2_bytes());
+        assert_eq!(Mad::from_bytes(layout, &mad1, Some(&mad2)), Ok(mad.clone()));
+
+        let mut mad2 = mad2;
+        mad2[MAD2_SIZE - 1] ^= 0x80;
+        assert_eq!(Mad::from_bytes(layout, &mad1, Some(&mad2)), Err(InvalidMad));
+    }
+
+    #[test]
+    fn keeps_the_directory_sectors() {
+        let layout = Layout::Classic4K;
+        let mut mad = Mad::new(layout);
+        for sector in [0, 16] {
+            let sector = Sector::new(sector, layout).unwrap();
+            mad.set_aid(sector, NDEF_AID);
+            assert_eq!(mad.aid(sector), FREE_AID);
+        }
+        assert_eq!(mad.sectors(FREE_AID).count(), 38);
+    }
+}
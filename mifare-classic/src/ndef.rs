@@ -0,0 +1,518 @@
+//! NDEF messages.
+//!
+//! Phones read tags in the NFC Data Exchange Format: a message made of
+//! records, each with a type and a payload. On the tag the message sits in
+//! an NDEF TLV (type, length, value) block in the data area, followed by a
+//! terminator. This module encodes and decodes the records phones act on,
+//! URIs, text and MIME data, independently of the kind of tag.
+//!
+//! ```ignore
+//! let mut buffer = [0; 128];
+//! let len = encode_ndef(&[Record::uri("https://www.rust-lang.org")], &mut buffer)?;
+//! session.write_ndef(&buffer[..len])?;
+//! ```
+
+use core::fmt;
+
+use crate::Hex;
+
+/// Record header flags
+const MESSAGE_BEGIN: u8 = 0x80;
+const MESSAGE_END: u8 = 0x40;
+const CHUNK: u8 = 0x20;
+const SHORT_RECORD: u8 = 0x10;
+const ID_LENGTH: u8 = 0x08;
+
+/// Type name formats, the low 3 bits of the header
+const TNF_WELL_KNOWN: u8 = 0x01;
+const TNF_MIME: u8 = 0x02;
+
+/// TLV block types
+const NULL_TLV: u8 = 0x00;
+const NDEF_TLV: u8 = 0x03;
+pub(crate) const TERMINATOR_TLV: u8 = 0xFE;
+
+/// Data area of a tag formatted with an empty message
+pub(crate) const EMPTY_MESSAGE: [u8; 3] = [NDEF_TLV, 0, TERMINATOR_TLV];
+
+/// Lengths from this one on take three bytes in a TLV block
+const LONG_TLV: usize = 0xFF;
+
+/// Abbreviations of URI record payloads, by their code in the first byte
+const URI_PREFIXES: [&str; 36] = [
+    "",
+    "http://www.",
+    "https://www.",
+    "http://",
+    "https://",
+    "tel:",
+    "mailto:",
+    "ftp://anonymous:anonymous@",
+    "ftp://ftp.",
+    "ftps://",
+    "sftp://",
+    "smb://",
+    "nfs://",
+    "ftp://",
+    "dav://",
+    "news:",
+    "telnet://",
+    "imap:",
+    "rtsp://",
+    "urn:",
+    "pop:",
+    "sip:",
+    "sips:",
+    "tftp:",
+    "btspp://",
+    "btl2cap://",
+    "btgoep://",
+    "tcpobex://",
+    "irdaobex://",
+    "file://",
+    "urn:epc:id:",
+    "urn:epc:tag:",
+    "urn:epc:pat:",
+    "urn:epc:raw:",
+    "urn:epc:",
+    "urn:nfc:",
+];
+
+/// Why an NDEF message couldn't be read or written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NdefError {
+    /// The data area holds no NDEF message
+    NoMessage,
+    /// A TLV block or record runs past the end of the data
+    Malformed,
+    /// The message is split into chunked records, which tags don't use
+    Chunked,
+    /// The message doesn't fit in the buffer or on the tag
+    TooBig,
+}
+
+impl fmt::Display for NdefError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NdefError::NoMessage => "no NDEF message",
+            NdefError::Malformed => "NDEF data is cut short",
+            NdefError::Chunked => "chunked NDEF records are not supported",
+            NdefError::TooBig => "NDEF message is too big",
+        })
+    }
+}
+
+/// One record of an NDEF message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Record<'a> {
+    /// A link, stored with its prefix abbreviated to one byte
+    Uri { prefix: &'static str, rest: &'a str },
+    /// Text in UTF-8 with an IANA language code such as `en`
+    Text { language: &'a str, text: &'a str },
+    /// Data of a MIME type such as `text/vcard`
+    Mime { mime_type: &'a str, data: &'a [u8] },
+    /// Any other record, including text in UTF-16
+    Other {
+        tnf: u8,
+        record_type: &'a [u8],
+        payload: &'a [u8],
+    },
+}
+
+impl<'a> Record<'a> {
+    /// A URI record, abbreviating the longest known prefix of `uri`
+    pub fn uri(uri: &'a str) -> Self {
+        let prefix = URI_PREFIXES
+            .iter()
+            .filter(|prefix| uri.starts_with(*prefix))
+            .max_by_key(|prefix| prefix.len())
+            .copied()
+            .unwrap_or("");
+        Record::Uri {
+            prefix,
+            rest: &uri[prefix.len()..],
+        }
+    }
+
+    pub fn text(language: &'a str, text: &'a str) -> Self {
+        Record::Text { language, text }
+    }
+
+    pub fn mime(mime_type: &'a str, data: &'a [u8]) -> Self {
+        Record::Mime { mime_type, data }
+    }
+
+    fn tnf(&self) -> u8 {
+        match self {
+            Record::Uri { .. } | Record::Text { .. } => TNF_WELL_KNOWN,
+            Record::Mime { .. } => TNF_MIME,
+            Record::Other { tnf, .. } => *tnf,
+        }
+    }
+
+    fn record_type(&self) -> &'a [u8] {
+        match self {
+            Record::Uri { .. } => b"U",
+            Record::Text { .. } => b"T",
+            Record::Mime { mime_type, .. } => mime_type.as_bytes(),
+            Record::Other { record_type, .. } => record_type,
+        }
+    }
+
+    fn payload_len(&self) -> usize {
+        match self {
+            Record::Uri { rest, .. } => 1 + rest.len(),
+            Record::Text { language, text } => 1 + language.len() + text.len(),
+            Record::Mime { data, .. } => data.len(),
+            Record::Other { payload, .. } => payload.len(),
+        }
+    }
+
+    /// Size of the record once encoded
+    fn encoded_len(&self) -> usize {
+        let payload_len = self.payload_len();
+        let length_bytes = if payload_len <= 0xFF { 1 } else { 4 };
+        2 + length_bytes + self.record_type().len() + payload_len
+    }
+
+    /// Writes the record to the start of `out`, which is long enough.
+    fn encode(&self, flags: u8, out: &mut [u8]) {
+        let payload_len = self.payload_len();
+        let record_type = self.record_type();
+        let mut pos = 0;
+        let mut put = |bytes: &[u8]| {
+            out[pos..pos + bytes.len()].copy_from_slice(bytes);
+            pos += bytes.len();
+        };
+
+        if payload_len <= 0xFF {
+            put(&[flags | SHORT_RECORD | self.tnf(), record_type.len() as u8]);
+            put(&[payload_len as u8]);
+        } else {
+            put(&[flags | self.tnf(), record_type.len() as u8]);
+            put(&(payload_len as u32).to_be_bytes());
+        }
+        put(record_type);
+        match self {
+            Record::Uri { prefix, rest } => {
+                let code = URI_PREFIXES.iter().position(|p| p == prefix).unwrap_or(0);
+                put(&[code as u8]);
+                put(rest.as_bytes());
+            }
+            // Status byte: UTF-8 and the length of the language code
+            Record::Text { language, text } => {
+                put(&[language.len() as u8 & 0x3F]);
+                put(language.as_bytes());
+                put(text.as_bytes());
+            }
+            Record::Mime { data, .. } => put(data),
+            Record::Other { payload, .. } => put(payload),
+        }
+    }
+
+    /// Makes sense of a record from its type name format, type and
+    /// payload, leaving it as [`Record::Other`] if it isn't one of the
+    /// known kinds.
+    fn decode(tnf: u8, record_type: &'a [u8], payload: &'a [u8]) -> Self {
+        let other = Record::Other {
+            tnf,
+            record_type,
+            payload,
+        };
+        match (tnf, record_type) {
+            (TNF_WELL_KNOWN, b"U") => {
+                let Some((&code, rest)) = payload.split_first() else {
+                    return other;
+                };
+                match (URI_PREFIXES.get(code as usize), core::str::from_utf8(rest)) {
+                    (Some(&prefix), Ok(rest)) => Record::Uri { prefix, rest },
+                    _ => other,
+                }
+            }
+            (TNF_WELL_KNOWN, b"T") => {
+                let Some((&status, rest)) = payload.split_first() else {
+                    return other;
+                };
+                let language_len = (status & 0x3F) as usize;
+                // Bit 7 marks UTF-16 text
+                if status & 0x80 != 0 || rest.len() < language_len {
+                    return other;
+                }
+                let (language, text) = rest.split_at(language_len);
+                match (core::str::from_utf8(language), core::str::from_utf8(text)) {
+                    (Ok(language), Ok(text)) => Record::Text { language, text },
+                    _ => other,
+                }
+            }
+            (TNF_MIME, _) => match core::str::from_utf8(record_type) {
+                Ok(mime_type) => Record::Mime {
+                    mime_type,
+                    data: payload,
+                },
+                Err(_) => other,
+            },
+            _ => other,
+        }
+    }
+}
+
+impl fmt::Display for Record<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Record::Uri { prefix, rest } => write!(f, "URI {}{}", prefix, rest),
+            Record::Text { language, text } => write!(f, "text ({}) {}", language, text),
+            Record::Mime { mime_type, data } => write!(f, "{}, {} bytes", mime_type, data.len()),
+            Record::Other {
+                tnf,
+                record_type,
+                payload,
+            } => write!(
+                f,
+                "record {} type {}, {} bytes",
+                tnf,
+                Hex(record_type),
+                payload.len()
+            ),
+        }
+    }
+}
+
+/// Encodes `records` as an NDEF message into `out`, returning its length.
+///
+/// No records make an empty message of 0 bytes, which is how a blank tag
+/// is formatted.
+pub fn encode_ndef(records: &[Record], out: &mut [u8]) -> Result<usize, NdefError> {
+    let len = records.iter().map(Record::encoded_len).sum();
+    if len > out.len() {
+        return Err(NdefError::TooBig);
+    }
+    let mut pos = 0;
+    for (i, record) in records.iter().enumerate() {
+        let mut flags = 0;
+        if i == 0 {
+            flags |= MESSAGE_BEGIN;
+        }
+        if i == records.len() - 1 {
+            flags |= MESSAGE_END;
+        }
+        record.encode(flags, &mut out[pos..]);
+        pos += record.encoded_len();
+    }
+    Ok(len)
+}
+
+/// Goes through the records of an NDEF message.
+///
+/// ```ignore
+/// for record in parse_ndef(message) {
+///     write!(buff, "{}\r\n", record?)?;
+/// }
+/// ```
+pub fn parse_ndef(message: &[u8]) -> Records<'_> {
+    Records { rest: message }
+}
+
+/// Iterator over the records of an NDEF message, see [`parse_ndef`]
+pub struct Records<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Records<'a> {
+    fn next_record(&mut self) -> Result<Record<'a>, NdefError> {
+        let header = take(&mut self.rest, 2)?;
+        let (flags, type_len) = (header[0], header[1] as usize);
+        if flags & CHUNK != 0 {
+            return Err(NdefError::Chunked);
+        }
+        let payload_len = if flags & SHORT_RECORD != 0 {
+            take(&mut self.rest, 1)?[0] as usize
+        } else {
+            let len = take(&mut self.rest, 4)?;
+            u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize
+        };
+        let id_len = if flags & ID_LENGTH != 0 {
+            take(&mut self.rest, 1)?[0] as usize
+        } else {
+            0
+        };
+        let record_type = take(&mut self.rest, type_len)?;
+        take(&mut self.rest, id_len)?;
+        let payload = take(&mut self.rest, payload_len)?;
+
+        // Anything after the last record is padding
+        if flags & MESSAGE_END != 0 {
+            self.rest = &[];
+        }
+        Ok(Record::decode(flags & 0x07, record_type, payload))
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<Record<'a>, NdefError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let record = self.next_record();
+        if record.is_err() {
+            self.rest = &[];
+        }
+        Some(record)
+    }
+}
+
+/// Splits `len` bytes off the front of `data`.
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], NdefError> {
+    if data.len() < len {
+        return Err(NdefError::Malformed);
+    }
+    let (taken, rest) = data.split_at(len);
+    *data = rest;
+    Ok(taken)
+}
+
+/// Finds the message in the first NDEF TLV block of a tag's data area,
+/// skipping other TLV blocks before it.
+///
+/// Gives [`NdefError::Malformed`] if `area` ends before the message does,
+/// so more of the data area can be read in and tried again.
+pub(crate) fn find_message(area: &[u8]) -> Result<&[u8], NdefError> {
+    let mut rest = area;
+    loop {
+        let tlv = take(&mut rest, 1)?[0];
+        match tlv {
+            NULL_TLV => continue,
+            TERMINATOR_TLV => return Err(NdefError::NoMessage),
+            _ => {}
+        }
+        let mut len = take(&mut rest, 1)?[0] as usize;
+        if len == LONG_TLV {
+            let long = take(&mut rest, 2)?;
+            len = u16::from_be_bytes([long[0], long[1]]) as usize;
+        }
+        let value = take(&mut rest, len)?;
+        if tlv == NDEF_TLV {
+            return Ok(value);
+        }
+    }
+}
+
+/// Writes the type and length of an NDEF TLV block holding a message of
+/// `len` bytes, returning how many bytes of `header` that took.
+pub(crate) fn tlv_header(len: usize, header: &mut [u8; 4]) -> Result<usize, NdefError> {
+    header[0] = NDEF_TLV;
+    if len < LONG_TLV {
+        header[1] = len as u8;
+        return Ok(2);
+    }
+    let len = u16::try_from(len).map_err(|_| NdefError::TooBig)?;
+    header[1] = LONG_TLV as u8;
+    header[2..].copy_from_slice(&len.to_be_bytes());
+    Ok(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abbreviates_the_longest_uri_prefix() {
+        for (uri, prefix, rest) in [
+            ("https://www.rust-lang.org", "https://www.", "rust-lang.org"),
+            ("http://example.com", "http://", "example.com"),
+            ("urn:epc:id:sgtin:1", "urn:epc:id:", "sgtin:1"),
+            ("urn:isbn:0", "urn:", "isbn:0"),
+            ("gopher://host", "", "gopher://host"),
+        ] {
+            assert_eq!(Record::uri(uri), Record::Uri { prefix, rest }, "{uri}");
+        }
+    }
+
+    #[test]
+    fn encodes_a_uri_message() {
+        let mut out = [0; 32];
+        let len = encode_ndef(&[Record::uri("https://www.rust-lang.org")], &mut out).unwrap();
+        assert_eq!(&out[..5], &[0xD1, 0x01, 0x0E, b'U', 0x02]);
+        assert_eq!(&out[5..len], b"rust-lang.org");
+    }
+
+    #[test]
+    fn round_trips_records() {
+        let vcard = [b'v'; 300];
+        let records = [
+            Record::uri("tel:+3212345678"),
+            Record::text("en", "hello"),
+            Record::mime("text/vcard", &vcard),
+            Record::Other {
+                tnf: 0x04,
+                record_type: b"android.com:pkg",
+                payload: b"org.example",
+            },
+        ];
+        let mut out = [0; 512];
+        let len = encode_ndef(&records, &mut out).unwrap();
+        assert!(parse_ndef(&out[..len]).map(Result::unwrap).eq(records));
+
+        // Padding after the last record is ignored
+        assert_eq!(parse_ndef(&out[..len + 4]).count(), records.len());
+    }
+
+    #[test]
+    fn empty_message_has_no_records() {
+        assert_eq!(encode_ndef(&[], &mut []), Ok(0));
+        assert_eq!(parse_ndef(&[]).count(), 0);
+    }
+
+    #[test]
+    fn rejects_messages_that_dont_fit() {
+        let mut out = [0; 16];
+        assert_eq!(
+            encode_ndef(&[Record::uri("https://www.rust-lang.org")], &mut out),
+            Err(NdefError::TooBig)
+        );
+    }
+
+    #[test]
+    fn stops_at_broken_records() {
+        let mut records = parse_ndef(&[0xD1, 0x01, 0x0E, b'U', 0x02, b'r']);
+        assert_eq!(records.next(), Some(Err(NdefError::Malformed)));
+        assert_eq!(records.next(), None);
+
+        let mut records = parse_ndef(&[0xB1, 0x01, 0x01, b'U', 0x00]);
+        assert_eq!(records.next(), Some(Err(NdefError::Chunked)));
+        assert_eq!(records.next(), None);
+    }
+
+    #[test]
+    fn finds_the_message_after_other_tlv_blocks() {
+        // Null block, then a lock control block, then the message
+        let area = [
+            0x00, 0x01, 0x03, 0xA0, 0x10, 0x44, 0x03, 0x02, 0xAB, 0xCD, 0xFE,
+        ];
+        assert_eq!(find_message(&area), Ok(&[0xAB, 0xCD][..]));
+        assert_eq!(find_message(&EMPTY_MESSAGE), Ok(&[][..]));
+        assert_eq!(find_message(&[TERMINATOR_TLV]), Err(NdefError::NoMessage));
+        assert_eq!(find_message(&area[..9]), Err(NdefError::Malformed));
+    }
+
+    #[test]
+    fn finds_a_message_with_a_long_length() {
+        let mut area = [0; 4 + 300];
+        area[..4].copy_from_slice(&[NDEF_TLV, 0xFF, 0x01, 0x2C]);
+        assert_eq!(find_message(&area).map(<[u8]>::len), Ok(300));
+    }
+
+    #[test]
+    fn switches_to_long_tlv_lengths_at_255() {
+        let mut header = [0; 4];
+        assert_eq!(tlv_header(0xFE, &mut header), Ok(2));
+        assert_eq!(&header[..2], &[NDEF_TLV, 0xFE]);
+
+        assert_eq!(tlv_header(0xFF, &mut header), Ok(4));
+        assert_eq!(header, [NDEF_TLV, 0xFF, 0x00, 0xFF]);
+
+        assert_eq!(tlv_header(0x1_0000, &mut header), Err(NdefError::TooBig));
+    }
+}
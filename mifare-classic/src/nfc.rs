@@ -0,0 +1,202 @@
+//! NDEF on MIFARE Classic cards.
+//!
+//! The NFC Forum maps NDEF onto Classic cards through the application
+//! directory: every sector listed with [`NDEF_AID`] holds a piece of the
+//! data area, in order, and the TLV blocks run across them. The directory
+//! and the NDEF sectors each have a published key A, so any phone can
+//! read them.
+
+use mfrc522::comm::Interface;
+
+use crate::ndef::{self, NdefError, EMPTY_MESSAGE, TERMINATOR_TLV};
+use crate::{
+    AccessConditions, Condition, Error, Key, KeyType, Mad, Sector, Session, Trailer, BLOCK_SIZE,
+    DEFAULT_KEY, MAD1_SIZE, MAD2_SIZE, NDEF_AID,
+};
+
+/// Key A of the directory sectors, published by NXP
+pub const MAD_KEY_A: Key = [0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5];
+
+/// Key A of NDEF sectors, published by the NFC Forum
+pub const NFC_KEY_A: Key = [0xD3, 0xF7, 0xD3, 0xF7, 0xD3, 0xF7];
+
+/// `78 77 88`: data read with either key and written with key B, the
+/// trailer written with key B
+const MAD_ACCESS: AccessConditions = AccessConditions {
+    data: [Condition::new(true, false, false); 3],
+    trailer: Condition::new(false, true, true),
+};
+
+/// `7F 07 88`: data read and written with either key, the trailer written
+/// with key B
+const NDEF_ACCESS: AccessConditions = AccessConditions {
+    data: [Condition::new(false, false, false); 3],
+    trailer: Condition::new(false, true, true),
+};
+
+/// General purpose byte of sector 0: directory present, card shared by
+/// several applications, and the directory version in the low bits
+const MAD_PRESENT: u8 = 0xC0;
+const MAD_VERSION: u8 = 0x03;
+
+/// Free byte of NDEF sector trailers: mapping version 1.0, free read and
+/// write access
+const NDEF_SECTOR: u8 = 0x40;
+
+impl<'a, E, COMM: Interface<Error = E>> Session<'a, COMM> {
+    /// Reads the application directory with [`MAD_KEY_A`].
+    ///
+    /// Fails with [`Error::NoMad`] if the card doesn't have one.
+    pub fn read_mad(&mut self) -> Result<Mad, Error<E>> {
+        self.set_key(KeyType::A, MAD_KEY_A);
+        let sector = Sector::new(0, self.layout()).unwrap();
+        let trailer = self.read(sector.trailer())?;
+        if trailer[9] & MAD_PRESENT != MAD_PRESENT {
+            return Err(Error::NoMad);
+        }
+
+        let mut mad1 = [0; MAD1_SIZE];
+        self.read_blocks(sector, 1, &mut mad1)?;
+        let mut mad2 = [0; MAD2_SIZE];
+        let has_mad2 = Mad::new(self.layout()).has_mad2() && trailer[9] & MAD_VERSION == 2;
+        if has_mad2 {
+            let sector = Sector::new(16, self.layout()).unwrap();
+            self.read_blocks(sector, 0, &mut mad2)?;
+        }
+        Mad::from_bytes(self.layout(), &mad1, has_mad2.then_some(&mad2))
+            .map_err(|_| Error::InvalidMad)
+    }
+
+    /// Reads the NDEF data area into `buffer` and gives the NDEF message
+    /// found in it, which [`parse_ndef`](crate::parse_ndef) splits into
+    /// records.
+    ///
+    /// Reading stops as soon as the whole message is in. A message longer
+    /// than `buffer` fails with [`NdefError::TooBig`].
+    pub fn read_ndef<'b>(&mut self, buffer: &'b mut [u8]) -> Result<&'b [u8], Error<E>> {
+        let mad = self.read_mad()?;
+        self.set_key(KeyType::A, NFC_KEY_A);
+        let mut len = 0;
+        for block in mad.sectors(NDEF_AID).flat_map(Sector::data_blocks) {
+            // Anything but running out of data means the message is in
+            if ndef::find_message(&buffer[..len]) != Err(NdefError::Malformed) {
+                break;
+            }
+            if len + BLOCK_SIZE > buffer.len() {
+                return Err(Error::Ndef(NdefError::TooBig));
+            }
+            let data = self.read(block)?;
+            buffer[len..len + BLOCK_SIZE].copy_from_slice(&data);
+            len += BLOCK_SIZE;
+        }
+
+        let buffer: &'b [u8] = buffer;
+        ndef::find_message(&buffer[..len]).map_err(Error::Ndef)
+    }
+
+    /// Writes an NDEF message made with [`encode_ndef`](crate::encode_ndef)
+    /// to a card formatted for NDEF.
+    ///
+    /// Only the blocks the message needs are written.
+    pub fn write_ndef(&mut self, message: &[u8]) -> Result<(), Error<E>> {
+        let mut header = [0; 4];
+        let header_len = ndef::tlv_header(message.len(), &mut header).map_err(Error::Ndef)?;
+        let len = header_len + message.len() + 1;
+
+        let mad = self.read_mad()?;
+        let capacity: usize = mad
+            .sectors(NDEF_AID)
+            .map(|sector| sector.data_blocks().count() * BLOCK_SIZE)
+            .sum();
+        if len > capacity {
+            return Err(Error::Ndef(NdefError::TooBig));
+        }
+
+        self.set_key(KeyType::A, NFC_KEY_A);
+        let mut bytes = header[..header_len]
+            .iter()
+            .chain(message)
+            .chain(&[TERMINATOR_TLV])
+            .copied();
+        let blocks = mad.sectors(NDEF_AID).flat_map(Sector::data_blocks);
+        for block in blocks.take(len.div_ceil(BLOCK_SIZE)) {
+            let mut data = [0; BLOCK_SIZE];
+            for byte in data.iter_mut() {
+                *byte = bytes.next().unwrap_or(0);
+            }
+            self.write(block, data)?;
+        }
+        Ok(())
+    }
+
+    /// Prepares a blank card for NDEF: writes the application directory,
+    /// gives every other sector to NDEF with an empty message, and sets the
+    /// published keys and access bits.
+    ///
+    /// The card has to open with [`DEFAULT_KEY`] as key A. Key B stays the
+    /// default key, which is the only one allowed to change the directory
+    /// and trailers afterwards.
+    pub fn format_ndef(&mut self) -> Result<(), Error<E>> {
+        let layout = self.layout();
+        self.set_key(KeyType::A, DEFAULT_KEY);
+        let mut mad = Mad::new(layout);
+        for sector in layout.sectors() {
+            mad.set_aid(sector, NDEF_AID);
+        }
+
+        let sector = Sector::new(0, layout).unwrap();
+        self.write_blocks(sector, 1, &mad.mad1_bytes())?;
+        let version = if mad.has_mad2() { 2 } else { 1 };
+        let directory = Trailer {
+            key_a: MAD_KEY_A,
+            access: MAD_ACCESS,
+            user_byte: MAD_PRESENT | version,
+            key_b: DEFAULT_KEY,
+        };
+        self.write_trailer(sector, &directory)?;
+        if mad.has_mad2() {
+            let sector = Sector::new(16, layout).unwrap();
+            self.write_blocks(sector, 0, &mad.mad2_bytes())?;
+            self.write_trailer(sector, &directory)?;
+        }
+
+        let trailer = Trailer {
+            key_a: NFC_KEY_A,
+            access: NDEF_ACCESS,
+            user_byte: NDEF_SECTOR,
+            key_b: DEFAULT_KEY,
+        };
+        for (i, sector) in mad.sectors(NDEF_AID).enumerate() {
+            if i == 0 {
+                let mut data = [0; BLOCK_SIZE];
+                data[..EMPTY_MESSAGE.len()].copy_from_slice(&EMPTY_MESSAGE);
+                self.write(sector.first_block(), data)?;
+            }
+            self.write_trailer(sector, &trailer)?;
+        }
+        Ok(())
+    }
+
+    /// Reads consecutive blocks of `sector` from `offset` on, as many as
+    /// fill `bytes`.
+    fn read_blocks(
+        &mut self,
+        sector: Sector,
+        offset: u8,
+        bytes: &mut [u8],
+    ) -> Result<(), Error<E>> {
+        for (i, chunk) in bytes.chunks_exact_mut(BLOCK_SIZE).enumerate() {
+            let block = sector.block(offset + i as u8).unwrap();
+            chunk.copy_from_slice(&self.read(block)?);
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, sector: Sector, offset: u8, bytes: &[u8]) -> Result<(), Error<E>> {
+        for (i, chunk) in bytes.chunks_exact(BLOCK_SIZE).enumerate() {
+            let block = sector.block(offset + i as u8).unwrap();
+            self.write(block, chunk.try_into().unwrap())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+//! Selecting cards with long UIDs.
+//!
+//! A UID comes in cascade levels of 4 bytes. Cards with a 7 or 10 byte UID,
+//! which includes every Ultralight and NTAG tag, answer the first level
+//! with a cascade tag and three UID bytes and set a bit in their SAK to ask
+//! for the next level. [`select_card`] goes through all of them.
+
+use mfrc522::comm::Interface;
+use mfrc522::{Initialized, Mfrc522};
+
+use crate::frame::{self, CASCADE_TAG, UID_INCOMPLETE};
+use crate::{Error, Layout};
+
+/// Longest UID, that of a triple size card
+const MAX_UID: usize = 10;
+
+/// A card selected by [`select_card`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Selected {
+    uid: [u8; MAX_UID],
+    uid_len: usize,
+    sak: u8,
+}
+
+impl Selected {
+    /// The 4, 7 or 10 byte UID
+    pub fn uid(&self) -> &[u8] {
+        &self.uid[..self.uid_len]
+    }
+
+    /// The select acknowledge of the last cascade level, which says what
+    /// kind of card this is
+    pub fn sak(&self) -> u8 {
+        self.sak
+    }
+
+    /// Whether the card is a MIFARE Ultralight or NTAG tag, to be read
+    /// with [`Ultralight`](crate::Ultralight)
+    pub fn is_ultralight(&self) -> bool {
+        self.sak == 0x00
+    }
+
+    /// Memory layout if the card is a MIFARE Classic
+    pub fn layout(&self) -> Option<Layout> {
+        Layout::from_sak(self.sak)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn new(uid: &[u8], sak: u8) -> Self {
+        let mut selected = Selected {
+            uid: [0; MAX_UID],
+            uid_len: 0,
+            sak,
+        };
+        selected.push(uid);
+        selected
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.uid[self.uid_len..self.uid_len + bytes.len()].copy_from_slice(bytes);
+        self.uid_len += bytes.len();
+    }
+}
+
+/// Asks idle cards in the field to answer, like the driver's `reqa`, and
+/// gives the ATQA of the card that did in the order it was sent.
+///
+/// The driver keeps the ATQA to itself, so this is for when it is wanted,
+/// as in a dump of the card.
+pub fn request<E, COMM: Interface<Error = E>>(
+    rfid: &mut Mfrc522<COMM, Initialized>,
+) -> Result<[u8; 2], Error<E>> {
+    let answer = rfid.transceive::<2>(&[frame::REQA], 7, 0)?;
+    if answer.valid_bytes != 2 || answer.valid_bits != 0 {
+        return Err(Error::Protocol);
+    }
+    Ok(answer.buffer)
+}
+
+/// Selects the card that just answered `reqa`, `wupa` or [`request`],
+/// going through as many cascade levels as its UID has.
+///
+/// Only one card may be in the field: colliding answers fail with
+/// [`Error::Collision`] rather than being told apart.
+pub fn select_card<E, COMM: Interface<Error = E>>(
+    rfid: &mut Mfrc522<COMM, Initialized>,
+) -> Result<Selected, Error<E>> {
+    let mut selected = Selected {
+        uid: [0; MAX_UID],
+        uid_len: 0,
+        sak: 0,
+    };
+    for select in frame::SELECT {
+        // Four UID bytes and their check byte, without CRC
+        let answer = rfid.transceive::<5>(&[select, frame::ANTICOLLISION], 0, 0)?;
+        if answer.valid_bytes != 5 || answer.valid_bits != 0 {
+            return Err(Error::Protocol);
+        }
+        let uid = answer.buffer;
+        let bcc = uid[..4].iter().fold(0, |bcc, byte| bcc ^ byte);
+        if uid[4] != bcc {
+            return Err(Error::BadBcc);
+        }
+
+        let mut command = [0; 7];
+        command[0] = select;
+        command[1] = frame::SELECT_UID;
+        command[2..].copy_from_slice(&uid);
+        let sak = frame::exchange::<3, _, _>(rfid, &command)?[0];
+
+        selected.sak = sak;
+        if sak & UID_INCOMPLETE == 0 {
+            selected.push(&uid[..4]);
+            return Ok(selected);
+        }
+        if uid[0] != CASCADE_TAG {
+            return Err(Error::Protocol);
+        }
+        selected.push(&uid[1..4]);
+    }
+    Err(Error::Protocol)
+}
@@ -0,0 +1,431 @@
+use mfrc522::comm::Interface;
+use mfrc522::{Initialized, Mfrc522};
+
+use crate::frame::{self, append_crc};
+use crate::{
+    select_card, Block, Error, FoundKey, Layout, Sector, Selected, Trailer, ValueBlock, BLOCK_SIZE,
+};
+
+/// A 6 byte sector key
+pub type Key = [u8; 6];
+
+/// Key A and B of a blank card
+pub const DEFAULT_KEY: Key = [0xFF; 6];
+
+/// Which of the two sector keys to authenticate with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    A,
+    B,
+}
+
+/// Cards sold as "magic", whose manufacturer block can be written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Magic {
+    /// First generation: a backdoor command opens block 0 without a key
+    Gen1a,
+    /// Second generation, or CUID: block 0 takes a normal write
+    Gen2,
+}
+
+/// Commands opening the backdoor of Gen1a cards, the first sent as 7 bits
+const GEN1A_UNLOCK: [u8; 2] = [0x40, 0x43];
+
+/// Reads and writes one selected card
+///
+/// The session remembers which sector the reader is authenticated for and
+/// authenticates again with its key whenever a block in another sector is
+/// accessed.
+///
+/// ```ignore
+/// rfid.reqa()?;
+/// let card = select_card(&mut rfid)?;
+/// let mut session = Session::new(&mut rfid, card)?;
+/// for block in Sector::new(1, session.layout()).unwrap().blocks() {
+///     let data = session.read(block)?;
+/// }
+/// session.finish()?;
+/// ```
+pub struct Session<'a, COMM: Interface> {
+    rfid: &'a mut Mfrc522<COMM, Initialized>,
+    card: Selected,
+    layout: Layout,
+    key_type: KeyType,
+    key: Key,
+    /// Sector the reader is authenticated for
+    authenticated: Option<Sector>,
+}
+
+impl<'a, E, COMM: Interface<Error = E>> Session<'a, COMM> {
+    /// Starts a session with the card `card`, using key A set to
+    /// [`DEFAULT_KEY`].
+    ///
+    /// The layout, Mini, 1K or 4K, comes from the card's SAK. Cards that
+    /// aren't MIFARE Classic fail with [`Error::Unsupported`].
+    pub fn new(rfid: &'a mut Mfrc522<COMM, Initialized>, card: Selected) -> Result<Self, Error<E>> {
+        let layout = card.layout().ok_or(Error::Unsupported)?;
+        Ok(Self {
+            rfid,
+            card,
+            layout,
+            key_type: KeyType::A,
+            key: DEFAULT_KEY,
+            authenticated: None,
+        })
+    }
+
+    pub fn uid(&self) -> &[u8] {
+        self.card.uid()
+    }
+
+    /// The card as it answered `select`
+    pub fn card(&self) -> &Selected {
+        &self.card
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Changes the key used from the next authentication on.
+    pub fn set_key(&mut self, key_type: KeyType, key: Key) {
+        self.key_type = key_type;
+        self.key = key;
+        self.authenticated = None;
+    }
+
+    /// Authenticates for `sector` with the current key, even if the reader
+    /// already is.
+    ///
+    /// A wrong key leaves the card idle, so the session selects it again
+    /// before returning [`Error::Auth`], ready for the next attempt.
+    pub fn authenticate(&mut self, sector: Sector) -> Result<(), Error<E>> {
+        self.authenticated = None;
+        // The MFRC522 driver only sends the key A command
+        if self.key_type == KeyType::B {
+            return Err(Error::KeyBUnsupported);
+        }
+
+        let block = sector.first_block().index();
+        match frame::authenticate(self.rfid, &self.card, block, &self.key) {
+            Ok(()) => {
+                self.authenticated = Some(sector);
+                Ok(())
+            }
+            Err(mfrc522::Error::Comm(error)) => Err(Error::Transport(error)),
+            Err(_) => {
+                self.reselect()?;
+                Err(Error::Auth)
+            }
+        }
+    }
+
+    /// Tries every key in `keys` on `sector` and keeps using the first one
+    /// that works.
+    ///
+    /// Gives `Ok(None)` if none does. The card is selected again after
+    /// every wrong key, so the next sector can be tried right away. Other
+    /// errors, such as the card being taken away, end the search. Key B is
+    /// skipped while the driver can't authenticate with it.
+    pub fn find_key(&mut self, sector: Sector, keys: &[Key]) -> Result<Option<FoundKey>, Error<E>> {
+        for key_type in [KeyType::A, KeyType::B] {
+            for &key in keys {
+                self.set_key(key_type, key);
+                match self.authenticate(sector) {
+                    Ok(()) => return Ok(Some(FoundKey { key_type, key })),
+                    Err(Error::Auth) => continue,
+                    Err(Error::KeyBUnsupported) => break,
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Wakes the card up and selects it again, for example after it has
+    /// refused a command.
+    ///
+    /// Fails with [`Error::WrongCard`] if another card answers instead.
+    pub fn reselect(&mut self) -> Result<(), Error<E>> {
+        self.authenticated = None;
+        self.rfid.stop_crypto1()?;
+        self.rfid.wupa()?;
+        let card = select_card(self.rfid)?;
+        if card.uid() != self.card.uid() {
+            return Err(Error::WrongCard);
+        }
+        Ok(())
+    }
+
+    /// Selects the card again after errors that leave it idle.
+    fn recover<T>(&mut self, result: Result<T, mfrc522::Error<E>>) -> Result<T, Error<E>> {
+        let error = match result {
+            Ok(value) => return Ok(value),
+            Err(error) => Error::from(error),
+        };
+        if error.needs_reselect() {
+            self.reselect()?;
+        }
+        Err(error)
+    }
+
+    fn authenticate_for(&mut self, block: Block) -> Result<(), Error<E>> {
+        if self.authenticated != Some(block.sector()) {
+            self.authenticate(block.sector())?;
+        }
+        Ok(())
+    }
+
+    pub fn read(&mut self, block: Block) -> Result<[u8; BLOCK_SIZE], Error<E>> {
+        self.authenticate_for(block)?;
+        let result = self.rfid.mf_read(block.index());
+        self.recover(result)
+    }
+
+    /// Writes a whole block.
+    ///
+    /// Writing a sector trailer changes its keys and access bits. Trailers
+    /// are checked the same way as in [`Session::write_trailer`]. Genuine
+    /// cards refuse writes to block 0, but magic ones take them, so the
+    /// UID check byte is checked first.
+    pub fn write(&mut self, block: Block, data: [u8; BLOCK_SIZE]) -> Result<(), Error<E>> {
+        if block.is_manufacturer() {
+            self.check_bcc(&data)?;
+        }
+        if block.is_trailer() {
+            let trailer = Trailer::from_block(&data).map_err(|_| Error::InvalidAccessBits)?;
+            return self.write_trailer(block.sector(), &trailer);
+        }
+        self.write_unchecked(block, data)
+    }
+
+    /// Writes the keys and access bits of `sector`.
+    ///
+    /// Refuses with [`Error::WouldLock`] if no key could change the trailer
+    /// afterwards.
+    pub fn write_trailer(&mut self, sector: Sector, trailer: &Trailer) -> Result<(), Error<E>> {
+        if trailer.access.is_permanent() {
+            return Err(Error::WouldLock);
+        }
+        self.write_unchecked(sector.trailer(), trailer.to_block())
+    }
+
+    /// Writes a trailer even if it makes the sector permanent, for cards
+    /// that are meant to be locked.
+    ///
+    /// The access bits are still encoded correctly, so the sector stays
+    /// readable as the new conditions allow.
+    pub fn force_write_trailer(
+        &mut self,
+        sector: Sector,
+        trailer: &Trailer,
+    ) -> Result<(), Error<E>> {
+        self.write_unchecked(sector.trailer(), trailer.to_block())
+    }
+
+    fn write_unchecked(&mut self, block: Block, data: [u8; BLOCK_SIZE]) -> Result<(), Error<E>> {
+        self.authenticate_for(block)?;
+        let result = self.rfid.mf_write(block.index(), data);
+        self.recover(result)
+    }
+
+    /// Writes block 0 of a magic card, giving it the UID at the start of
+    /// `data`.
+    ///
+    /// Write it last: the card answers with the new UID from the next
+    /// select on, so the session can't select it again. Cards that aren't
+    /// of the given kind refuse or don't answer.
+    pub fn write_manufacturer_block(
+        &mut self,
+        data: [u8; BLOCK_SIZE],
+        magic: Magic,
+    ) -> Result<(), Error<E>> {
+        let block = Block::new(0, self.layout).unwrap();
+        match magic {
+            Magic::Gen2 => self.write(block, data),
+            Magic::Gen1a => {
+                self.check_bcc(&data)?;
+                self.gen1a_write(block, data)
+            }
+        }
+    }
+
+    /// Checks the byte after a 4 byte UID, the XOR of the UID bytes.
+    /// Cards with longer UIDs don't have one.
+    fn check_bcc(&self, data: &[u8; BLOCK_SIZE]) -> Result<(), Error<E>> {
+        let bcc = data[..4].iter().fold(0, |bcc, byte| bcc ^ byte);
+        if self.card.uid().len() == 4 && data[4] != bcc {
+            return Err(Error::BadBcc);
+        }
+        Ok(())
+    }
+
+    /// Opens the backdoor and writes the block without authenticating.
+    fn gen1a_write(&mut self, block: Block, data: [u8; BLOCK_SIZE]) -> Result<(), Error<E>> {
+        self.authenticated = None;
+        self.rfid.stop_crypto1()?;
+        // The backdoor only opens for a halted card, and halting gets no
+        // answer
+        let _ = self.rfid.hlta();
+        frame::expect_ack(self.rfid, &GEN1A_UNLOCK[..1], 7)?;
+        frame::expect_ack(self.rfid, &GEN1A_UNLOCK[1..], 0)?;
+
+        let mut command = [frame::WRITE, block.index(), 0, 0];
+        append_crc(&mut command);
+        frame::expect_ack(self.rfid, &command, 0)?;
+        let mut frame = [0; BLOCK_SIZE + 2];
+        frame[..BLOCK_SIZE].copy_from_slice(&data);
+        append_crc(&mut frame);
+        frame::expect_ack(self.rfid, &frame, 0)?;
+        Ok(())
+    }
+
+    /// Reads and decodes a value block.
+    pub fn read_value(&mut self, block: Block) -> Result<ValueBlock, Error<E>> {
+        let data = self.read(block)?;
+        ValueBlock::from_block(&data).map_err(|_| Error::InvalidValueBlock)
+    }
+
+    /// Makes `block` a value block holding `value`, with its own block
+    /// number as address.
+    pub fn write_value(&mut self, block: Block, value: i32) -> Result<(), Error<E>> {
+        let value = ValueBlock {
+            value,
+            address: block.index(),
+        };
+        self.write(block, value.to_block())
+    }
+
+    /// Loads the value of `block` into the transfer buffer and adds `delta`.
+    /// Nothing is stored until [`Session::transfer`].
+    pub fn increment(&mut self, block: Block, delta: u32) -> Result<(), Error<E>> {
+        self.value_command(frame::INCREMENT, block, delta)
+    }
+
+    /// Loads the value of `block` into the transfer buffer and subtracts
+    /// `delta`. Nothing is stored until [`Session::transfer`].
+    pub fn decrement(&mut self, block: Block, delta: u32) -> Result<(), Error<E>> {
+        self.value_command(frame::DECREMENT, block, delta)
+    }
+
+    /// Loads the value of `block` into the transfer buffer unchanged, to
+    /// copy it to another block with [`Session::transfer`].
+    pub fn restore(&mut self, block: Block) -> Result<(), Error<E>> {
+        self.value_command(frame::RESTORE, block, 0)
+    }
+
+    /// Writes the transfer buffer to `block`.
+    pub fn transfer(&mut self, block: Block) -> Result<(), Error<E>> {
+        self.authenticate_for(block)?;
+        let mut command = [frame::TRANSFER, block.index(), 0, 0];
+        append_crc(&mut command);
+        let result = frame::expect_ack(self.rfid, &command, 0);
+        self.recover(result)
+    }
+
+    fn value_command(&mut self, command: u8, block: Block, operand: u32) -> Result<(), Error<E>> {
+        self.authenticate_for(block)?;
+        let mut frame = [command, block.index(), 0, 0];
+        append_crc(&mut frame);
+        let result = frame::expect_ack(self.rfid, &frame, 0);
+        self.recover(result)?;
+
+        let mut frame = [0; 6];
+        frame[..4].copy_from_slice(&operand.to_le_bytes());
+        append_crc(&mut frame);
+        // The card only answers the operand to refuse it
+        let result = match self.rfid.transceive::<1>(&frame, 0, 0) {
+            Err(mfrc522::Error::Timeout) => Ok(()),
+            Ok(_) => Err(mfrc522::Error::Nak),
+            Err(error) => Err(error),
+        };
+        self.recover(result)
+    }
+
+    /// Reads and decodes the trailer of `sector`. Key A always reads as
+    /// zeros, and so does key B unless the access bits let it be read.
+    pub fn read_trailer(&mut self, sector: Sector) -> Result<Trailer, Error<E>> {
+        let data = self.read(sector.trailer())?;
+        Trailer::from_block(&data).map_err(|_| Error::InvalidAccessBits)
+    }
+
+    /// Puts the card to sleep and turns off encryption, so the reader is
+    /// ready for the next card.
+    ///
+    /// Encryption is turned off even when the card has already gone and
+    /// doesn't answer the halt command.
+    pub fn finish(self) -> Result<(), Error<E>> {
+        let halted = self.rfid.hlta();
+        self.rfid.stop_crypto1()?;
+        Ok(halted?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::convert::Infallible;
+
+    use embedded_hal::spi::{ErrorType, Operation, SpiDevice};
+    use mfrc522::comm::blocking::spi::SpiInterface;
+
+    use super::*;
+    use crate::{AccessConditions, Condition};
+
+    /// SPI bus that reads back zeros and counts transactions
+    struct Quiet<'a> {
+        transactions: &'a Cell<usize>,
+    }
+
+    impl ErrorType for Quiet<'_> {
+        type Error = Infallible;
+    }
+
+    impl SpiDevice for Quiet<'_> {
+        fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+            for operation in operations {
+                match operation {
+                    Operation::Read(words) | Operation::TransferInPlace(words) => words.fill(0),
+                    Operation::Transfer(read, _) => read.fill(0),
+                    _ => {}
+                }
+            }
+            self.transactions.set(self.transactions.get() + 1);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn refuses_trailer_that_would_lock_the_sector() {
+        let transactions = Cell::new(0);
+        let spi = Quiet {
+            transactions: &transactions,
+        };
+        let mut rfid = Mfrc522::new(SpiInterface::new(spi)).init().unwrap();
+        let initialized = transactions.get();
+        let card = Selected::new(&[0xDE, 0xAD, 0xBE, 0xEF], 0x08);
+        let mut session = Session::new(&mut rfid, card).unwrap();
+
+        let sector = Sector::new(1, session.layout()).unwrap();
+        let trailer = Trailer {
+            key_a: DEFAULT_KEY,
+            access: AccessConditions {
+                trailer: Condition::new(true, true, true),
+                ..AccessConditions::TRANSPORT
+            },
+            user_byte: 0x69,
+            key_b: DEFAULT_KEY,
+        };
+        assert_eq!(
+            session.write_trailer(sector, &trailer),
+            Err(Error::WouldLock)
+        );
+        assert_eq!(
+            session.write(sector.trailer(), trailer.to_block()),
+            Err(Error::WouldLock)
+        );
+
+        // Neither attempt got as far as the reader
+        assert_eq!(transactions.get(), initialized);
+    }
+}
@@ -0,0 +1,370 @@
+//! MIFARE Ultralight and NTAG21x tags.
+//!
+//! These tags have no sectors and no keys. Memory is a row of 4 byte
+//! pages: pages 0 to 2 hold the 7 byte UID and lock bits, page 3 the
+//! capability container saying the tag holds NDEF, and user data starts
+//! at page 4. A read gives 4 pages at once, a write takes one.
+//!
+//! NTAG213, 215 and 216 tell which they are through GET_VERSION, can keep
+//! pages behind a 32 bit password, and count how often they were read.
+//! Those settings live in configuration pages after the user data.
+
+use mfrc522::comm::Interface;
+use mfrc522::{Initialized, Mfrc522};
+
+use crate::frame::{self, append_crc};
+use crate::ndef::{self, NdefError, EMPTY_MESSAGE, TERMINATOR_TLV};
+use crate::{select_card, Error, Selected};
+
+/// Size of one page in bytes
+pub const PAGE_SIZE: usize = 4;
+
+/// Password of NTAG tags
+pub type Password = [u8; 4];
+
+/// Password acknowledge, which the tag answers a right password with
+pub type Pack = [u8; 2];
+
+/// First page of user data
+const FIRST_DATA_PAGE: u8 = 4;
+
+/// Page holding the capability container
+const CC_PAGE: u8 = 3;
+
+/// First byte of a capability container for NDEF
+const NDEF_MAGIC: u8 = 0xE1;
+
+/// NDEF mapping version 1.0
+const NDEF_VERSION: u8 = 0x10;
+
+/// Bit of the ACCESS byte turning on the NFC read counter
+const NFC_CNT_EN: u8 = 0x10;
+
+/// The NFC read counter of NTAG tags
+const NFC_COUNTER: u8 = 0x02;
+
+/// Kinds of tag told apart by GET_VERSION
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TagType {
+    /// Original Ultralight, or any tag with an unknown version
+    Ultralight,
+    Ntag213,
+    Ntag215,
+    Ntag216,
+}
+
+impl TagType {
+    /// Works out the type from the 8 byte answer to GET_VERSION.
+    pub fn from_version(version: &[u8]) -> Option<Self> {
+        // Vendor NXP, product type NTAG, storage size
+        match version {
+            [_, 0x04, 0x04, _, _, _, 0x0F, _] => Some(TagType::Ntag213),
+            [_, 0x04, 0x04, _, _, _, 0x11, _] => Some(TagType::Ntag215),
+            [_, 0x04, 0x04, _, _, _, 0x13, _] => Some(TagType::Ntag216),
+            _ => None,
+        }
+    }
+
+    pub fn page_count(self) -> u8 {
+        match self {
+            TagType::Ultralight => 16,
+            TagType::Ntag213 => 45,
+            TagType::Ntag215 => 135,
+            TagType::Ntag216 => 231,
+        }
+    }
+
+    /// Page after the last one of user data
+    pub fn data_end(self) -> u8 {
+        match self {
+            TagType::Ultralight => 16,
+            TagType::Ntag213 => 40,
+            TagType::Ntag215 => 130,
+            TagType::Ntag216 => 226,
+        }
+    }
+
+    /// First configuration page, followed by the access, password and
+    /// password acknowledge pages. Plain Ultralight tags have none.
+    pub fn config_page(self) -> Option<u8> {
+        match self {
+            TagType::Ultralight => None,
+            TagType::Ntag213 => Some(0x29),
+            TagType::Ntag215 => Some(0x83),
+            TagType::Ntag216 => Some(0xE3),
+        }
+    }
+
+    /// Data area size in the capability container, in units of 8 bytes,
+    /// as the tags ship with it
+    fn cc_size(self) -> u8 {
+        match self {
+            TagType::Ultralight => 0x06,
+            TagType::Ntag213 => 0x12,
+            TagType::Ntag215 => 0x3E,
+            TagType::Ntag216 => 0x6D,
+        }
+    }
+
+    /// Whether writing `page` could set lock or one time bits for good
+    fn is_lock_page(self, page: u8) -> bool {
+        // NTAG tags keep their dynamic lock bits right before the
+        // configuration pages
+        page < FIRST_DATA_PAGE || self.config_page() == Some(page + 1)
+    }
+}
+
+/// Reads and writes one selected Ultralight or NTAG tag
+///
+/// ```ignore
+/// rfid.reqa()?;
+/// let card = select_card(&mut rfid)?;
+/// let mut tag = Ultralight::new(&mut rfid, card)?;
+/// let pages = tag.read(4)?;
+/// tag.finish()?;
+/// ```
+pub struct Ultralight<'a, COMM: Interface> {
+    rfid: &'a mut Mfrc522<COMM, Initialized>,
+    card: Selected,
+    tag_type: TagType,
+}
+
+impl<'a, E, COMM: Interface<Error = E>> Ultralight<'a, COMM> {
+    /// Starts a session with the tag `card`, asking it for its version to
+    /// tell its type.
+    ///
+    /// Plain Ultralight tags refuse GET_VERSION and are selected again.
+    pub fn new(rfid: &'a mut Mfrc522<COMM, Initialized>, card: Selected) -> Result<Self, Error<E>> {
+        let mut tag = Self {
+            rfid,
+            card,
+            tag_type: TagType::Ultralight,
+        };
+        match frame::exchange::<10, _, _>(tag.rfid, &[frame::GET_VERSION]) {
+            Ok(version) => {
+                tag.tag_type = TagType::from_version(&version[..8]).unwrap_or(TagType::Ultralight)
+            }
+            Err(mfrc522::Error::Nak | mfrc522::Error::Timeout) => tag.reselect()?,
+            Err(error) => return Err(error.into()),
+        }
+        Ok(tag)
+    }
+
+    pub fn uid(&self) -> &[u8] {
+        self.card.uid()
+    }
+
+    pub fn tag_type(&self) -> TagType {
+        self.tag_type
+    }
+
+    /// Wakes the tag up and selects it again, for example after it has
+    /// refused a command.
+    ///
+    /// Fails with [`Error::WrongCard`] if another tag answers instead.
+    pub fn reselect(&mut self) -> Result<(), Error<E>> {
+        self.rfid.wupa()?;
+        let card = select_card(self.rfid)?;
+        if card.uid() != self.card.uid() {
+            return Err(Error::WrongCard);
+        }
+        Ok(())
+    }
+
+    /// Selects the tag again after errors that leave it idle.
+    fn recover<T>(&mut self, result: Result<T, mfrc522::Error<E>>) -> Result<T, Error<E>> {
+        let error = match result {
+            Ok(value) => return Ok(value),
+            Err(error) => Error::from(error),
+        };
+        if error.needs_reselect() {
+            self.reselect()?;
+        }
+        Err(error)
+    }
+
+    /// Reads 4 pages from `page` on, wrapping around after the last one.
+    pub fn read(&mut self, page: u8) -> Result<[u8; 4 * PAGE_SIZE], Error<E>> {
+        let result = frame::exchange::<18, _, _>(self.rfid, &[frame::READ_PAGES, page]);
+        let answer = self.recover(result)?;
+        let mut pages = [0; 4 * PAGE_SIZE];
+        pages.copy_from_slice(&answer[..4 * PAGE_SIZE]);
+        Ok(pages)
+    }
+
+    pub fn read_page(&mut self, page: u8) -> Result<[u8; PAGE_SIZE], Error<E>> {
+        let pages = self.read(page)?;
+        Ok([pages[0], pages[1], pages[2], pages[3]])
+    }
+
+    /// Writes one page.
+    ///
+    /// Refuses with [`Error::WouldLock`] to write the UID, the lock bits
+    /// or the one time bits of the capability container, which can be set
+    /// but never cleared again.
+    pub fn write_page(&mut self, page: u8, data: [u8; PAGE_SIZE]) -> Result<(), Error<E>> {
+        if self.tag_type.is_lock_page(page) {
+            return Err(Error::WouldLock);
+        }
+        self.write_unchecked(page, data)
+    }
+
+    fn write_unchecked(&mut self, page: u8, data: [u8; PAGE_SIZE]) -> Result<(), Error<E>> {
+        let mut command = [0; 2 + PAGE_SIZE + 2];
+        command[0] = frame::WRITE_PAGE;
+        command[1] = page;
+        command[2..2 + PAGE_SIZE].copy_from_slice(&data);
+        append_crc(&mut command);
+        let result = frame::expect_ack(self.rfid, &command, 0);
+        self.recover(result)
+    }
+
+    /// Unlocks the pages behind the password of an NTAG tag, giving the
+    /// password acknowledge to check that the tag is genuine.
+    ///
+    /// A wrong password fails with [`Error::Auth`] and counts towards the
+    /// tag's limit of wrong attempts, if one is set.
+    pub fn authenticate(&mut self, password: Password) -> Result<Pack, Error<E>> {
+        let mut command = [0; 1 + 4];
+        command[0] = frame::PWD_AUTH;
+        command[1..].copy_from_slice(&password);
+        let result = frame::exchange::<4, _, _>(self.rfid, &command);
+        match self.recover(result) {
+            Ok(answer) => Ok([answer[0], answer[1]]),
+            Err(Error::Nak) => Err(Error::Auth),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Puts the pages from `first_page` on behind `password`, for writing.
+    ///
+    /// The tag only takes this if it isn't protected yet or the session
+    /// has authenticated. Plain Ultralight tags fail with
+    /// [`Error::Unsupported`].
+    pub fn set_password(
+        &mut self,
+        password: Password,
+        pack: Pack,
+        first_page: u8,
+    ) -> Result<(), Error<E>> {
+        let config = self.config_page()?;
+        self.write_unchecked(config + 2, password)?;
+        self.write_unchecked(config + 3, [pack[0], pack[1], 0, 0])?;
+        // AUTH0 goes last, once the password is in place
+        let mut cfg0 = self.read_page(config)?;
+        cfg0[3] = first_page;
+        self.write_unchecked(config, cfg0)
+    }
+
+    /// Turns on the counter of NFC reads, see [`Ultralight::read_counter`].
+    pub fn enable_counter(&mut self) -> Result<(), Error<E>> {
+        let config = self.config_page()?;
+        let mut access = self.read_page(config + 1)?;
+        access[0] |= NFC_CNT_EN;
+        self.write_unchecked(config + 1, access)
+    }
+
+    /// Reads how often the tag has been read over NFC since the counter
+    /// was turned on. Tags with the counter off refuse.
+    pub fn read_counter(&mut self) -> Result<u32, Error<E>> {
+        self.config_page()?;
+        let result = frame::exchange::<5, _, _>(self.rfid, &[frame::READ_CNT, NFC_COUNTER]);
+        let answer = self.recover(result)?;
+        Ok(u32::from_le_bytes([answer[0], answer[1], answer[2], 0]))
+    }
+
+    fn config_page(&self) -> Result<u8, Error<E>> {
+        self.tag_type.config_page().ok_or(Error::Unsupported)
+    }
+
+    /// Reads the capability container, failing if it doesn't announce
+    /// NDEF. Gives the size of the data area in bytes.
+    fn ndef_capacity(&mut self) -> Result<usize, Error<E>> {
+        let cc = self.read_page(CC_PAGE)?;
+        if cc[0] != NDEF_MAGIC {
+            return Err(Error::Ndef(NdefError::NoMessage));
+        }
+        let data_pages = self.tag_type.data_end() - FIRST_DATA_PAGE;
+        Ok((cc[2] as usize * 8).min(data_pages as usize * PAGE_SIZE))
+    }
+
+    /// Reads the data area into `buffer` and gives the NDEF message found
+    /// in it, which [`parse_ndef`](crate::parse_ndef) splits into records.
+    ///
+    /// Reading stops as soon as the whole message is in. A message longer
+    /// than `buffer` fails with [`NdefError::TooBig`].
+    pub fn read_ndef<'b>(&mut self, buffer: &'b mut [u8]) -> Result<&'b [u8], Error<E>> {
+        let capacity = self.ndef_capacity()?;
+        let mut len = 0;
+        let mut page = FIRST_DATA_PAGE;
+        while len < capacity {
+            // Anything but running out of data means the message is in
+            if ndef::find_message(&buffer[..len]) != Err(NdefError::Malformed) {
+                break;
+            }
+            let chunk = (capacity - len).min(4 * PAGE_SIZE);
+            if len + chunk > buffer.len() {
+                return Err(Error::Ndef(NdefError::TooBig));
+            }
+            let pages = self.read(page)?;
+            buffer[len..len + chunk].copy_from_slice(&pages[..chunk]);
+            len += chunk;
+            page += 4;
+        }
+
+        let buffer: &'b [u8] = buffer;
+        ndef::find_message(&buffer[..len]).map_err(Error::Ndef)
+    }
+
+    /// Writes an NDEF message made with [`encode_ndef`](crate::encode_ndef)
+    /// to a tag formatted for NDEF.
+    ///
+    /// Only the pages the message needs are written.
+    pub fn write_ndef(&mut self, message: &[u8]) -> Result<(), Error<E>> {
+        let mut header = [0; 4];
+        let header_len = ndef::tlv_header(message.len(), &mut header).map_err(Error::Ndef)?;
+        let len = header_len + message.len() + 1;
+        if len > self.ndef_capacity()? {
+            return Err(Error::Ndef(NdefError::TooBig));
+        }
+
+        let mut bytes = header[..header_len]
+            .iter()
+            .chain(message)
+            .chain(&[TERMINATOR_TLV])
+            .copied();
+        for page in FIRST_DATA_PAGE..FIRST_DATA_PAGE + len.div_ceil(PAGE_SIZE) as u8 {
+            let mut data = [0; PAGE_SIZE];
+            for byte in data.iter_mut() {
+                *byte = bytes.next().unwrap_or(0);
+            }
+            self.write_page(page, data)?;
+        }
+        Ok(())
+    }
+
+    /// Prepares a tag for NDEF with an empty message.
+    ///
+    /// NTAG tags ship formatted. On a blank Ultralight this writes the
+    /// capability container, whose bits can't be cleared again. Tags whose
+    /// container is neither blank nor NDEF fail with
+    /// [`Error::Unsupported`].
+    pub fn format_ndef(&mut self) -> Result<(), Error<E>> {
+        let cc = self.read_page(CC_PAGE)?;
+        if cc == [0; PAGE_SIZE] {
+            let cc = [NDEF_MAGIC, NDEF_VERSION, self.tag_type.cc_size(), 0x00];
+            self.write_unchecked(CC_PAGE, cc)?;
+        } else if cc[0] != NDEF_MAGIC {
+            return Err(Error::Unsupported);
+        }
+        let mut data = [0; PAGE_SIZE];
+        data[..EMPTY_MESSAGE.len()].copy_from_slice(&EMPTY_MESSAGE);
+        self.write_page(FIRST_DATA_PAGE, data)
+    }
+
+    /// Puts the tag to sleep, so the reader is ready for the next one.
+    pub fn finish(self) -> Result<(), Error<E>> {
+        Ok(self.rfid.hlta()?)
+    }
+}
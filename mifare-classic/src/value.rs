@@ -0,0 +1,109 @@
+//! Value blocks.
+//!
+//! A data block can hold a signed 32 bit value that the card adds to and
+//! subtracts from itself. The value is stored three times, once inverted,
+//! followed by an address byte stored four times, so a torn write shows
+//! up as a block that no longer decodes.
+//!
+//! Changes go through the card's transfer buffer: increment, decrement
+//! and restore load a block's value into it and change it there, and
+//! transfer writes it to a block, which may be another one in the same
+//! sector. The access bits decide which key may do what.
+
+use crate::BLOCK_SIZE;
+
+/// A block that doesn't hold a value in the value block format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InvalidValueBlock;
+
+/// Decoded contents of a value block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ValueBlock {
+    pub value: i32,
+    /// Free for the application, usually the number of the block, for
+    /// example to find the original of a backup copy
+    pub address: u8,
+}
+
+impl ValueBlock {
+    pub fn from_block(block: &[u8; BLOCK_SIZE]) -> Result<Self, InvalidValueBlock> {
+        let word =
+            |i: usize| u32::from_le_bytes([block[i], block[i + 1], block[i + 2], block[i + 3]]);
+        let value = word(0);
+        let address = block[12];
+        if word(4) != !value
+            || word(8) != value
+            || block[13] != !address
+            || block[14] != address
+            || block[15] != !address
+        {
+            return Err(InvalidValueBlock);
+        }
+        Ok(Self {
+            value: value as i32,
+            address,
+        })
+    }
+
+    pub fn to_block(&self) -> [u8; BLOCK_SIZE] {
+        let value = self.value.to_le_bytes();
+        let inverted = (!self.value).to_le_bytes();
+        let mut block = [0; BLOCK_SIZE];
+        block[0..4].copy_from_slice(&value);
+        block[4..8].copy_from_slice(&inverted);
+        block[8..12].copy_from_slice(&value);
+        block[12..16].copy_from_slice(&[self.address, !self.address, self.address, !self.address]);
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HUNDRED_AT_4: [u8; BLOCK_SIZE] = [
+        0x64, 0, 0, 0, 0x9B, 0xFF, 0xFF, 0xFF, 0x64, 0, 0, 0, 4, 0xFB, 4, 0xFB,
+    ];
+
+    #[test]
+    fn encodes_value_inverted_and_repeated() {
+        let block = ValueBlock {
+            value: 100,
+            address: 4,
+        };
+        assert_eq!(block.to_block(), HUNDRED_AT_4);
+    }
+
+    #[test]
+    fn round_trips() {
+        for value in [0, 1, -1, 100, i32::MIN, i32::MAX] {
+            for address in [0, 4, 0x80, 0xFF] {
+                let block = ValueBlock { value, address };
+                assert_eq!(ValueBlock::from_block(&block.to_block()), Ok(block));
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_any_changed_byte() {
+        for i in 0..BLOCK_SIZE {
+            let mut block = HUNDRED_AT_4;
+            block[i] ^= 0x01;
+            assert_eq!(
+                ValueBlock::from_block(&block),
+                Err(InvalidValueBlock),
+                "byte {i}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_blank_block() {
+        assert_eq!(
+            ValueBlock::from_block(&[0; BLOCK_SIZE]),
+            Err(InvalidValueBlock)
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Snapping frequencies to the notes of a scale.
+//!
+//! A theremin-style instrument sounds nicer when it lands on real notes
+//! instead of sliding through every frequency in between.
+//! [`Scale::quantize`] picks the closest note from the table in this crate
+//! that belongs to the scale.
+
+use crate::*;
+
+/// Every note from `NOTE_B0` to `NOTE_DS8`, a semitone apart
+pub const CHROMATIC: [f64; 89] = [
+    NOTE_B0, NOTE_C1, NOTE_CS1, NOTE_D1, NOTE_DS1, NOTE_E1, NOTE_F1, NOTE_FS1, NOTE_G1, NOTE_GS1,
+    NOTE_A1, NOTE_AS1, NOTE_B1, NOTE_C2, NOTE_CS2, NOTE_D2, NOTE_DS2, NOTE_E2, NOTE_F2, NOTE_FS2,
+    NOTE_G2, NOTE_GS2, NOTE_A2, NOTE_AS2, NOTE_B2, NOTE_C3, NOTE_CS3, NOTE_D3, NOTE_DS3, NOTE_E3,
+    NOTE_F3, NOTE_FS3, NOTE_G3, NOTE_GS3, NOTE_A3, NOTE_AS3, NOTE_B3, NOTE_C4, NOTE_CS4, NOTE_D4,
+    NOTE_DS4, NOTE_E4, NOTE_F4, NOTE_FS4, NOTE_G4, NOTE_GS4, NOTE_A4, NOTE_AS4, NOTE_B4, NOTE_C5,
+    NOTE_CS5, NOTE_D5, NOTE_DS5, NOTE_E5, NOTE_F5, NOTE_FS5, NOTE_G5, NOTE_GS5, NOTE_A5, NOTE_AS5,
+    NOTE_B5, NOTE_C6, NOTE_CS6, NOTE_D6, NOTE_DS6, NOTE_E6, NOTE_F6, NOTE_FS6, NOTE_G6, NOTE_GS6,
+    NOTE_A6, NOTE_AS6, NOTE_B6, NOTE_C7, NOTE_CS7, NOTE_D7, NOTE_DS7, NOTE_E7, NOTE_F7, NOTE_FS7,
+    NOTE_G7, NOTE_GS7, NOTE_A7, NOTE_AS7, NOTE_B7, NOTE_C8, NOTE_CS8, NOTE_D8, NOTE_DS8,
+];
+
+/// Semitones above C of `CHROMATIC[0]`, which is a B
+const FIRST_PITCH_CLASS: usize = 11;
+
+/// Key of a scale, as the note it starts on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    C,
+    CS,
+    D,
+    DS,
+    E,
+    F,
+    FS,
+    G,
+    GS,
+    A,
+    AS,
+    B,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scale {
+    /// Every semitone
+    Chromatic,
+    Major,
+    /// Major scale without the 4th and 7th, nothing clashes
+    MajorPentatonic,
+    MinorPentatonic,
+}
+
+impl Scale {
+    /// Semitones of each note of the scale above its key
+    pub const fn intervals(self) -> &'static [u8] {
+        match self {
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
+        }
+    }
+
+    /// Whether `CHROMATIC[index]` is in this scale in `key`
+    pub fn contains(self, key: Key, index: usize) -> bool {
+        let semitones = (index + FIRST_PITCH_CLASS + 12 - key as usize) % 12;
+        self.intervals().contains(&(semitones as u8))
+    }
+
+    /// Closest note of the scale in `key` to `freq`, judged by pitch rather
+    /// than by Hz. Zero or negative frequencies come back as [`REST`].
+    pub fn quantize(self, key: Key, freq: f64) -> f64 {
+        if freq <= 0.0 {
+            return REST;
+        }
+
+        let mut best = REST;
+        let mut best_ratio = f64::MAX;
+        for (index, &note) in CHROMATIC.iter().enumerate() {
+            if !self.contains(key, index) {
+                continue;
+            }
+            let ratio = if note > freq {
+                note / freq
+            } else {
+                freq / note
+            };
+            if ratio < best_ratio {
+                best = note;
+                best_ratio = ratio;
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frequency halfway between two notes in pitch
+    fn between(low: f64, high: f64) -> f64 {
+        (low * high).sqrt()
+    }
+
+    #[test]
+    fn notes_of_the_scale_stay_put() {
+        for &note in &CHROMATIC {
+            assert_eq!(Scale::Chromatic.quantize(Key::C, note), note);
+        }
+        for note in [
+            NOTE_C4, NOTE_D4, NOTE_E4, NOTE_F4, NOTE_G4, NOTE_A4, NOTE_B4,
+        ] {
+            assert_eq!(Scale::Major.quantize(Key::C, note), note);
+        }
+    }
+
+    #[test]
+    fn snaps_to_the_closer_note_by_pitch() {
+        let middle = between(NOTE_E4, NOTE_F4);
+        assert_eq!(Scale::Major.quantize(Key::C, middle * 0.999), NOTE_E4);
+        assert_eq!(Scale::Major.quantize(Key::C, middle * 1.001), NOTE_F4);
+
+        // F# isn't in C major, so it goes to F or G, a semitone either way
+        let middle = between(NOTE_F4, NOTE_G4);
+        assert_eq!(Scale::Major.quantize(Key::C, middle * 0.999), NOTE_F4);
+        assert_eq!(Scale::Major.quantize(Key::C, middle * 1.001), NOTE_G4);
+    }
+
+    #[test]
+    fn wraps_around_the_octave() {
+        // B is the last note of an octave and C the first of the next
+        let middle = between(NOTE_B3, NOTE_C4);
+        assert_eq!(Scale::Major.quantize(Key::C, middle * 0.999), NOTE_B3);
+        assert_eq!(Scale::Major.quantize(Key::C, middle * 1.001), NOTE_C4);
+
+        // B major has C# rather than C, and starts on the table's first note
+        assert!(Scale::Major.contains(Key::B, 0));
+        assert!(!Scale::Major.contains(Key::B, 1));
+        assert!(Scale::Major.contains(Key::B, 2));
+    }
+
+    #[test]
+    fn pentatonic_leaves_out_the_4th_and_7th() {
+        assert_eq!(Scale::Major.quantize(Key::C, NOTE_F4), NOTE_F4);
+        assert_eq!(Scale::MajorPentatonic.quantize(Key::C, NOTE_F4), NOTE_E4);
+        assert_eq!(Scale::Major.quantize(Key::C, NOTE_B4), NOTE_B4);
+        assert_eq!(Scale::MajorPentatonic.quantize(Key::C, NOTE_B4), NOTE_C5);
+    }
+
+    #[test]
+    fn relative_pentatonics_share_their_notes() {
+        for index in 0..CHROMATIC.len() {
+            assert_eq!(
+                Scale::MajorPentatonic.contains(Key::C, index),
+                Scale::MinorPentatonic.contains(Key::A, index),
+                "index {index}"
+            );
+        }
+    }
+
+    #[test]
+    fn stays_inside_the_table() {
+        assert_eq!(Scale::Chromatic.quantize(Key::C, 1.0), NOTE_B0);
+        assert_eq!(Scale::Chromatic.quantize(Key::C, 20_000.0), NOTE_DS8);
+        assert_eq!(Scale::Major.quantize(Key::C, 0.0), REST);
+        assert_eq!(Scale::Major.quantize(Key::C, -440.0), REST);
+    }
+}
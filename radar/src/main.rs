@@ -0,0 +1,243 @@
+//! # Ultrasonic Radar Example
+//!
+//! Sweeps an HC-SR04 mounted on a servo from 0° to 180° and back, taking
+//! one distance reading per step. The echoes are drawn as a radar screen
+//! on an SSD1306 OLED and sent over USB serial as `angle,distance` lines,
+//! with the distance in cm or `-` when nothing was in range.
+//!
+//! | Part     | Pins                              |
+//! |----------|-----------------------------------|
+//! | Servo    | GPIO 9 (PWM slice 4 channel B)    |
+//! | HC-SR04  | TRIG GPIO 17, ECHO GPIO 16 (PIO0) |
+//! | SSD1306  | SDA GPIO 18, SCL GPIO 19 (I2C1)   |
+//!
+//! The servo uses the pulse widths saved by the servo crate's `calibrate`
+//! example when there are any.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use hal::fugit::RateExtU32;
+use hal::gpio::{FunctionI2C, Pin};
+use hal::pio::PIOExt;
+use hal::Clock;
+use heapless::String;
+use panic_halt as _;
+use rp235x_hal as hal;
+use servo::flash::load_calibration;
+use servo::{PwmSettings, Servo, ServoConfig, SERVO_FREQ_HZ};
+use ssd1306::{prelude::*, I2CDisplayInterface, Ssd1306};
+use ultrasonic::pio_echo::PioHcsr04;
+use ultrasonic::{Config, Distance};
+use usb_device::{class_prelude::*, prelude::*};
+use usbd_serial::SerialPort;
+
+mod plot;
+mod sweep;
+
+use plot::RadarPlot;
+use sweep::Sweep;
+
+/// Tell the Boot ROM about our application
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
+
+/// External high-speed crystal on the Raspberry Pi Pico 2 board is 12 MHz.
+/// Adjust if your board has a different frequency
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Sweep positions from 0° to 180°, 3° apart
+const STEPS: usize = 61;
+
+/// Edge of the radar screen, which also keeps echoes short
+const MAX_RANGE: Distance = Distance::from_cm(200.0);
+
+/// Time for the servo to reach the next step and stop shaking before the
+/// sensor is fired. This also gives the last burst time to die away.
+const SETTLE_US: u64 = 40_000;
+
+/// Time to swing back to 0° at power up
+const START_US: u64 = 1_000_000;
+
+/// Used until the `calibrate` example has saved measured pulse widths
+const SERVO_CONFIG: ServoConfig = ServoConfig {
+    min_pulse_us: 500,
+    center_pulse_us: 1450,
+    max_pulse_us: 2400,
+    range_degrees: 180.0,
+    inverted: false,
+};
+
+#[hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // The single-cycle I/O block controls our GPIO pins
+    let sio = hal::Sio::new(pac.SIO);
+
+    // Set the pins up according to their function on this particular board
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    let sys_clk_hz = clocks.system_clock.freq().to_Hz();
+
+    // For USB Serial
+    let usb_bus = UsbBusAllocator::new(hal::usb::UsbBus::new(
+        pac.USB,
+        pac.USB_DPRAM,
+        clocks.usb_clock,
+        true,
+        &mut pac.RESETS,
+    ));
+
+    // Set up the USB Communications Class Device driver
+    let mut serial = SerialPort::new(&usb_bus);
+
+    // Create a USB device with a fake VID and PID
+    let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
+        .strings(&[StringDescriptors::default()
+            .manufacturer("Fake company")
+            .product("Ultrasonic radar")
+            .serial_number("TEST")])
+        .unwrap()
+        .device_class(2) // from: https://www.usb.org/defined-class-codes
+        .build();
+
+    // Servo
+    let mut pwm_slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
+    let pwm_settings = PwmSettings::for_frequency(sys_clk_hz, SERVO_FREQ_HZ);
+    let pwm = &mut pwm_slices.pwm4;
+    pwm.set_div_int(pwm_settings.div_int);
+    pwm.set_div_frac(pwm_settings.div_frac);
+    pwm.set_top(pwm_settings.top);
+    pwm.enable();
+
+    let channel = &mut pwm.channel_b;
+    channel.output_to(pins.gpio9);
+
+    // Prefer the pulse widths measured with the `calibrate` example for
+    // channel 0
+    let servo_config = load_calibration(0)
+        .map(|calibration| calibration.apply(SERVO_CONFIG))
+        .unwrap_or(SERVO_CONFIG);
+    let mut servo = Servo::new(channel, servo_config, pwm_settings);
+
+    // Sensor
+    // Pulled down so an unplugged sensor reads as no echo, not stuck
+    let echo = pins
+        .gpio16
+        .into_pull_down_input()
+        .into_function::<hal::gpio::FunctionPio0>();
+    let trigger = pins.gpio17.into_function::<hal::gpio::FunctionPio0>();
+
+    let (mut pio, sm0, _, _, _) = pac.PIO0.split(&mut pac.RESETS);
+    let mut sensor = PioHcsr04::new(
+        &mut pio,
+        sm0,
+        trigger.id().num,
+        echo.id().num,
+        sys_clk_hz,
+        Config {
+            max_range: MAX_RANGE,
+            ..Config::default()
+        },
+    )
+    .unwrap();
+
+    // Display
+    let sda_pin: Pin<_, FunctionI2C, _> = pins.gpio18.reconfigure();
+    let scl_pin: Pin<_, FunctionI2C, _> = pins.gpio19.reconfigure();
+
+    let i2c = hal::I2C::i2c1(
+        pac.I2C1,
+        sda_pin,
+        scl_pin,
+        400.kHz(),
+        &mut pac.RESETS,
+        &clocks.system_clock,
+    );
+
+    let interface = I2CDisplayInterface::new(i2c);
+    let mut display = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0)
+        .into_buffered_graphics_mode();
+    display.init().unwrap();
+
+    let mut sweep = Sweep::<STEPS>::new();
+    let mut plot = RadarPlot::<STEPS>::new(MAX_RANGE);
+    servo.set_angle(sweep.angle());
+
+    let mut out: String<32> = String::new();
+    let mut measure_at = timer.get_counter().ticks() + START_US;
+    loop {
+        let _ = usb_dev.poll(&mut [&mut serial]);
+
+        let now = timer.get_counter().ticks();
+        if now >= measure_at && !sensor.is_pending() {
+            sensor.start();
+        }
+
+        let Some(result) = sensor.poll() else {
+            continue;
+        };
+
+        // Errors, including nothing in range, show as an empty step
+        let distance = result.ok();
+        plot.record(sweep.index(), distance);
+
+        out.clear();
+        let _ = match distance {
+            Some(distance) => write!(out, "{:.0},{:.1}\r\n", sweep.angle(), distance.as_cm()),
+            None => write!(out, "{:.0},-\r\n", sweep.angle()),
+        };
+        let _ = serial.write(out.as_bytes());
+
+        // Start moving before redrawing, so the servo settles meanwhile
+        servo.set_angle(sweep.advance());
+        measure_at = timer.get_counter().ticks() + SETTLE_US;
+
+        display.clear(BinaryColor::Off).unwrap();
+        plot.draw(&mut display).unwrap();
+        display.flush().unwrap();
+    }
+}
+
+/// Program metadata for `picotool info`
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"Ultrasonic Radar Example"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
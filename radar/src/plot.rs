@@ -0,0 +1,162 @@
+//! Radar screen on a monochrome display.
+//!
+//! The sensor sits at the middle of the bottom edge, with 0° to the right
+//! and 180° to the left. Each echo is drawn as a blip at its angle and
+//! distance. The display can't dim single pixels, so blips fade by
+//! shrinking as the beam moves away from them, and vanish after one sweep
+//! unless the beam finds them again.
+
+use core::fmt::Write;
+
+use embedded_graphics::mono_font::iso_8859_1::FONT_5X8;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Arc, Circle, Line, PrimitiveStyle};
+use embedded_graphics::text::{Alignment, Baseline, Text, TextStyleBuilder};
+use heapless::String;
+use ultrasonic::Distance;
+
+use crate::sweep::Sweep;
+
+/// Echoes seen less than this many steps ago are drawn at full size
+const FRESH_STEPS: u32 = 8;
+
+#[derive(Clone, Copy)]
+struct Echo {
+    distance: Distance,
+    /// Value of `RadarPlot::steps` when the echo was seen
+    seen_at: u32,
+}
+
+/// Latest echo at each of the `N` sweep positions
+pub struct RadarPlot<const N: usize> {
+    echoes: [Option<Echo>; N],
+    max_range: Distance,
+    /// Position of the last reading
+    beam: usize,
+    /// Readings recorded so far, used as the clock for fading
+    steps: u32,
+}
+
+impl<const N: usize> RadarPlot<N> {
+    /// Creates an empty screen. Echoes at `max_range` are drawn on the
+    /// outer ring.
+    pub fn new(max_range: Distance) -> Self {
+        Self {
+            echoes: [None; N],
+            max_range,
+            beam: 0,
+            steps: 0,
+        }
+    }
+
+    /// Stores the reading at sweep position `index`, `None` when nothing
+    /// was in range, and moves the beam there.
+    pub fn record(&mut self, index: usize, distance: Option<Distance>) {
+        let Some(slot) = self.echoes.get_mut(index) else {
+            return;
+        };
+        self.steps = self.steps.wrapping_add(1);
+        *slot = distance.map(|distance| Echo {
+            distance,
+            seen_at: self.steps,
+        });
+        self.beam = index;
+    }
+
+    /// Draws the whole screen. The target is expected to be cleared first.
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let area = target.bounding_box();
+        let size = area.size.width.min(area.size.height * 2);
+        // Leave a pixel at the top so the outer ring isn't cut off
+        let radius = (size / 2).saturating_sub(1);
+        let origin = Point::new(
+            area.top_left.x + area.size.width as i32 / 2,
+            area.bottom_right().unwrap_or(area.top_left).y,
+        );
+
+        // Range rings at half and full range, and the base line
+        let ring = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+        for diameter in [radius, radius * 2] {
+            Arc::with_center(origin, diameter, 180.0.deg(), 180.0.deg())
+                .into_styled(ring)
+                .draw(target)?;
+        }
+        Line::new(
+            origin - Point::new(radius as i32, 0),
+            origin + Point::new(radius as i32, 0),
+        )
+        .into_styled(ring)
+        .draw(target)?;
+
+        // Beam
+        let beam_angle = Sweep::<N>::angle_of(self.beam);
+        Line::new(origin, polar(origin, radius as f32, beam_angle))
+            .into_styled(ring)
+            .draw(target)?;
+
+        // Blips, shrinking from 3 pixels across to 1 before vanishing
+        let max_mm = self.max_range.as_mm();
+        let fade_steps = N as u32;
+        for (index, echo) in self.echoes.iter().enumerate() {
+            let Some(echo) = echo else {
+                continue;
+            };
+            let age = self.steps.wrapping_sub(echo.seen_at);
+            let diameter = match age {
+                age if age < FRESH_STEPS => 3,
+                age if age < fade_steps / 2 => 2,
+                age if age < fade_steps => 1,
+                _ => continue,
+            };
+            let r = (echo.distance.as_mm() / max_mm).min(1.0) * radius as f32;
+            let centre = polar(origin, r, Sweep::<N>::angle_of(index));
+            Circle::with_center(centre, diameter)
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                .draw(target)?;
+        }
+
+        // Scale and latest reading in the corners, clear of the rings
+        let font = MonoTextStyle::new(&FONT_5X8, BinaryColor::On);
+        let mut text: String<16> = String::new();
+        let _ = write!(text, "{:.0}cm", self.max_range.as_cm());
+        Text::with_baseline(&text, area.top_left, font, Baseline::Top).draw(target)?;
+
+        text.clear();
+        let _ = match self.echoes.get(self.beam).copied().flatten() {
+            Some(echo) => write!(text, "{:.0}cm", echo.distance.as_cm()),
+            None => write!(text, "-"),
+        };
+        let right = TextStyleBuilder::new()
+            .alignment(Alignment::Right)
+            .baseline(Baseline::Top)
+            .build();
+        let top_right = Point::new(
+            area.top_left.x + area.size.width as i32 - 1,
+            area.top_left.y,
+        );
+        Text::with_text_style(&text, top_right, font, right).draw(target)?;
+
+        text.clear();
+        let _ = write!(text, "{:.0}\u{b0}", beam_angle);
+        let line_height = FONT_5X8.character_size.height as i32;
+        Text::with_text_style(&text, top_right + Point::new(0, line_height), font, right)
+            .draw(target)?;
+
+        Ok(())
+    }
+}
+
+/// Point `r` pixels from `origin` at `degrees`, counted anticlockwise from
+/// the right
+fn polar(origin: Point, r: f32, degrees: f32) -> Point {
+    let radians = degrees.to_radians();
+    Point::new(
+        origin.x + libm::roundf(r * libm::cosf(radians)) as i32,
+        origin.y - libm::roundf(r * libm::sinf(radians)) as i32,
+    )
+}
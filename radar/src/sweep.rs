@@ -0,0 +1,51 @@
+/// Servo positions for a back and forth sweep over 0–180°
+///
+/// The `N` positions are spread evenly, so position 0 is 0° and position
+/// `N - 1` is 180°. The end positions are visited once per turn, not twice.
+pub struct Sweep<const N: usize> {
+    index: usize,
+    forward: bool,
+}
+
+impl<const N: usize> Sweep<N> {
+    /// Starts at 0°, heading towards 180°.
+    pub fn new() -> Self {
+        const { assert!(N >= 2) };
+        Self {
+            index: 0,
+            forward: true,
+        }
+    }
+
+    /// Current position
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Angle of the current position in degrees
+    pub fn angle(&self) -> f32 {
+        Self::angle_of(self.index)
+    }
+
+    /// Angle of any position in degrees
+    pub fn angle_of(index: usize) -> f32 {
+        index as f32 * 180.0 / (N - 1) as f32
+    }
+
+    /// Moves on to the next position, turning round at either end, and
+    /// returns its angle.
+    pub fn advance(&mut self) -> f32 {
+        if self.forward && self.index == N - 1 {
+            self.forward = false;
+        } else if !self.forward && self.index == 0 {
+            self.forward = true;
+        }
+
+        if self.forward {
+            self.index += 1;
+        } else {
+            self.index -= 1;
+        }
+        self.angle()
+    }
+}
@@ -15,6 +15,10 @@ use embedded_hal_bus::spi::ExclusiveDevice;
 
 use mfrc522::{comm::blocking::spi::SpiInterface, Mfrc522};
 
+use mifare_classic::{
+    select_card, AccessConditions, Error, Hex, KeyType, Sector, Session, Trailer, DEFAULT_KEY,
+};
+
 use hal::fugit::RateExtU32;
 
 #[link_section = ".start_block"]
@@ -88,95 +92,103 @@ fn main() -> ! {
     let itf = SpiInterface::new(spi);
     let mut rfid = Mfrc522::new(itf).init().unwrap();
 
-    let target_sector = 1;
-    let rel_block = 3;
-    const DATA: [u8; 16] = [
-        0x52, 0x75, 0x73, 0x74, 0x65, 0x64, // Key A: "Rusted"
-        0xFF, 0x07, 0x80, 0x69, // Access bits and trailer byte
-        0x46, 0x65, 0x72, 0x72, 0x69, 0x73, // Key B: "Ferris"
-    ];
-    let current_key = &[0xFF; 6];
-    let new_key: &[u8; 6] = &DATA[..6].try_into().unwrap();
+    // New keys, keeping the access bits blank cards ship with. The session
+    // checks the access bits before writing, so a typo here can't lock the
+    // sector.
+    const TRAILER: Trailer = Trailer {
+        key_a: *b"Rusted",
+        access: AccessConditions::TRANSPORT,
+        user_byte: 0x69,
+        key_b: *b"Ferris",
+    };
 
     loop {
         let _ = usb_dev.poll(&mut [&mut serial]);
 
-        if let Ok(atqa) = rfid.reqa() {
-            if let Ok(uid) = rfid.select(&atqa) {
-                serial
-                    .write("\r\n----Before Write----\r\n".as_bytes())
-                    .unwrap();
-                if let Err(e) =
-                    read_sector(&uid, target_sector, current_key, &mut rfid, &mut serial)
-                {
-                    serial.write(e.as_bytes()).unwrap();
+        if rfid.reqa().is_ok() {
+            if let Ok(card) = select_card(&mut rfid) {
+                let mut session = match Session::new(&mut rfid, card) {
+                    Ok(session) => session,
+                    Err(e) => {
+                        report_error("Not a MIFARE Classic card", e, &mut serial);
+                        let _ = rfid.hlta();
+                        continue;
+                    }
+                };
+                let target_sector = Sector::new(1, session.layout()).unwrap();
+                session.set_key(KeyType::A, DEFAULT_KEY);
+
+                let _ = serial.write("\r\n----Before Write----\r\n".as_bytes());
+                if let Err(e) = read_sector(&mut session, target_sector, &mut serial) {
+                    report_error("Read failed", e, &mut serial);
                 }
 
-                if let Err(e) =
-                    write_block(&uid, target_sector, rel_block, DATA, current_key, &mut rfid)
-                {
-                    serial.write(e.as_bytes()).unwrap();
+                if let Err(e) = session.write_trailer(target_sector, &TRAILER) {
+                    report_error("Write failed", e, &mut serial);
                 }
 
-                serial
-                    .write("\r\n----After Write----\r\n".as_bytes())
-                    .unwrap();
-                if let Err(e) = read_sector(&uid, target_sector, new_key, &mut rfid, &mut serial) {
-                    serial.write(e.as_bytes()).unwrap();
+                let _ = serial.write("\r\n----After Write----\r\n".as_bytes());
+                session.set_key(KeyType::A, TRAILER.key_a);
+                if let Err(e) = read_sector(&mut session, target_sector, &mut serial) {
+                    report_error("Read failed", e, &mut serial);
+                }
+                if let Err(e) = print_access(&mut session, target_sector, &mut serial) {
+                    report_error("Read failed", e, &mut serial);
                 }
-                rfid.hlta().unwrap();
-                rfid.stop_crypto1().unwrap();
+                // The card may already have been taken away
+                let _ = session.finish();
             }
         }
     }
 }
 
-fn write_block<E, COMM: mfrc522::comm::Interface<Error = E>>(
-    uid: &mfrc522::Uid,
-    sector: u8,
-    rel_block: u8,
-    data: [u8; 16],
-    key: &[u8; 6],
-    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
-) -> Result<(), &'static str> {
-    let block_offset = sector * 4;
-    let abs_block = block_offset + rel_block;
-
-    rfid.mf_authenticate(uid, block_offset, key)
-        .map_err(|_| "Auth failed")?;
-
-    rfid.mf_write(abs_block, data).map_err(|_| "Write failed")?;
-
+fn read_sector<COMM: mfrc522::comm::Interface, B: UsbBus>(
+    session: &mut Session<COMM>,
+    sector: Sector,
+    serial: &mut SerialPort<B>,
+) -> Result<(), Error<COMM::Error>> {
+    let mut buff: String<64> = String::new();
+    for block in sector.blocks() {
+        let data = session.read(block)?;
+        write!(buff, "{}\r\n", Hex(&data)).unwrap();
+        let _ = serial.write(buff.as_bytes());
+        buff.clear();
+    }
     Ok(())
 }
 
-fn read_sector<E, COMM: mfrc522::comm::Interface<Error = E>, B: UsbBus>(
-    uid: &mfrc522::Uid,
-    sector: u8,
-    key: &[u8; 6],
-    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+/// Prints who may read and write what in the sector.
+fn print_access<COMM: mfrc522::comm::Interface, B: UsbBus>(
+    session: &mut Session<COMM>,
+    sector: Sector,
     serial: &mut SerialPort<B>,
-) -> Result<(), &'static str> {
-    let block_offset = sector * 4;
-    rfid.mf_authenticate(uid, block_offset, key)
-        .map_err(|_| "Auth failed")?;
-
-    for abs_block in block_offset..block_offset + 4 {
-        let data = rfid.mf_read(abs_block).map_err(|_| "Read failed")?;
-        print_hex_to_serial(&data, serial);
-        serial
-            .write("\r\n".as_bytes())
-            .map_err(|_| "Write failed")?;
+) -> Result<(), Error<COMM::Error>> {
+    let access = session.read_trailer(sector)?.access;
+    let mut buff: String<128> = String::new();
+    for group in 0..3 {
+        write!(
+            buff,
+            "data {}: {}\r\n",
+            group,
+            access.data_permissions(group)
+        )
+        .unwrap();
+        let _ = serial.write(buff.as_bytes());
+        buff.clear();
     }
+    write!(buff, "trailer: {}\r\n", access.trailer_permissions()).unwrap();
+    let _ = serial.write(buff.as_bytes());
     Ok(())
 }
 
-fn print_hex_to_serial<B: UsbBus>(data: &[u8], serial: &mut SerialPort<B>) {
+fn report_error<E: core::fmt::Debug, B: UsbBus>(
+    action: &str,
+    error: Error<E>,
+    serial: &mut SerialPort<B>,
+) {
     let mut buff: String<64> = String::new();
-    for &d in data.iter() {
-        write!(buff, "{:02x} ", d).unwrap();
-    }
-    serial.write(buff.as_bytes()).unwrap();
+    let _ = write!(buff, "{}: {}\r\n", action, error);
+    let _ = serial.write(buff.as_bytes());
 }
 
 #[link_section = ".bi_entries"]
@@ -0,0 +1,52 @@
+//! Wall clock for naming and stamping dump files
+//!
+//! The board has no battery backed clock, so the time starts at
+//! 2000-01-01 00:00:00 on every power up until it is set over USB.
+
+use core::cell::Cell;
+
+use embedded_sdmmc::{TimeSource, Timestamp};
+use hal::timer::CopyableTimer0;
+use rfid_dump::date::{DateTime, Y2K};
+use rp235x_hal as hal;
+
+pub struct WallClock {
+    timer: hal::Timer<CopyableTimer0>,
+    /// Seconds since 1970 at timer tick 0
+    epoch: Cell<u64>,
+}
+
+impl WallClock {
+    pub fn new(timer: hal::Timer<CopyableTimer0>) -> Self {
+        Self {
+            timer,
+            epoch: Cell::new(Y2K),
+        }
+    }
+
+    pub fn now(&self) -> DateTime {
+        DateTime::from_unix(self.epoch.get() + self.uptime())
+    }
+
+    pub fn set(&self, now: DateTime) {
+        self.epoch.set(now.to_unix() - self.uptime());
+    }
+
+    fn uptime(&self) -> u64 {
+        self.timer.get_counter().ticks() / 1_000_000
+    }
+}
+
+impl TimeSource for &WallClock {
+    fn get_timestamp(&self) -> Timestamp {
+        let now = self.now();
+        Timestamp {
+            year_since_1970: (now.year - 1970) as u8,
+            zero_indexed_month: now.month - 1,
+            zero_indexed_day: now.day - 1,
+            hours: now.hour,
+            minutes: now.minute,
+            seconds: now.second,
+        }
+    }
+}
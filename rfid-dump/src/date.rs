@@ -0,0 +1,175 @@
+//! Calendar dates for the wall clock.
+//!
+//! Kept apart from the timer that drives the clock, so the calendar maths
+//! builds and is tested on the host.
+
+/// 2000-01-01 00:00:00 in seconds since 1970
+pub const Y2K: u64 = 946_684_800;
+
+/// Days from 0000-03-01 to 1970-01-01
+const UNIX_EPOCH_DAYS: u64 = 719_468;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// Parses `YYYY-MM-DD HH:MM:SS`, from 2000 on.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (date, time) = text.trim().split_once(' ')?;
+        let mut date = date.split('-').map(|part| part.parse::<u16>().ok());
+        let mut time = time.trim().split(':').map(|part| part.parse::<u8>().ok());
+        let parsed = Self {
+            year: date.next()??,
+            month: u8::try_from(date.next()??).ok()?,
+            day: u8::try_from(date.next()??).ok()?,
+            hour: time.next()??,
+            minute: time.next()??,
+            second: time.next()??,
+        };
+        if date.next().is_some() || time.next().is_some() {
+            return None;
+        }
+
+        let valid = (2000..2100).contains(&parsed.year)
+            && (1..=12).contains(&parsed.month)
+            && parsed.day >= 1
+            && parsed.hour < 24
+            && parsed.minute < 60
+            && parsed.second < 60;
+        // Days past the end of the month roll over into the next one
+        if !valid || Self::from_unix(parsed.to_unix()) != parsed {
+            return None;
+        }
+        Some(parsed)
+    }
+
+    /// Date and time from seconds since 1970, after Howard Hinnant's
+    /// `civil_from_days`
+    pub fn from_unix(seconds: u64) -> Self {
+        let days = seconds / 86_400 + UNIX_EPOCH_DAYS;
+        let era = days / 146_097;
+        let day_of_era = days % 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        // Months counted from March, so the leap day comes last
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = era * 400 + year_of_era + (month <= 2) as u64;
+
+        let time = seconds % 86_400;
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+        }
+    }
+
+    /// Seconds since 1970, after Howard Hinnant's `days_from_civil`
+    pub fn to_unix(self) -> u64 {
+        let month = self.month as u64;
+        let year = self.year as u64 - (month <= 2) as u64;
+        let era = year / 400;
+        let year_of_era = year % 400;
+        let month_from_march = if month > 2 { month - 3 } else { month + 9 };
+        let day_of_year = (153 * month_from_march + 2) / 5 + self.day as u64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - UNIX_EPOCH_DAYS;
+
+        days * 86_400 + self.hour as u64 * 3600 + self.minute as u64 * 60 + self.second as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> DateTime {
+        DateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        }
+    }
+
+    #[test]
+    fn known_instants() {
+        for (seconds, expected) in [
+            (0, date(1970, 1, 1, 0, 0, 0)),
+            (Y2K, date(2000, 1, 1, 0, 0, 0)),
+            (951_782_400, date(2000, 2, 29, 0, 0, 0)),
+            (1_234_567_890, date(2009, 2, 13, 23, 31, 30)),
+            (4_107_542_399, date(2100, 2, 28, 23, 59, 59)),
+        ] {
+            assert_eq!(DateTime::from_unix(seconds), expected, "{seconds}");
+            assert_eq!(expected.to_unix(), seconds);
+        }
+    }
+
+    #[test]
+    fn round_trips_every_day_of_the_century() {
+        let mut seconds = Y2K + 12 * 3600 + 34 * 60 + 56;
+        let mut last = DateTime::from_unix(seconds - 86_400);
+        while seconds < Y2K + 100 * 366 * 86_400 {
+            let now = DateTime::from_unix(seconds);
+            assert_eq!(now.to_unix(), seconds);
+            assert!(
+                now.day == last.day + 1 || now.day == 1,
+                "{now:?} after {last:?}"
+            );
+            last = now;
+            seconds += 86_400;
+        }
+    }
+
+    #[test]
+    fn parses_dates_from_2000_on() {
+        assert_eq!(
+            DateTime::parse("2026-10-18 13:05:09"),
+            Some(date(2026, 10, 18, 13, 5, 9))
+        );
+        assert_eq!(
+            DateTime::parse(" 2024-02-29  23:59:59\r\n"),
+            Some(date(2024, 2, 29, 23, 59, 59))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_dates() {
+        for text in [
+            "",
+            "2026-10-18",
+            "2026-10-18 13:05",
+            "2026-10-18 13:05:09:00",
+            "2026-10-18-01 13:05:09",
+            "1999-12-31 23:59:59",
+            "2100-01-01 00:00:00",
+            "2026-13-01 00:00:00",
+            "2026-00-10 00:00:00",
+            "2026-10-00 00:00:00",
+            "2026-04-31 00:00:00",
+            "2025-02-29 00:00:00",
+            "2026-10-18 24:00:00",
+            "2026-10-18 12:60:00",
+            "2026-10-18 12:00:60",
+            "2026-1O-18 12:00:00",
+            "2026-300-18 12:00:00",
+        ] {
+            assert_eq!(DateTime::parse(text), None, "{text:?}");
+        }
+    }
+}
@@ -0,0 +1,5 @@
+//! The parts of the RFID dumper that don't need the hardware
+
+#![no_std]
+
+pub mod date;
@@ -2,7 +2,7 @@
 #![no_main]
 
 use hal::block::ImageDef;
-use heapless::String;
+use heapless::{String, Vec};
 use panic_halt as _;
 use rp235x_hal::{self as hal, Clock};
 
@@ -12,17 +12,36 @@ use usbd_serial::SerialPort;
 use core::fmt::Write;
 
 use embedded_hal_bus::spi::ExclusiveDevice;
+use embedded_sdmmc::{BlockDevice, Mode, SdCard, TimeSource, VolumeIdx, VolumeManager};
 
 use mfrc522::{comm::blocking::spi::SpiInterface, Mfrc522};
 
+use mifare_classic::{
+    parse_key_line, request, select_card, Block, Dump, Error, FoundKey, Hex, InvalidKey, Key,
+    KeyMap, KeyType, Sector, Session, KNOWN_KEYS,
+};
+
 use hal::fugit::RateExtU32;
 
+mod clock;
+mod save;
+
+use clock::WallClock;
+use rfid_dump::date::DateTime;
+use save::save_dump;
+
 #[link_section = ".start_block"]
 #[used]
 pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
 
 const XTAL_FREQ_HZ: u32 = 12_000_000u32;
 
+/// Room for the known keys plus the ones loaded from SD card or USB
+const MAX_KEYS: usize = 64;
+
+/// Extra keys on the SD card, one per line as 12 hex digits
+const KEY_FILE: &str = "KEYS.DIC";
+
 #[hal::entry]
 fn main() -> ! {
     // Boiler Plate
@@ -88,88 +107,296 @@ fn main() -> ! {
     let itf = SpiInterface::new(spi);
     let mut rfid = Mfrc522::new(itf).init().unwrap();
 
+    // SD card Setup, on SPI1 since the reader has SPI0
+    let sd_cs = pins.gpio13.into_push_pull_output();
+    let sd_sck = pins.gpio10.into_function::<hal::gpio::FunctionSpi>();
+    let sd_mosi = pins.gpio11.into_function::<hal::gpio::FunctionSpi>();
+    let sd_miso = pins.gpio12.into_function::<hal::gpio::FunctionSpi>();
+    let sd_bus = hal::spi::Spi::<_, _, _, 8>::new(pac.SPI1, (sd_mosi, sd_miso, sd_sck));
+
+    let sd_spi = sd_bus.init(
+        &mut pac.RESETS,
+        clocks.peripheral_clock.freq(),
+        400.kHz(), // card initialization happens at low baud rate
+        embedded_hal::spi::MODE_0,
+    );
+
+    let sd_spi = ExclusiveDevice::new(sd_spi, sd_cs, timer).unwrap();
+    let sdcard = SdCard::new(sd_spi, timer);
+    let clock = WallClock::new(timer);
+    let mut volume_mgr = VolumeManager::new(sdcard, &clock);
+
+    let mut dictionary: Vec<Key, MAX_KEYS> = Vec::new();
+    dictionary.extend_from_slice(&KNOWN_KEYS).unwrap();
+    let mut keys_loaded = false;
+    let mut line: String<32> = String::new();
+
     loop {
         let _ = usb_dev.poll(&mut [&mut serial]);
-        if let Ok(atqa) = rfid.reqa() {
-            if let Ok(uid) = rfid.select(&atqa) {
-                if let Err(e) = dump_memory(&uid, &mut rfid, &mut serial) {
-                    serial.write(e.as_bytes()).unwrap();
+
+        // Wait for the host to open the port so it sees the result
+        if !keys_loaded && timer.get_counter().ticks() >= 2_000_000 {
+            keys_loaded = true;
+            let mut buff: String<64> = String::new();
+            match load_keys(&mut volume_mgr, &mut dictionary) {
+                Ok(added) => write!(buff, "Loaded {} keys from {}\r\n", added, KEY_FILE),
+                Err(e) => write!(buff, "No keys loaded from SD card: {:?}\r\n", e),
+            }
+            .unwrap();
+            let _ = serial.write(buff.as_bytes());
+        }
+
+        read_commands(&mut serial, &mut line, &mut dictionary, &clock);
+
+        if let Ok(atqa) = request(&mut rfid) {
+            if let Ok(card) = select_card(&mut rfid) {
+                let mut session = match Session::new(&mut rfid, card) {
+                    Ok(session) => session,
+                    Err(e) => {
+                        report_error("Not a MIFARE Classic card", e, &mut serial);
+                        let _ = rfid.hlta();
+                        continue;
+                    }
+                };
+                let mut dump = Dump::new(session.uid(), session.layout());
+                dump.set_atqa(atqa);
+                dump.set_sak(session.card().sak());
+                if let Err(e) = dump_memory(&mut session, &mut dictionary, &mut dump, &mut serial) {
+                    report_error("Dump failed", e, &mut serial);
+                }
+                print_key_map(dump.keys(), &mut serial);
+                // The card may already have been taken away
+                let _ = session.finish();
+
+                // Even a partial dump is worth keeping
+                if dump.read_count() > 0 {
+                    let mut buff: String<64> = String::new();
+                    match save_dump(&mut volume_mgr, &dump, clock.now()) {
+                        Ok(path) => write!(buff, "Saved {} and .JSN\r\n", path),
+                        Err(e) => write!(buff, "Saving failed: {:?}\r\n", e),
+                    }
+                    .unwrap();
+                    let _ = serial.write(buff.as_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// Adds the keys in [`KEY_FILE`] to the dictionary, returning how many
+/// were new.
+fn load_keys<D: BlockDevice, T: TimeSource>(
+    volume_mgr: &mut VolumeManager<D, T>,
+    dictionary: &mut Vec<Key, MAX_KEYS>,
+) -> Result<usize, embedded_sdmmc::Error<D::Error>>
+where
+    D::Error: core::fmt::Debug,
+{
+    let mut volume = volume_mgr.open_volume(VolumeIdx(0))?;
+    let mut root_dir = volume.open_root_dir()?;
+    let mut file = root_dir.open_file_in_dir(KEY_FILE, Mode::ReadOnly)?;
+
+    let mut added = 0;
+    let mut line: String<64> = String::new();
+    while !file.is_eof() {
+        let mut chunk = [0u8; 32];
+        let num_read = file.read(&mut chunk)?;
+        for &byte in &chunk[..num_read] {
+            if byte == b'\n' {
+                if let Ok(Some(key)) = parse_key_line(&line) {
+                    added += add_key(dictionary, key) as usize;
                 }
-                rfid.hlta().unwrap();
-                rfid.stop_crypto1().unwrap();
+                line.clear();
+            } else {
+                // Lines too long for the buffer are cut short
+                let _ = line.push(byte as char);
             }
         }
     }
+    if let Ok(Some(key)) = parse_key_line(&line) {
+        added += add_key(dictionary, key) as usize;
+    }
+    Ok(added)
+}
+
+/// Handles lines typed into the serial port: a key to add to the
+/// dictionary, or `time YYYY-MM-DD HH:MM:SS` to set the clock.
+fn read_commands<B: UsbBus>(
+    serial: &mut SerialPort<B>,
+    line: &mut String<32>,
+    dictionary: &mut Vec<Key, MAX_KEYS>,
+    clock: &WallClock,
+) {
+    let mut chunk = [0u8; 16];
+    let Ok(num_read) = serial.read(&mut chunk) else {
+        return;
+    };
+    for &byte in &chunk[..num_read] {
+        if byte != b'\r' && byte != b'\n' {
+            let _ = line.push(byte as char);
+            continue;
+        }
+        if let Some(time) = line.strip_prefix("time ") {
+            let reply = match DateTime::parse(time) {
+                Some(now) => {
+                    clock.set(now);
+                    "Clock set\r\n"
+                }
+                None => "Expected time YYYY-MM-DD HH:MM:SS\r\n",
+            };
+            let _ = serial.write(reply.as_bytes());
+            line.clear();
+            continue;
+        }
+        let reply = match parse_key_line(line) {
+            Ok(Some(key)) if add_key(dictionary, key) => "Key added\r\n",
+            Ok(Some(_)) => "Key already known, or no room for more\r\n",
+            Ok(None) => "",
+            Err(InvalidKey) => "Not a key, expected 12 hex digits\r\n",
+        };
+        let _ = serial.write(reply.as_bytes());
+        line.clear();
+    }
+}
+
+/// Adds `key` unless the dictionary already has it or is full.
+fn add_key(dictionary: &mut Vec<Key, MAX_KEYS>, key: Key) -> bool {
+    !dictionary.contains(&key) && dictionary.push(key).is_ok()
 }
 
-fn dump_memory<E, COMM: mfrc522::comm::Interface<Error = E>, B: UsbBus>(
-    uid: &mfrc522::Uid,
-    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+/// Dumps every sector some key in the dictionary opens into `dump`, along
+/// with the keys that opened them.
+fn dump_memory<COMM: mfrc522::comm::Interface, B: UsbBus>(
+    session: &mut Session<COMM>,
+    dictionary: &mut Vec<Key, MAX_KEYS>,
+    dump: &mut Dump,
     serial: &mut SerialPort<B>,
-) -> Result<(), &'static str> {
+) -> Result<(), Error<COMM::Error>> {
     let mut buff: String<64> = String::new();
-    for sector in 0..16 {
+    for sector in session.layout().sectors() {
         // Printing the Sector number
-        write!(buff, "\r\n-----------SECTOR {}-----------\r\n", sector).unwrap();
-        serial.write(buff.as_bytes()).unwrap();
+        write!(
+            buff,
+            "\r\n-----------SECTOR {}-----------\r\n",
+            sector.index()
+        )
+        .unwrap();
+        let _ = serial.write(buff.as_bytes());
         buff.clear();
-        read_sector(uid, sector, rfid, serial)?;
+
+        let Some(found) = session.find_key(sector, dictionary)? else {
+            let _ = serial.write("No key opens this sector, skipping\r\n".as_bytes());
+            continue;
+        };
+        dump.keys_mut().set(sector, Some(found));
+        // Cards often share one key between sectors, so try it first next
+        if let Some(position) = dictionary.iter().position(|&key| key == found.key) {
+            dictionary[..=position].rotate_right(1);
+        }
+        read_sector(session, sector, found, dump, serial)?;
     }
     Ok(())
 }
 
-fn read_sector<E, COMM: mfrc522::comm::Interface<Error = E>, B: UsbBus>(
-    uid: &mfrc522::Uid,
-    sector: u8,
-    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+fn read_sector<COMM: mfrc522::comm::Interface, B: UsbBus>(
+    session: &mut Session<COMM>,
+    sector: Sector,
+    found: FoundKey,
+    dump: &mut Dump,
     serial: &mut SerialPort<B>,
-) -> Result<(), &'static str> {
-    const AUTH_KEY: [u8; 6] = [0xFF; 6];
-
-    let mut buff: String<64> = String::new();
+) -> Result<(), Error<COMM::Error>> {
+    let mut buff: String<96> = String::new();
 
-    let block_offset = sector * 4;
-    rfid.mf_authenticate(uid, block_offset, &AUTH_KEY)
-        .map_err(|_| "Auth failed")?;
-
-    for abs_block in block_offset..block_offset + 4 {
-        let rel_block = abs_block - block_offset;
-        let data = rfid.mf_read(abs_block).map_err(|_| "Read failed")?;
-
-        // Prining the Block absolute and relative numbers
-        write!(buff, "\r\nBLOCK {} (REL: {}) | ", abs_block, rel_block).unwrap();
-        serial.write(buff.as_bytes()).unwrap();
-        buff.clear();
-
-        // Printing the block data
-        print_hex_to_serial(&data, serial);
-
-        // Printing block type
-        let block_type = get_block_type(sector, rel_block);
-        write!(buff, "| {} ", block_type).unwrap();
-        serial.write(buff.as_bytes()).unwrap();
+    for block in sector.blocks() {
+        match session.read(block) {
+            Ok(mut data) => {
+                // The card never gives out the key it was opened with, but
+                // we know it
+                if block.is_trailer() {
+                    match found.key_type {
+                        KeyType::A => data[..6].copy_from_slice(&found.key),
+                        KeyType::B => data[10..].copy_from_slice(&found.key),
+                    }
+                }
+                dump.set_block(block, &data);
+                // Printing the Block absolute and relative numbers, the
+                // block data and the block type
+                write!(
+                    buff,
+                    "\r\nBLOCK {} (REL: {}) | {} | {} ",
+                    block.index(),
+                    block.offset(),
+                    Hex(&data),
+                    get_block_type(block)
+                )
+                .unwrap();
+            }
+            // The access bits may keep this key from reading some blocks
+            Err(e) if e.needs_reselect() => {
+                write!(
+                    buff,
+                    "\r\nBLOCK {} (REL: {}) | {} ",
+                    block.index(),
+                    block.offset(),
+                    e
+                )
+                .unwrap();
+            }
+            Err(e) => return Err(e),
+        }
+        let _ = serial.write(buff.as_bytes());
         buff.clear();
     }
-    serial
-        .write("\r\n".as_bytes())
-        .map_err(|_| "Write failed")?;
+    let _ = serial.write("\r\n".as_bytes());
     Ok(())
 }
 
-fn get_block_type(sector: u8, rel_block: u8) -> &'static str {
-    match rel_block {
-        0 if sector == 0 => "MFD",
-        3 => "TRAILER",
-        _ => "DATA",
+fn get_block_type(block: Block) -> &'static str {
+    if block.is_manufacturer() {
+        "MFD"
+    } else if block.is_trailer() {
+        "TRAILER"
+    } else {
+        "DATA"
     }
 }
 
-fn print_hex_to_serial<B: UsbBus>(data: &[u8], serial: &mut SerialPort<B>) {
+fn print_key_map<B: UsbBus>(key_map: &KeyMap, serial: &mut SerialPort<B>) {
     let mut buff: String<64> = String::new();
-    for &d in data.iter() {
-        write!(buff, "{:02x} ", d).unwrap();
+    let _ = serial.write("\r\n-----------KEY MAP-----------\r\n".as_bytes());
+    for (sector, found) in key_map.iter() {
+        match found {
+            Some(found) => write!(
+                buff,
+                "SECTOR {:2} | KEY {:?} | {}\r\n",
+                sector.index(),
+                found.key_type,
+                Hex(&found.key)
+            ),
+            None => write!(buff, "SECTOR {:2} | no key found\r\n", sector.index()),
+        }
+        .unwrap();
+        let _ = serial.write(buff.as_bytes());
+        buff.clear();
     }
-    serial.write(buff.as_bytes()).unwrap();
+    write!(
+        buff,
+        "{} of {} sectors opened\r\n",
+        key_map.found(),
+        key_map.layout().sector_count()
+    )
+    .unwrap();
+    let _ = serial.write(buff.as_bytes());
+}
+
+fn report_error<E: core::fmt::Debug, B: UsbBus>(
+    action: &str,
+    error: Error<E>,
+    serial: &mut SerialPort<B>,
+) {
+    let mut buff: String<64> = String::new();
+    let _ = write!(buff, "{}: {}\r\n", action, error);
+    let _ = serial.write(buff.as_bytes());
 }
 
 #[link_section = ".bi_entries"]
@@ -177,7 +404,7 @@ fn print_hex_to_serial<B: UsbBus>(data: &[u8], serial: &mut SerialPort<B>) {
 pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
     hal::binary_info::rp_cargo_bin_name!(),
     hal::binary_info::rp_cargo_version!(),
-    hal::binary_info::rp_program_description!(c"PWM Blinky Example"),
+    hal::binary_info::rp_program_description!(c"RFID Dump Example"),
     hal::binary_info::rp_cargo_homepage_url!(),
     hal::binary_info::rp_program_build_attribute!(),
 ];
@@ -0,0 +1,131 @@
+//! Saving dumps to the SD card
+//!
+//! Every card gets a directory named after its UID, and every dump two
+//! files in it named after the month, day, hour and minute it was taken:
+//! `DEADBEEF/10181305.MFD` with the raw card contents and
+//! `DEADBEEF/10181305.JSN` describing the card. The year is in the files'
+//! timestamps. The SD card only takes 8.3 names, so a UID longer than 4
+//! bytes is cut short in the directory name. The JSON file has all of it.
+
+use core::fmt::{self, Write};
+
+use embedded_sdmmc::{BlockDevice, Mode, TimeSource, VolumeIdx, VolumeManager};
+use heapless::{String, Vec};
+use mifare_classic::Dump;
+use rfid_dump::date::DateTime;
+
+/// Most dumps kept of one card
+const MAX_DUMPS: u16 = 9999;
+
+/// Writes `dump` to the SD card, returning the path of the `.MFD` file.
+///
+/// Earlier dumps of the card are never overwritten: when the name for
+/// `now` is taken, by another dump in the same minute or because the
+/// clock was never set, the dump gets the first `DUMPnnnn` name not yet
+/// taken in the card's directory instead.
+pub fn save_dump<D: BlockDevice, T: TimeSource>(
+    volume_mgr: &mut VolumeManager<D, T>,
+    dump: &Dump,
+    now: DateTime,
+) -> Result<String<24>, embedded_sdmmc::Error<D::Error>>
+where
+    D::Error: core::fmt::Debug,
+{
+    let mut volume = volume_mgr.open_volume(VolumeIdx(0))?;
+    let mut root_dir = volume.open_root_dir()?;
+
+    let mut dir_name: String<8> = String::new();
+    for byte in dump.uid().iter().take(4) {
+        write!(dir_name, "{:02X}", byte).unwrap();
+    }
+    match root_dir.make_dir_in_dir(dir_name.as_str()) {
+        Ok(()) | Err(embedded_sdmmc::Error::DirAlreadyExists) => {}
+        Err(e) => return Err(e),
+    }
+    let mut dir = root_dir.open_dir(dir_name.as_str())?;
+
+    let mut name: String<12> = String::new();
+    write!(
+        name,
+        "{:02}{:02}{:02}{:02}.MFD",
+        now.month, now.day, now.hour, now.minute
+    )
+    .unwrap();
+    let mut number = 0;
+    let mut file = loop {
+        match dir.open_file_in_dir(name.as_str(), Mode::ReadWriteCreate) {
+            Err(embedded_sdmmc::Error::FileAlreadyExists) if number < MAX_DUMPS => {
+                number += 1;
+                name.clear();
+                write!(name, "DUMP{:04}.MFD", number).unwrap();
+            }
+            result => break result?,
+        }
+    };
+    file.write(dump.as_bytes())?;
+    file.close()?;
+
+    let mut path: String<24> = String::new();
+    write!(path, "{}/{}", dir_name, name).unwrap();
+
+    name.truncate(name.len() - 3);
+    name.push_str("JSN").unwrap();
+    let mut file = dir.open_file_in_dir(name.as_str(), Mode::ReadWriteCreate)?;
+    let mut writer = BlockWriter::new(|bytes: &[u8]| file.write(bytes));
+    let result = write!(writer, "{}", dump.json());
+    writer.finish(result)?;
+    file.close()?;
+
+    Ok(path)
+}
+
+/// Lets `write!` send text to a file a block at a time, rather than in
+/// the many small pieces formatting produces
+struct BlockWriter<F, E> {
+    sink: F,
+    buffer: Vec<u8, 512>,
+    error: Option<E>,
+}
+
+impl<F: FnMut(&[u8]) -> Result<(), E>, E> BlockWriter<F, E> {
+    fn new(sink: F) -> Self {
+        Self {
+            sink,
+            buffer: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn flush(&mut self) -> fmt::Result {
+        if let Err(e) = (self.sink)(&self.buffer) {
+            self.error = Some(e);
+            return Err(fmt::Error);
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Writes out what is left, giving back the error that stopped
+    /// formatting, if any.
+    fn finish(mut self, result: fmt::Result) -> Result<(), E> {
+        if result.is_ok() {
+            let _ = self.flush();
+        }
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<F: FnMut(&[u8]) -> Result<(), E>, E> Write for BlockWriter<F, E> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            if self.buffer.is_full() {
+                self.flush()?;
+            }
+            let _ = self.buffer.push(byte);
+        }
+        Ok(())
+    }
+}
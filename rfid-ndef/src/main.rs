@@ -0,0 +1,244 @@
+//! # RFID NDEF Example
+//!
+//! Turns MIFARE Classic cards into NFC tags that phones read. Type a
+//! command into the serial port and hold a card to the reader to carry it
+//! out:
+//!
+//! - `format` prepares a blank card for NDEF
+//! - `url https://www.rust-lang.org` stores a link that phones open
+//! - `text Hello, Ferris!` stores a line of English text
+//!
+//! A command only applies to the next card. A card held to the reader
+//! without a command has its NDEF records printed.
+//!
+//! | Part    | Pins                                            |
+//! |---------|-------------------------------------------------|
+//! | MFRC522 | SCK GPIO 6, MOSI GPIO 7, MISO GPIO 4, CS GPIO 5 |
+//!
+//! Formatting sets the published NFC keys, after which the card no longer
+//! opens with the default key A.
+
+#![no_std]
+#![no_main]
+
+use hal::block::ImageDef;
+use heapless::String;
+use panic_halt as _;
+use rp235x_hal::{self as hal, Clock};
+
+use usb_device::{class_prelude::*, prelude::*};
+use usbd_serial::SerialPort;
+
+use core::fmt::Write;
+
+use embedded_hal_bus::spi::ExclusiveDevice;
+
+use mfrc522::{comm::blocking::spi::SpiInterface, Mfrc522};
+
+use mifare_classic::{encode_ndef, parse_ndef, select_card, Error, Record, Session};
+
+use hal::fugit::RateExtU32;
+
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
+
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Longest command, and with it the longest link or text
+const MAX_LINE: usize = 128;
+
+/// What to do with the next card
+enum Command {
+    Format,
+    /// Write the NDEF message encoded in the message buffer
+    Write,
+}
+
+#[hal::entry]
+fn main() -> ! {
+    // Boiler Plate
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+    let sio = hal::Sio::new(pac.SIO);
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    let timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    // For USB Serial
+    let usb_bus = UsbBusAllocator::new(hal::usb::UsbBus::new(
+        pac.USB,
+        pac.USB_DPRAM,
+        clocks.usb_clock,
+        true,
+        &mut pac.RESETS,
+    ));
+
+    // Set up the USB Communications Class Device driver
+    let mut serial = SerialPort::new(&usb_bus);
+
+    // Create a USB device with a fake VID and PID
+    let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
+        .strings(&[StringDescriptors::default()
+            .manufacturer("Fake company")
+            .product("Serial port")
+            .serial_number("TEST")])
+        .unwrap()
+        .device_class(2) // from: https://www.usb.org/defined-class-codes
+        .build();
+
+    // RFID Setup
+    let spi_mosi = pins.gpio7.into_function::<hal::gpio::FunctionSpi>();
+    let spi_miso = pins.gpio4.into_function::<hal::gpio::FunctionSpi>();
+    let spi_sclk = pins.gpio6.into_function::<hal::gpio::FunctionSpi>();
+    let spi_bus = hal::spi::Spi::<_, _, _, 8>::new(pac.SPI0, (spi_mosi, spi_miso, spi_sclk));
+
+    let spi_cs = pins.gpio5.into_push_pull_output();
+    let spi = spi_bus.init(
+        &mut pac.RESETS,
+        clocks.peripheral_clock.freq(),
+        1_000.kHz(),
+        embedded_hal::spi::MODE_0,
+    );
+
+    let spi = ExclusiveDevice::new(spi, spi_cs, timer).unwrap();
+    let itf = SpiInterface::new(spi);
+    let mut rfid = Mfrc522::new(itf).init().unwrap();
+
+    let mut command: Option<Command> = None;
+    let mut line: String<MAX_LINE> = String::new();
+    // Encoded message for the next card
+    let mut message = [0u8; MAX_LINE + 16];
+    let mut message_len = 0;
+    // The whole data area of a 1K card. Longer messages on 4K cards are
+    // reported as too big.
+    let mut area = [0u8; 720];
+
+    loop {
+        let _ = usb_dev.poll(&mut [&mut serial]);
+
+        if read_line(&mut serial, &mut line) {
+            let record = if let Some(uri) = line.strip_prefix("url ") {
+                Some(Record::uri(uri.trim()))
+            } else {
+                line.strip_prefix("text ")
+                    .map(|text| Record::text("en", text.trim()))
+            };
+            command = match record {
+                Some(record) => match encode_ndef(&[record], &mut message) {
+                    Ok(len) => {
+                        message_len = len;
+                        Some(Command::Write)
+                    }
+                    Err(_) => None,
+                },
+                None if line.trim() == "format" => Some(Command::Format),
+                None => None,
+            };
+            let reply = match command {
+                Some(_) => "Hold a card to the reader\r\n",
+                None => "Expected format, url LINK or text TEXT\r\n",
+            };
+            let _ = serial.write(reply.as_bytes());
+            line.clear();
+        }
+
+        if rfid.reqa().is_ok() {
+            if let Ok(card) = select_card(&mut rfid) {
+                let mut session = match Session::new(&mut rfid, card) {
+                    Ok(session) => session,
+                    Err(e) => {
+                        report_error("Not a MIFARE Classic card", e, &mut serial);
+                        let _ = rfid.hlta();
+                        continue;
+                    }
+                };
+                let result = match command.take() {
+                    Some(Command::Format) => session.format_ndef().map(|()| {
+                        let _ = serial.write("Formatted for NDEF\r\n".as_bytes());
+                    }),
+                    Some(Command::Write) => session.write_ndef(&message[..message_len]).map(|()| {
+                        let _ = serial.write("Written\r\n".as_bytes());
+                    }),
+                    None => session
+                        .read_ndef(&mut area)
+                        .map(|message| print_records(message, &mut serial)),
+                };
+                if let Err(e) = result {
+                    report_error("Card error", e, &mut serial);
+                }
+                // The card may already have been taken away
+                let _ = session.finish();
+            }
+        }
+    }
+}
+
+fn print_records<B: UsbBus>(message: &[u8], serial: &mut SerialPort<B>) {
+    if message.is_empty() {
+        let _ = serial.write("Empty NDEF message\r\n".as_bytes());
+    }
+    for record in parse_ndef(message) {
+        let mut buff: String<{ MAX_LINE + 32 }> = String::new();
+        let _ = match record {
+            Ok(record) => write!(buff, "{}\r\n", record),
+            Err(e) => write!(buff, "Bad record: {}\r\n", e),
+        };
+        let _ = serial.write(buff.as_bytes());
+    }
+}
+
+/// Collects what is typed into the serial port, returning true once
+/// `line` holds a whole line.
+fn read_line<B: UsbBus>(serial: &mut SerialPort<B>, line: &mut String<MAX_LINE>) -> bool {
+    let mut byte = [0u8];
+    while let Ok(1) = serial.read(&mut byte) {
+        match byte[0] {
+            b'\r' | b'\n' if !line.is_empty() => return true,
+            b'\r' | b'\n' => {}
+            // Lines too long for the buffer are cut short
+            byte => {
+                let _ = line.push(byte as char);
+            }
+        }
+    }
+    false
+}
+
+fn report_error<E: core::fmt::Debug, B: UsbBus>(
+    action: &str,
+    error: Error<E>,
+    serial: &mut SerialPort<B>,
+) {
+    let mut buff: String<64> = String::new();
+    let _ = write!(buff, "{}: {}\r\n", action, error);
+    let _ = serial.write(buff.as_bytes());
+}
+
+/// Program metadata for `picotool info`
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"RFID NDEF Example"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
@@ -15,6 +15,8 @@ use embedded_hal_bus::spi::ExclusiveDevice;
 
 use mfrc522::{comm::blocking::spi::SpiInterface, Mfrc522};
 
+use mifare_classic::{select_card, Error, Hex, Sector, Session};
+
 use hal::fugit::RateExtU32;
 
 #[link_section = ".start_block"]
@@ -90,46 +92,50 @@ fn main() -> ! {
 
     loop {
         let _ = usb_dev.poll(&mut [&mut serial]);
-        if let Ok(atqa) = rfid.reqa() {
-            if let Ok(uid) = rfid.select(&atqa) {
-                if let Err(e) = read_sector(&uid, 0, &mut rfid, &mut serial) {
-                    serial.write(e.as_bytes()).unwrap();
+        if rfid.reqa().is_ok() {
+            if let Ok(card) = select_card(&mut rfid) {
+                let mut session = match Session::new(&mut rfid, card) {
+                    Ok(session) => session,
+                    Err(e) => {
+                        report_error("Not a MIFARE Classic card", e, &mut serial);
+                        let _ = rfid.hlta();
+                        continue;
+                    }
+                };
+                let sector = Sector::new(0, session.layout()).unwrap();
+                if let Err(e) = read_sector(&mut session, sector, &mut serial) {
+                    report_error("Read failed", e, &mut serial);
                 }
-                rfid.hlta().unwrap();
-                rfid.stop_crypto1().unwrap();
+                // The card may already have been taken away
+                let _ = session.finish();
             }
         }
     }
 }
 
-fn read_sector<E, COMM: mfrc522::comm::Interface<Error = E>, B: UsbBus>(
-    uid: &mfrc522::Uid,
-    sector: u8,
-    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+fn read_sector<COMM: mfrc522::comm::Interface, B: UsbBus>(
+    session: &mut Session<COMM>,
+    sector: Sector,
     serial: &mut SerialPort<B>,
-) -> Result<(), &'static str> {
-    const AUTH_KEY: [u8; 6] = [0xFF; 6];
-
-    let block_offset = sector * 4;
-    rfid.mf_authenticate(uid, block_offset, &AUTH_KEY)
-        .map_err(|_| "Auth failed")?;
-
-    for abs_block in block_offset..block_offset + 4 {
-        let data = rfid.mf_read(abs_block).map_err(|_| "Read failed")?;
-        print_hex_to_serial(&data, serial);
-        serial
-            .write("\r\n".as_bytes())
-            .map_err(|_| "Write failed")?;
+) -> Result<(), Error<COMM::Error>> {
+    let mut buff: String<64> = String::new();
+    for block in sector.blocks() {
+        let data = session.read(block)?;
+        write!(buff, "{}\r\n", Hex(&data)).unwrap();
+        let _ = serial.write(buff.as_bytes());
+        buff.clear();
     }
     Ok(())
 }
 
-fn print_hex_to_serial<B: UsbBus>(data: &[u8], serial: &mut SerialPort<B>) {
+fn report_error<E: core::fmt::Debug, B: UsbBus>(
+    action: &str,
+    error: Error<E>,
+    serial: &mut SerialPort<B>,
+) {
     let mut buff: String<64> = String::new();
-    for &d in data.iter() {
-        write!(buff, "{:02x} ", d).unwrap();
-    }
-    serial.write(buff.as_bytes()).unwrap();
+    let _ = write!(buff, "{}: {}\r\n", action, error);
+    let _ = serial.write(buff.as_bytes());
 }
 
 #[link_section = ".bi_entries"]
@@ -0,0 +1,569 @@
+//! # RFID Restore Example
+//!
+//! Writes a dump saved by `rfid-dump`, or a raw `.mfd` dump from another
+//! tool, back to a MIFARE Classic card. Type `load DEADBEEF/10181305.MFD`
+//! (or a `.JSN` file) into the serial port, then hold a blank card to the
+//! reader. Every block is read back after writing and any difference is
+//! shown byte by byte.
+//!
+//! | Part    | Pins                                               |
+//! |---------|----------------------------------------------------|
+//! | MFRC522 | SCK GPIO 6, MOSI GPIO 7, MISO GPIO 4, CS GPIO 5    |
+//! | SD card | SCK GPIO 10, MOSI GPIO 11, MISO GPIO 12, CS GPIO 13 |
+//!
+//! Only data blocks are written unless [`WRITE_TRAILERS`] is set, so the
+//! card keeps the default key. Block 0, which holds the UID, can only be
+//! written on magic cards set in [`MAGIC`].
+
+#![no_std]
+#![no_main]
+
+use hal::block::ImageDef;
+use heapless::{String, Vec};
+use panic_halt as _;
+use rp235x_hal::{self as hal, Clock};
+
+use usb_device::{class_prelude::*, prelude::*};
+use usbd_serial::SerialPort;
+
+use core::fmt::Write;
+
+use embedded_hal_bus::spi::ExclusiveDevice;
+use embedded_sdmmc::{BlockDevice, Mode, SdCard, TimeSource, Timestamp, VolumeIdx, VolumeManager};
+
+use mfrc522::{comm::blocking::spi::SpiInterface, Mfrc522};
+
+use mifare_classic::{
+    select_card, Block, Dump, DumpError, Error, Hex, Key, KeyType, Magic, Sector, Session, Trailer,
+    BLOCK_SIZE, DEFAULT_KEY,
+};
+
+use hal::fugit::RateExtU32;
+
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
+
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Also write the keys and access bits of the dump. A card restored with
+/// them only opens with the dump's keys afterwards.
+const WRITE_TRAILERS: bool = false;
+
+/// Kind of magic card to write block 0 of, and with it the UID. Genuine
+/// cards refuse the write.
+const MAGIC: Option<Magic> = None;
+
+/// Room for the JSON dump of a 4K card
+const MAX_FILE: usize = 20 * 1024;
+
+/// A dummy timesource, which is mostly important for creating files.
+#[derive(Default)]
+pub struct DummyTimesource();
+
+impl TimeSource for DummyTimesource {
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp {
+            year_since_1970: 0,
+            zero_indexed_month: 0,
+            zero_indexed_day: 0,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+}
+
+/// Why a dump couldn't be loaded
+enum LoadError<E: core::fmt::Debug> {
+    Sd(embedded_sdmmc::Error<E>),
+    TooBig,
+    Dump(DumpError),
+}
+
+impl<E: core::fmt::Debug> core::fmt::Display for LoadError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LoadError::Sd(error) => write!(f, "SD card error {:?}", error),
+            LoadError::TooBig => f.write_str("file too big"),
+            LoadError::Dump(error) => write!(f, "not a dump ({:?})", error),
+        }
+    }
+}
+
+impl<E: core::fmt::Debug> From<embedded_sdmmc::Error<E>> for LoadError<E> {
+    fn from(error: embedded_sdmmc::Error<E>) -> Self {
+        LoadError::Sd(error)
+    }
+}
+
+/// How the blocks of a restore went
+#[derive(Default)]
+struct Tally {
+    matching: usize,
+    differing: usize,
+    failed: usize,
+    skipped: usize,
+}
+
+#[hal::entry]
+fn main() -> ! {
+    // Boiler Plate
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+    let sio = hal::Sio::new(pac.SIO);
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    let timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    // For USB Serial
+    let usb_bus = UsbBusAllocator::new(hal::usb::UsbBus::new(
+        pac.USB,
+        pac.USB_DPRAM,
+        clocks.usb_clock,
+        true,
+        &mut pac.RESETS,
+    ));
+
+    // Set up the USB Communications Class Device driver
+    let mut serial = SerialPort::new(&usb_bus);
+
+    // Create a USB device with a fake VID and PID
+    let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
+        .strings(&[StringDescriptors::default()
+            .manufacturer("Fake company")
+            .product("Serial port")
+            .serial_number("TEST")])
+        .unwrap()
+        .device_class(2) // from: https://www.usb.org/defined-class-codes
+        .build();
+
+    // RFID Setup
+    let spi_mosi = pins.gpio7.into_function::<hal::gpio::FunctionSpi>();
+    let spi_miso = pins.gpio4.into_function::<hal::gpio::FunctionSpi>();
+    let spi_sclk = pins.gpio6.into_function::<hal::gpio::FunctionSpi>();
+    let spi_bus = hal::spi::Spi::<_, _, _, 8>::new(pac.SPI0, (spi_mosi, spi_miso, spi_sclk));
+
+    let spi_cs = pins.gpio5.into_push_pull_output();
+    let spi = spi_bus.init(
+        &mut pac.RESETS,
+        clocks.peripheral_clock.freq(),
+        1_000.kHz(),
+        embedded_hal::spi::MODE_0,
+    );
+
+    let spi = ExclusiveDevice::new(spi, spi_cs, timer).unwrap();
+    let itf = SpiInterface::new(spi);
+    let mut rfid = Mfrc522::new(itf).init().unwrap();
+
+    // SD card Setup, on SPI1 since the reader has SPI0
+    let sd_cs = pins.gpio13.into_push_pull_output();
+    let sd_sck = pins.gpio10.into_function::<hal::gpio::FunctionSpi>();
+    let sd_mosi = pins.gpio11.into_function::<hal::gpio::FunctionSpi>();
+    let sd_miso = pins.gpio12.into_function::<hal::gpio::FunctionSpi>();
+    let sd_bus = hal::spi::Spi::<_, _, _, 8>::new(pac.SPI1, (sd_mosi, sd_miso, sd_sck));
+
+    let sd_spi = sd_bus.init(
+        &mut pac.RESETS,
+        clocks.peripheral_clock.freq(),
+        400.kHz(), // card initialization happens at low baud rate
+        embedded_hal::spi::MODE_0,
+    );
+
+    let sd_spi = ExclusiveDevice::new(sd_spi, sd_cs, timer).unwrap();
+    let sdcard = SdCard::new(sd_spi, timer);
+    let mut volume_mgr = VolumeManager::new(sdcard, DummyTimesource::default());
+
+    let mut file_buffer = [0u8; MAX_FILE];
+    let mut dump: Option<Dump> = None;
+    let mut line: String<32> = String::new();
+
+    loop {
+        let _ = usb_dev.poll(&mut [&mut serial]);
+
+        if read_line(&mut serial, &mut line) {
+            let mut buff: String<128> = String::new();
+            match line.strip_prefix("load ") {
+                Some(path) => match load_dump(&mut volume_mgr, path.trim(), &mut file_buffer) {
+                    Ok(loaded) => {
+                        write!(
+                            buff,
+                            "Loaded {:?} dump of {}, {} blocks. Hold a card to the reader\r\n",
+                            loaded.layout(),
+                            Hex(loaded.uid()),
+                            loaded.read_count()
+                        )
+                        .unwrap();
+                        dump = Some(loaded);
+                    }
+                    Err(e) => write!(buff, "Loading failed: {}\r\n", e).unwrap(),
+                },
+                None => buff.push_str("Expected load DIR/FILE.MFD\r\n").unwrap(),
+            }
+            let _ = serial.write(buff.as_bytes());
+            line.clear();
+        }
+
+        let Some(dump) = &dump else {
+            continue;
+        };
+        if rfid.reqa().is_ok() {
+            if let Ok(card) = select_card(&mut rfid) {
+                let mut session = match Session::new(&mut rfid, card) {
+                    Ok(session) if session.layout() == dump.layout() => session,
+                    Ok(session) => {
+                        let mut buff: String<64> = String::new();
+                        write!(
+                            buff,
+                            "The dump is of a {:?} card, this is a {:?}\r\n",
+                            dump.layout(),
+                            session.layout()
+                        )
+                        .unwrap();
+                        let _ = serial.write(buff.as_bytes());
+                        let _ = session.finish();
+                        continue;
+                    }
+                    Err(e) => {
+                        report_error("Not a MIFARE Classic card", e, &mut serial);
+                        let _ = rfid.hlta();
+                        continue;
+                    }
+                };
+                let mut tally = Tally::default();
+                if let Err(e) = restore(&mut session, dump, &mut tally, &mut serial) {
+                    report_error("Restore failed", e, &mut serial);
+                }
+                print_tally(&tally, &mut serial);
+                // The card may already have been taken away
+                let _ = session.finish();
+            }
+        }
+    }
+}
+
+/// Collects what is typed into the serial port, returning true once
+/// `line` holds a whole line.
+fn read_line<B: UsbBus>(serial: &mut SerialPort<B>, line: &mut String<32>) -> bool {
+    let mut byte = [0u8];
+    while let Ok(1) = serial.read(&mut byte) {
+        match byte[0] {
+            b'\r' | b'\n' if !line.is_empty() => return true,
+            b'\r' | b'\n' => {}
+            // Lines too long for the buffer are cut short
+            byte => {
+                let _ = line.push(byte as char);
+            }
+        }
+    }
+    false
+}
+
+/// Reads a dump from `path`, a file in the root directory or in one
+/// directory below it. Files ending in `.JSN` or `.JSON` are read as JSON,
+/// all others as raw dumps.
+fn load_dump<D: BlockDevice, T: TimeSource>(
+    volume_mgr: &mut VolumeManager<D, T>,
+    path: &str,
+    buffer: &mut [u8],
+) -> Result<Dump, LoadError<D::Error>>
+where
+    D::Error: core::fmt::Debug,
+{
+    let mut volume = volume_mgr.open_volume(VolumeIdx(0))?;
+    let mut root_dir = volume.open_root_dir()?;
+    let (dir_name, file_name) = path.rsplit_once('/').unwrap_or(("", path));
+    let mut dir = if dir_name.is_empty() {
+        root_dir
+    } else {
+        root_dir.open_dir(dir_name)?
+    };
+    let mut file = dir.open_file_in_dir(file_name, Mode::ReadOnly)?;
+
+    let mut len = 0;
+    while !file.is_eof() {
+        if len == buffer.len() {
+            return Err(LoadError::TooBig);
+        }
+        len += file.read(&mut buffer[len..])?;
+    }
+    let bytes = &buffer[..len];
+
+    let extension = file_name
+        .rsplit_once('.')
+        .map_or("", |(_, extension)| extension);
+    let dump = if extension.eq_ignore_ascii_case("JSN") || extension.eq_ignore_ascii_case("JSON") {
+        let text = core::str::from_utf8(bytes).map_err(|_| LoadError::Dump(DumpError::Json))?;
+        Dump::from_json(text)
+    } else {
+        Dump::from_mfd(bytes)
+    };
+    dump.map_err(LoadError::Dump)
+}
+
+/// Errors after which the card can't be talked to anymore. The others
+/// only concern one block.
+fn is_fatal<E>(error: &Error<E>) -> bool {
+    matches!(
+        error,
+        Error::Timeout | Error::Transport(_) | Error::WrongCard
+    )
+}
+
+/// Keys the card may have for `sector`: the default one if it is blank,
+/// or the dump's key A if it was restored with trailers before.
+fn card_keys(dump: &Dump, sector: Sector) -> Vec<Key, 2> {
+    let mut keys = Vec::new();
+    keys.push(DEFAULT_KEY).unwrap();
+    if let Some(found) = dump.keys().get(sector) {
+        if found.key_type == KeyType::A && found.key != DEFAULT_KEY {
+            keys.push(found.key).unwrap();
+        }
+    }
+    keys
+}
+
+fn restore<COMM: mfrc522::comm::Interface, B: UsbBus>(
+    session: &mut Session<COMM>,
+    dump: &Dump,
+    tally: &mut Tally,
+    serial: &mut SerialPort<B>,
+) -> Result<(), Error<COMM::Error>> {
+    let mut buff: String<64> = String::new();
+    for sector in dump.layout().sectors() {
+        write!(
+            buff,
+            "\r\n-----------SECTOR {}-----------\r\n",
+            sector.index()
+        )
+        .unwrap();
+        let _ = serial.write(buff.as_bytes());
+        buff.clear();
+
+        if session
+            .find_key(sector, &card_keys(dump, sector))?
+            .is_none()
+        {
+            let _ = serial.write("No key opens this sector on the card, skipping\r\n".as_bytes());
+            tally.skipped += sector.block_count() as usize;
+            continue;
+        }
+        for block in sector.data_blocks() {
+            match dump.block(block) {
+                Some(data) => write_block(session, block, data, tally, serial)?,
+                None => {
+                    print_result(block, "not in the dump, skipped", serial);
+                    tally.skipped += 1;
+                }
+            }
+        }
+        if WRITE_TRAILERS {
+            write_trailer(session, sector, dump, tally, serial)?;
+        } else {
+            tally.skipped += 1;
+        }
+    }
+
+    // Last, as the card has another UID afterwards
+    let block = Block::new(0, dump.layout()).unwrap();
+    match (MAGIC, dump.block(block)) {
+        (Some(magic), Some(data)) => {
+            if magic == Magic::Gen2 {
+                session.find_key(block.sector(), &card_keys(dump, block.sector()))?;
+            }
+            match session.write_manufacturer_block(*data, magic) {
+                Ok(()) => {
+                    // The session can't select the card by its new UID
+                    print_result(block, "written, hold the card again to check", serial);
+                    tally.matching += 1;
+                }
+                Err(e) if !is_fatal(&e) => {
+                    print_error(block, e, serial);
+                    tally.failed += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        _ => tally.skipped += 1,
+    }
+    Ok(())
+}
+
+/// Writes one data block and reads it back.
+fn write_block<COMM: mfrc522::comm::Interface, B: UsbBus>(
+    session: &mut Session<COMM>,
+    block: Block,
+    data: &[u8; BLOCK_SIZE],
+    tally: &mut Tally,
+    serial: &mut SerialPort<B>,
+) -> Result<(), Error<COMM::Error>> {
+    let written = session
+        .write(block, *data)
+        .and_then(|()| session.read(block));
+    match written {
+        Ok(actual) => compare(block, data, &actual, tally, serial),
+        Err(e) if !is_fatal(&e) => {
+            print_error(block, e, serial);
+            tally.failed += 1;
+        }
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
+
+/// Writes the keys and access bits of the dump, after checking them, and
+/// reads them back with the new key A.
+fn write_trailer<COMM: mfrc522::comm::Interface, B: UsbBus>(
+    session: &mut Session<COMM>,
+    sector: Sector,
+    dump: &Dump,
+    tally: &mut Tally,
+    serial: &mut SerialPort<B>,
+) -> Result<(), Error<COMM::Error>> {
+    let block = sector.trailer();
+    let Some(data) = dump.block(block) else {
+        print_result(block, "not in the dump, skipped", serial);
+        tally.skipped += 1;
+        return Ok(());
+    };
+    // Dumps only have key A where some key A opened the sector
+    let Some(key_a) = dump
+        .keys()
+        .get(sector)
+        .filter(|found| found.key_type == KeyType::A)
+        .map(|found| found.key)
+    else {
+        print_result(block, "key A unknown, skipped", serial);
+        tally.skipped += 1;
+        return Ok(());
+    };
+    let Ok(mut trailer) = Trailer::from_block(data) else {
+        print_error(block, Error::<COMM::Error>::InvalidAccessBits, serial);
+        tally.failed += 1;
+        return Ok(());
+    };
+    trailer.key_a = key_a;
+
+    let written = session.write_trailer(sector, &trailer).and_then(|()| {
+        session.set_key(KeyType::A, key_a);
+        session.read(block)
+    });
+    match written {
+        Ok(actual) => {
+            // Key A reads back as zeros, and so does key B unless the
+            // access bits make it readable
+            let mut expected = trailer.to_block();
+            expected[..6].fill(0);
+            if !trailer.access.key_b_readable() {
+                expected[10..].fill(0);
+            }
+            compare(block, &expected, &actual, tally, serial);
+        }
+        Err(e) if !is_fatal(&e) => {
+            print_error(block, e, serial);
+            tally.failed += 1;
+        }
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
+
+/// Counts the block as matching or not, showing the bytes that differ.
+fn compare<B: UsbBus>(
+    block: Block,
+    expected: &[u8; BLOCK_SIZE],
+    actual: &[u8; BLOCK_SIZE],
+    tally: &mut Tally,
+    serial: &mut SerialPort<B>,
+) {
+    if expected == actual {
+        print_result(block, "ok", serial);
+        tally.matching += 1;
+        return;
+    }
+    tally.differing += 1;
+
+    // Marks line up with the bytes printed by `Hex`
+    let mut marks: String<48> = String::new();
+    for (expected, actual) in expected.iter().zip(actual) {
+        let mark = if expected == actual { "   " } else { "^^ " };
+        marks.push_str(mark).unwrap();
+    }
+    let mut buff: String<96> = String::new();
+    write!(buff, "BLOCK {} | DIFFERS\r\n", block.index()).unwrap();
+    let _ = serial.write(buff.as_bytes());
+    buff.clear();
+    write!(buff, "  dump: {}\r\n", Hex(expected)).unwrap();
+    let _ = serial.write(buff.as_bytes());
+    buff.clear();
+    write!(buff, "  card: {}\r\n", Hex(actual)).unwrap();
+    let _ = serial.write(buff.as_bytes());
+    buff.clear();
+    write!(buff, "        {}\r\n", marks.trim_end()).unwrap();
+    let _ = serial.write(buff.as_bytes());
+}
+
+fn print_result<B: UsbBus>(block: Block, result: &str, serial: &mut SerialPort<B>) {
+    let mut buff: String<64> = String::new();
+    write!(buff, "BLOCK {} | {}\r\n", block.index(), result).unwrap();
+    let _ = serial.write(buff.as_bytes());
+}
+
+fn print_error<E: core::fmt::Debug, B: UsbBus>(
+    block: Block,
+    error: Error<E>,
+    serial: &mut SerialPort<B>,
+) {
+    let mut buff: String<64> = String::new();
+    let _ = write!(buff, "BLOCK {} | {}\r\n", block.index(), error);
+    let _ = serial.write(buff.as_bytes());
+}
+
+fn print_tally<B: UsbBus>(tally: &Tally, serial: &mut SerialPort<B>) {
+    let mut buff: String<96> = String::new();
+    write!(
+        buff,
+        "\r\n{} blocks match, {} differ, {} failed, {} skipped\r\n",
+        tally.matching, tally.differing, tally.failed, tally.skipped
+    )
+    .unwrap();
+    let _ = serial.write(buff.as_bytes());
+}
+
+fn report_error<E: core::fmt::Debug, B: UsbBus>(
+    action: &str,
+    error: Error<E>,
+    serial: &mut SerialPort<B>,
+) {
+    let mut buff: String<64> = String::new();
+    let _ = write!(buff, "{}: {}\r\n", action, error);
+    let _ = serial.write(buff.as_bytes());
+}
+
+/// Program metadata for `picotool info`
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"RFID Restore Example"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
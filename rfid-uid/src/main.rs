@@ -14,7 +14,9 @@ use core::fmt::Write;
 
 use embedded_hal_bus::spi::ExclusiveDevice;
 
-use mfrc522::{comm::blocking::spi::SpiInterface, Mfrc522};
+use mfrc522::{comm::blocking::spi::SpiInterface, Initialized, Mfrc522};
+
+use mifare_classic::{parse_ndef, select_card, Error, Hex, Selected, Ultralight};
 
 use hal::fugit::RateExtU32;
 
@@ -91,22 +93,83 @@ fn main() -> ! {
 
     loop {
         let _ = usb_dev.poll(&mut [&mut serial]);
-        if let Ok(atqa) = rfid.reqa() {
-            if let Ok(uid) = rfid.select(&atqa) {
-                serial.write("\r\nUID: \r\n".as_bytes()).unwrap();
-                print_hex_to_serial(uid.as_bytes(), &mut serial);
+        if rfid.reqa().is_ok() {
+            // Goes through all cascade levels, so 7 byte UIDs come out whole
+            if let Ok(card) = select_card(&mut rfid) {
+                let mut buff: String<64> = String::new();
+                write!(
+                    buff,
+                    "\r\nUID: \r\n{}\r\nSAK: {:02x}\r\n",
+                    Hex(card.uid()),
+                    card.sak()
+                )
+                .unwrap();
+                let _ = serial.write(buff.as_bytes());
+
+                if card.is_ultralight() {
+                    if let Err(e) = print_tag(&mut rfid, card, &mut serial) {
+                        report_error("Reading the tag failed", e, &mut serial);
+                    }
+                }
+                // So the card only shows up again once it comes back
+                let _ = rfid.hlta();
                 timer.delay_ms(500);
             }
         }
     }
 }
 
-fn print_hex_to_serial<B: UsbBus>(data: &[u8], serial: &mut SerialPort<B>) {
-    let mut buff: String<64> = String::new();
-    for &d in data.iter() {
-        write!(buff, "{:02x} ", d).unwrap();
+/// Prints the type, read counter and NDEF records of an Ultralight or
+/// NTAG tag.
+fn print_tag<COMM: mfrc522::comm::Interface, B: UsbBus>(
+    rfid: &mut Mfrc522<COMM, Initialized>,
+    card: Selected,
+    serial: &mut SerialPort<B>,
+) -> Result<(), Error<COMM::Error>> {
+    let mut tag = Ultralight::new(rfid, card)?;
+    let tag_type = tag.tag_type();
+    let mut buff: String<160> = String::new();
+    write!(
+        buff,
+        "Type: {:?}, {} pages\r\n",
+        tag_type,
+        tag_type.page_count()
+    )
+    .unwrap();
+    let _ = serial.write(buff.as_bytes());
+    buff.clear();
+
+    if tag_type.config_page().is_some() {
+        match tag.read_counter() {
+            Ok(count) => write!(buff, "Read {} times\r\n", count).unwrap(),
+            Err(_) => buff.push_str("Read counter off\r\n").unwrap(),
+        }
+        let _ = serial.write(buff.as_bytes());
+        buff.clear();
     }
-    serial.write(buff.as_bytes()).unwrap();
+
+    // The data area of the biggest tag, an NTAG216
+    let mut area = [0u8; 888];
+    let message = tag.read_ndef(&mut area)?;
+    for record in parse_ndef(message) {
+        let _ = match record {
+            Ok(record) => write!(buff, "{}\r\n", record),
+            Err(e) => write!(buff, "Bad record: {}\r\n", e),
+        };
+        let _ = serial.write(buff.as_bytes());
+        buff.clear();
+    }
+    Ok(())
+}
+
+fn report_error<E: core::fmt::Debug, B: UsbBus>(
+    action: &str,
+    error: Error<E>,
+    serial: &mut SerialPort<B>,
+) {
+    let mut buff: String<64> = String::new();
+    let _ = write!(buff, "{}: {}\r\n", action, error);
+    let _ = serial.write(buff.as_bytes());
 }
 
 #[link_section = ".bi_entries"]
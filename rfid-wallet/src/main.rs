@@ -0,0 +1,392 @@
+//! # RFID Wallet Example
+//!
+//! Keeps a balance on a MIFARE Classic card as a value block, which the
+//! card adds to and subtracts from itself. Type a command into the serial
+//! port and hold a card to the reader to carry it out:
+//!
+//! - `format 100` makes the card a wallet holding 100
+//! - `topup 20` adds 20 to the balance
+//! - `pay 15` takes 15 off, unless the balance is too low
+//!
+//! A command only applies to the next card. A card held to the reader
+//! without a command just shows its balance. The green LED and a high beep
+//! mean done, the red LED and a low beep declined or failed.
+//!
+//! | Part      | Pins                                            |
+//! |-----------|-------------------------------------------------|
+//! | MFRC522   | SCK GPIO 6, MOSI GPIO 7, MISO GPIO 4, CS GPIO 5 |
+//! | Green LED | GPIO 16                                         |
+//! | Red LED   | GPIO 17                                         |
+//! | Buzzer    | GPIO 15                                         |
+//!
+//! The balance is kept twice, in [`WALLET_BLOCK`] and [`BACKUP_BLOCK`], so
+//! a card pulled away halfway through an update is repaired from the other
+//! copy on the next tap. The card keeps the default key and access bits,
+//! which let anyone with key A top up. A real wallet would set access bits
+//! that only allow key A to pay.
+
+#![no_std]
+#![no_main]
+
+use embedded_hal::{delay::DelayNs, digital::OutputPin, pwm::SetDutyCycle};
+use hal::block::ImageDef;
+use heapless::String;
+use panic_halt as _;
+use rp235x_hal::{self as hal, Clock};
+
+use usb_device::{class_prelude::*, prelude::*};
+use usbd_serial::SerialPort;
+
+use core::fmt::Write;
+
+use embedded_hal_bus::spi::ExclusiveDevice;
+
+use mfrc522::{comm::blocking::spi::SpiInterface, Mfrc522};
+
+use mifare_classic::{select_card, Block, Error, Hex, Layout, Session};
+
+use hal::fugit::RateExtU32;
+
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
+
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Value block holding the balance
+const WALLET_BLOCK: u8 = 4;
+
+/// Copy of the balance, in the same sector so the card can copy between
+/// the two
+const BACKUP_BLOCK: u8 = 5;
+
+const fn get_top(freq: f64, div_int: u8) -> u16 {
+    let result = 150_000_000. / (freq * div_int as f64);
+    result as u16 - 1
+}
+
+const PWM_DIV_INT: u8 = 64;
+
+/// Beep for a command carried out
+const DONE_TOP: u16 = get_top(2000., PWM_DIV_INT);
+
+/// Beep for a command declined or failed
+const FAILED_TOP: u16 = get_top(300., PWM_DIV_INT);
+
+/// What to do with the next card
+#[derive(Clone, Copy)]
+enum Command {
+    TopUp(i32),
+    Pay(i32),
+    Format(i32),
+}
+
+impl Command {
+    /// Parses `topup N`, `pay N` or `format N`. Top ups and payments have
+    /// to be above 0.
+    fn parse(line: &str) -> Option<Self> {
+        let (name, amount) = line.trim().split_once(' ')?;
+        let amount: i32 = amount.trim().parse().ok()?;
+        match name {
+            "topup" if amount > 0 => Some(Command::TopUp(amount)),
+            "pay" if amount > 0 => Some(Command::Pay(amount)),
+            "format" => Some(Command::Format(amount)),
+            _ => None,
+        }
+    }
+}
+
+/// How a command went on the card
+enum Outcome {
+    /// Carried out, leaving this balance
+    Done(i32),
+    /// Not carried out, as the balance is too low or would overflow
+    Declined(i32),
+}
+
+/// The two value blocks of the balance
+struct Wallet {
+    main: Block,
+    backup: Block,
+}
+
+impl Wallet {
+    fn new(layout: Layout) -> Self {
+        Self {
+            main: Block::new(WALLET_BLOCK, layout).unwrap(),
+            backup: Block::new(BACKUP_BLOCK, layout).unwrap(),
+        }
+    }
+
+    /// Reads the balance, first repairing the copy an interrupted update
+    /// left behind.
+    fn balance<COMM: mfrc522::comm::Interface>(
+        &self,
+        session: &mut Session<COMM>,
+    ) -> Result<i32, Error<COMM::Error>> {
+        let main = session.read_value(self.main);
+        let backup = session.read_value(self.backup);
+        match (main, backup) {
+            (Ok(main), Ok(backup)) if main.value == backup.value => Ok(main.value),
+            // The main block is always updated first, so it is the newer
+            // one if both decode
+            (Ok(main), _) => {
+                Self::copy(session, self.main, self.backup)?;
+                Ok(main.value)
+            }
+            (Err(Error::InvalidValueBlock), Ok(backup)) => {
+                Self::copy(session, self.backup, self.main)?;
+                Ok(backup.value)
+            }
+            (Err(e), _) => Err(e),
+        }
+    }
+
+    fn top_up<COMM: mfrc522::comm::Interface>(
+        &self,
+        session: &mut Session<COMM>,
+        amount: i32,
+    ) -> Result<Outcome, Error<COMM::Error>> {
+        let balance = self.balance(session)?;
+        if balance.checked_add(amount).is_none() {
+            return Ok(Outcome::Declined(balance));
+        }
+        session.increment(self.main, amount as u32)?;
+        self.commit(session)
+    }
+
+    fn pay<COMM: mfrc522::comm::Interface>(
+        &self,
+        session: &mut Session<COMM>,
+        amount: i32,
+    ) -> Result<Outcome, Error<COMM::Error>> {
+        let balance = self.balance(session)?;
+        if balance < amount {
+            return Ok(Outcome::Declined(balance));
+        }
+        session.decrement(self.main, amount as u32)?;
+        self.commit(session)
+    }
+
+    fn format<COMM: mfrc522::comm::Interface>(
+        &self,
+        session: &mut Session<COMM>,
+        balance: i32,
+    ) -> Result<Outcome, Error<COMM::Error>> {
+        session.write_value(self.main, balance)?;
+        session.write_value(self.backup, balance)?;
+        Ok(Outcome::Done(balance))
+    }
+
+    /// Stores the result of an increment or decrement in the main block,
+    /// then in the backup.
+    fn commit<COMM: mfrc522::comm::Interface>(
+        &self,
+        session: &mut Session<COMM>,
+    ) -> Result<Outcome, Error<COMM::Error>> {
+        session.transfer(self.main)?;
+        Self::copy(session, self.main, self.backup)?;
+        let balance = session.read_value(self.main)?;
+        Ok(Outcome::Done(balance.value))
+    }
+
+    fn copy<COMM: mfrc522::comm::Interface>(
+        session: &mut Session<COMM>,
+        from: Block,
+        to: Block,
+    ) -> Result<(), Error<COMM::Error>> {
+        session.restore(from)?;
+        session.transfer(to)
+    }
+}
+
+#[hal::entry]
+fn main() -> ! {
+    // Boiler Plate
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+    let sio = hal::Sio::new(pac.SIO);
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    let mut timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    // For USB Serial
+    let usb_bus = UsbBusAllocator::new(hal::usb::UsbBus::new(
+        pac.USB,
+        pac.USB_DPRAM,
+        clocks.usb_clock,
+        true,
+        &mut pac.RESETS,
+    ));
+
+    // Set up the USB Communications Class Device driver
+    let mut serial = SerialPort::new(&usb_bus);
+
+    // Create a USB device with a fake VID and PID
+    let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
+        .strings(&[StringDescriptors::default()
+            .manufacturer("Fake company")
+            .product("Serial port")
+            .serial_number("TEST")])
+        .unwrap()
+        .device_class(2) // from: https://www.usb.org/defined-class-codes
+        .build();
+
+    // RFID Setup
+    let spi_mosi = pins.gpio7.into_function::<hal::gpio::FunctionSpi>();
+    let spi_miso = pins.gpio4.into_function::<hal::gpio::FunctionSpi>();
+    let spi_sclk = pins.gpio6.into_function::<hal::gpio::FunctionSpi>();
+    let spi_bus = hal::spi::Spi::<_, _, _, 8>::new(pac.SPI0, (spi_mosi, spi_miso, spi_sclk));
+
+    let spi_cs = pins.gpio5.into_push_pull_output();
+    let spi = spi_bus.init(
+        &mut pac.RESETS,
+        clocks.peripheral_clock.freq(),
+        1_000.kHz(),
+        embedded_hal::spi::MODE_0,
+    );
+
+    let spi = ExclusiveDevice::new(spi, spi_cs, timer).unwrap();
+    let itf = SpiInterface::new(spi);
+    let mut rfid = Mfrc522::new(itf).init().unwrap();
+
+    // LEDs and buzzer Setup
+    let mut green = pins.gpio16.into_push_pull_output();
+    let mut red = pins.gpio17.into_push_pull_output();
+
+    let mut pwm_slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
+    let buzzer = &mut pwm_slices.pwm7;
+    buzzer.enable();
+    buzzer.set_div_int(PWM_DIV_INT);
+    buzzer.channel_b.output_to(pins.gpio15);
+
+    let mut command: Option<Command> = None;
+    let mut line: String<32> = String::new();
+
+    loop {
+        let _ = usb_dev.poll(&mut [&mut serial]);
+
+        if read_line(&mut serial, &mut line) {
+            command = Command::parse(&line);
+            let reply = match command {
+                Some(_) => "Hold a card to the reader\r\n",
+                None => "Expected topup N, pay N or format N\r\n",
+            };
+            let _ = serial.write(reply.as_bytes());
+            line.clear();
+        }
+
+        if rfid.reqa().is_ok() {
+            if let Ok(card) = select_card(&mut rfid) {
+                let mut session = match Session::new(&mut rfid, card) {
+                    Ok(session) => session,
+                    Err(_) => {
+                        let _ = serial.write("Not a MIFARE Classic card\r\n".as_bytes());
+                        let _ = rfid.hlta();
+                        signal(&mut red, FAILED_TOP, buzzer, &mut timer);
+                        continue;
+                    }
+                };
+                let wallet = Wallet::new(session.layout());
+                let result = match command.take() {
+                    Some(Command::TopUp(amount)) => wallet.top_up(&mut session, amount),
+                    Some(Command::Pay(amount)) => wallet.pay(&mut session, amount),
+                    Some(Command::Format(balance)) => wallet.format(&mut session, balance),
+                    None => wallet.balance(&mut session).map(Outcome::Done),
+                };
+
+                let mut buff: String<64> = String::new();
+                let done = match result {
+                    Ok(Outcome::Done(balance)) => {
+                        write!(buff, "Card {}: balance {}\r\n", Hex(session.uid()), balance)
+                            .unwrap();
+                        true
+                    }
+                    Ok(Outcome::Declined(balance)) => {
+                        write!(buff, "Declined, balance {}\r\n", balance).unwrap();
+                        false
+                    }
+                    Err(Error::InvalidValueBlock) => {
+                        buff.push_str("Not a wallet, format the card first\r\n")
+                            .unwrap();
+                        false
+                    }
+                    Err(e) => {
+                        let _ = write!(buff, "Card error: {}\r\n", e);
+                        false
+                    }
+                };
+                let _ = serial.write(buff.as_bytes());
+                // The card may already have been taken away
+                let _ = session.finish();
+
+                if done {
+                    signal(&mut green, DONE_TOP, buzzer, &mut timer);
+                } else {
+                    signal(&mut red, FAILED_TOP, buzzer, &mut timer);
+                }
+            }
+        }
+    }
+}
+
+/// Lights `led` and beeps at the pitch of `top`.
+fn signal<L: OutputPin, S: hal::pwm::SliceId>(
+    led: &mut L,
+    top: u16,
+    buzzer: &mut hal::pwm::Slice<S, hal::pwm::FreeRunning>,
+    timer: &mut impl DelayNs,
+) {
+    led.set_high().unwrap();
+    buzzer.set_top(top);
+    buzzer.channel_b.set_duty_cycle_percent(50).unwrap();
+    timer.delay_ms(150);
+    buzzer.channel_b.set_duty_cycle(0).unwrap();
+    timer.delay_ms(350);
+    led.set_low().unwrap();
+}
+
+/// Collects what is typed into the serial port, returning true once
+/// `line` holds a whole line.
+fn read_line<B: UsbBus>(serial: &mut SerialPort<B>, line: &mut String<32>) -> bool {
+    let mut byte = [0u8];
+    while let Ok(1) = serial.read(&mut byte) {
+        match byte[0] {
+            b'\r' | b'\n' if !line.is_empty() => return true,
+            b'\r' | b'\n' => {}
+            // Lines too long for the buffer are cut short
+            byte => {
+                let _ = line.push(byte as char);
+            }
+        }
+    }
+    false
+}
+
+/// Program metadata for `picotool info`
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"RFID Wallet Example"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
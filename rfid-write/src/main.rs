@@ -15,6 +15,8 @@ use embedded_hal_bus::spi::ExclusiveDevice;
 
 use mfrc522::{comm::blocking::spi::SpiInterface, Mfrc522};
 
+use mifare_classic::{select_card, Error, Hex, Sector, Session};
+
 use hal::fugit::RateExtU32;
 
 #[link_section = ".start_block"]
@@ -88,8 +90,6 @@ fn main() -> ! {
     let itf = SpiInterface::new(spi);
     let mut rfid = Mfrc522::new(itf).init().unwrap();
 
-    let target_sector = 4;
-    let rel_block = 2;
     const DATA: [u8; 16] = [
         b'i', b'm', b'p', b'l', b'R', b'u', b's', b't', // "implRust"
         0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Remaining bytes as 0x00
@@ -98,80 +98,62 @@ fn main() -> ! {
     loop {
         let _ = usb_dev.poll(&mut [&mut serial]);
 
-        if let Ok(atqa) = rfid.reqa() {
-            if let Ok(uid) = rfid.select(&atqa) {
-                serial
-                    .write("\r\n----Before Write----\r\n".as_bytes())
-                    .unwrap();
-                if let Err(e) = read_sector(&uid, target_sector, &mut rfid, &mut serial) {
-                    serial.write(e.as_bytes()).unwrap();
+        if rfid.reqa().is_ok() {
+            if let Ok(card) = select_card(&mut rfid) {
+                let mut session = match Session::new(&mut rfid, card) {
+                    Ok(session) => session,
+                    Err(e) => {
+                        report_error("Not a MIFARE Classic card", e, &mut serial);
+                        let _ = rfid.hlta();
+                        continue;
+                    }
+                };
+                let target_sector = Sector::new(4, session.layout()).unwrap();
+                let target_block = target_sector.block(2).unwrap();
+
+                let _ = serial.write("\r\n----Before Write----\r\n".as_bytes());
+                if let Err(e) = read_sector(&mut session, target_sector, &mut serial) {
+                    report_error("Read failed", e, &mut serial);
                 }
 
-                if let Err(e) = write_block(&uid, target_sector, rel_block, DATA, &mut rfid) {
-                    serial.write(e.as_bytes()).unwrap();
+                if let Err(e) = session.write(target_block, DATA) {
+                    report_error("Write failed", e, &mut serial);
                 }
 
-                serial
-                    .write("\r\n----After Write----\r\n".as_bytes())
-                    .unwrap();
-                if let Err(e) = read_sector(&uid, target_sector, &mut rfid, &mut serial) {
-                    serial.write(e.as_bytes()).unwrap();
+                let _ = serial.write("\r\n----After Write----\r\n".as_bytes());
+                if let Err(e) = read_sector(&mut session, target_sector, &mut serial) {
+                    report_error("Read failed", e, &mut serial);
                 }
-                rfid.hlta().unwrap();
-                rfid.stop_crypto1().unwrap();
+                // The card may already have been taken away
+                let _ = session.finish();
             }
         }
     }
 }
 
-fn write_block<E, COMM: mfrc522::comm::Interface<Error = E>>(
-    uid: &mfrc522::Uid,
-    sector: u8,
-    rel_block: u8,
-    data: [u8; 16],
-    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
-) -> Result<(), &'static str> {
-    const AUTH_KEY: [u8; 6] = [0xFF; 6];
-
-    let block_offset = sector * 4;
-    let abs_block = block_offset + rel_block;
-
-    rfid.mf_authenticate(uid, block_offset, &AUTH_KEY)
-        .map_err(|_| "Auth failed")?;
-
-    rfid.mf_write(abs_block, data).map_err(|_| "Write failed")?;
-
-    Ok(())
-}
-
-fn read_sector<E, COMM: mfrc522::comm::Interface<Error = E>, B: UsbBus>(
-    uid: &mfrc522::Uid,
-    sector: u8,
-    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+fn read_sector<COMM: mfrc522::comm::Interface, B: UsbBus>(
+    session: &mut Session<COMM>,
+    sector: Sector,
     serial: &mut SerialPort<B>,
-) -> Result<(), &'static str> {
-    const AUTH_KEY: [u8; 6] = [0xFF; 6];
-
-    let block_offset = sector * 4;
-    rfid.mf_authenticate(uid, block_offset, &AUTH_KEY)
-        .map_err(|_| "Auth failed")?;
-
-    for abs_block in block_offset..block_offset + 4 {
-        let data = rfid.mf_read(abs_block).map_err(|_| "Read failed")?;
-        print_hex_to_serial(&data, serial);
-        serial
-            .write("\r\n".as_bytes())
-            .map_err(|_| "Write failed")?;
+) -> Result<(), Error<COMM::Error>> {
+    let mut buff: String<64> = String::new();
+    for block in sector.blocks() {
+        let data = session.read(block)?;
+        write!(buff, "{}\r\n", Hex(&data)).unwrap();
+        let _ = serial.write(buff.as_bytes());
+        buff.clear();
     }
     Ok(())
 }
 
-fn print_hex_to_serial<B: UsbBus>(data: &[u8], serial: &mut SerialPort<B>) {
+fn report_error<E: core::fmt::Debug, B: UsbBus>(
+    action: &str,
+    error: Error<E>,
+    serial: &mut SerialPort<B>,
+) {
     let mut buff: String<64> = String::new();
-    for &d in data.iter() {
-        write!(buff, "{:02x} ", d).unwrap();
-    }
-    serial.write(buff.as_bytes()).unwrap();
+    let _ = write!(buff, "{}: {}\r\n", action, error);
+    let _ = serial.write(buff.as_bytes());
 }
 
 #[link_section = ".bi_entries"]
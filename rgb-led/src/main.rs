@@ -0,0 +1,135 @@
+//! # RGB LED Example
+//!
+//! Drives an RGB LED from three PWM channels: red on GPIO 16, green on GPIO 17
+//! and blue on GPIO 18. It cycles through the hue wheel, sweeps the colour
+//! temperature from candle light to daylight and then fades between a few
+//! fixed colours.
+//!
+//! Set `POLARITY` to match the LED you have. Each colour pin needs its own
+//! current limiting resistor.
+
+#![no_std]
+#![no_main]
+
+// Ensure we halt the program on panic (if we don't mention this crate it won't
+// be linked)
+use panic_halt as _;
+
+// Alias for our HAL crate
+use rp235x_hal as hal;
+
+// Some things we need
+use color::{Hsv, Rgb};
+use embedded_hal::delay::DelayNs;
+use rgb_led::{Polarity, RgbLed};
+
+mod rgb_led;
+
+/// Tell the Boot ROM about our application
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
+
+/// External high-speed crystal on the Raspberry Pi Pico 2 board is 12 MHz.
+/// Adjust if your board has a different frequency
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Change to `Polarity::CommonAnode` if the shared LED pin goes to 3V3
+const POLARITY: Polarity = Polarity::CommonCathode;
+
+/// The colours we fade between at the end of each cycle
+const PALETTE: [Rgb; 5] = [
+    Rgb::RED,
+    Rgb::new(255, 140, 0), // Orange
+    Rgb::GREEN,
+    Rgb::new(0, 180, 255), // Sky blue
+    Rgb::new(160, 0, 255), // Purple
+];
+
+#[hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // The single-cycle I/O block controls our GPIO pins
+    let sio = hal::Sio::new(pac.SIO);
+
+    // Set the pins up according to their function on this particular board
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let mut timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    // Init PWMs
+    let mut pwm_slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
+
+    // GPIO 16 and 17 belong to PWM0, GPIO 18 belongs to PWM1
+    let pwm0 = &mut pwm_slices.pwm0;
+    pwm0.enable();
+    let pwm1 = &mut pwm_slices.pwm1;
+    pwm1.enable();
+
+    let red = &mut pwm_slices.pwm0.channel_a;
+    red.output_to(pins.gpio16);
+    let green = &mut pwm_slices.pwm0.channel_b;
+    green.output_to(pins.gpio17);
+    let blue = &mut pwm_slices.pwm1.channel_a;
+    blue.output_to(pins.gpio18);
+
+    let mut led = RgbLed::new(red, green, blue, POLARITY);
+
+    loop {
+        // Walk around the colour wheel at full saturation
+        for hue in 0..360 {
+            led.set_hsv(Hsv::new(hue as f32, 1.0, 1.0));
+            timer.delay_ms(10);
+        }
+
+        // Warm to cool white
+        for kelvin in (1_500..=8_000).step_by(50) {
+            led.set_kelvin(kelvin);
+            timer.delay_ms(15);
+        }
+
+        for color in PALETTE {
+            led.fade_to(color, 800, &mut timer);
+            timer.delay_ms(400);
+        }
+
+        led.fade_to(Rgb::BLACK, 800, &mut timer);
+        timer.delay_ms(500);
+    }
+}
+
+/// Program metadata for `picotool info`
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"RGB LED Example"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
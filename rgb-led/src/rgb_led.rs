@@ -0,0 +1,122 @@
+use embedded_hal::delay::DelayNs;
+use embedded_hal::pwm::SetDutyCycle;
+
+use color::{gamma_correct, Hsv, Rgb};
+
+/// How the LED package is wired
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polarity {
+    /// Shared pin goes to GND, a channel lights up when its pin is high
+    CommonCathode,
+    /// Shared pin goes to 3V3, a channel lights up when its pin is low
+    CommonAnode,
+}
+
+/// Per-channel gamma exponents
+///
+/// The red, green and blue dies rarely have the same brightness curve, so
+/// each one can be tuned separately to get a neutral white.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gamma {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl Default for Gamma {
+    fn default() -> Self {
+        Self {
+            r: 2.2,
+            g: 2.2,
+            b: 2.2,
+        }
+    }
+}
+
+/// Time between two colour updates while fading, in milliseconds
+const FADE_STEP_MS: u32 = 10;
+
+/// An RGB LED driven by three PWM channels
+pub struct RgbLed<R, G, B> {
+    red: R,
+    green: G,
+    blue: B,
+    polarity: Polarity,
+    gamma: Gamma,
+    color: Rgb,
+}
+
+impl<R, G, B> RgbLed<R, G, B>
+where
+    R: SetDutyCycle,
+    G: SetDutyCycle,
+    B: SetDutyCycle,
+{
+    /// Creates the driver and switches the LED off.
+    pub fn new(red: R, green: G, blue: B, polarity: Polarity) -> Self {
+        let mut led = Self {
+            red,
+            green,
+            blue,
+            polarity,
+            gamma: Gamma::default(),
+            color: Rgb::BLACK,
+        };
+        led.set_rgb(Rgb::BLACK);
+        led
+    }
+
+    pub fn set_gamma(&mut self, gamma: Gamma) {
+        self.gamma = gamma;
+        self.set_rgb(self.color);
+    }
+
+    /// The colour the LED is currently showing
+    pub fn color(&self) -> Rgb {
+        self.color
+    }
+
+    pub fn set_rgb(&mut self, color: Rgb) {
+        self.color = color;
+        let polarity = self.polarity;
+        set_channel(&mut self.red, color.r, self.gamma.r, polarity);
+        set_channel(&mut self.green, color.g, self.gamma.g, polarity);
+        set_channel(&mut self.blue, color.b, self.gamma.b, polarity);
+    }
+
+    pub fn set_hsv(&mut self, color: Hsv) {
+        self.set_rgb(color.to_rgb());
+    }
+
+    /// Shows white light of the given colour temperature.
+    pub fn set_kelvin(&mut self, kelvin: u16) {
+        self.set_rgb(Rgb::from_kelvin(kelvin));
+    }
+
+    pub fn off(&mut self) {
+        self.set_rgb(Rgb::BLACK);
+    }
+
+    /// Blends from the current colour to `target` over `duration_ms`.
+    ///
+    /// This blocks until the fade is complete.
+    pub fn fade_to(&mut self, target: Rgb, duration_ms: u32, delay: &mut impl DelayNs) {
+        let start = self.color;
+        let steps = (duration_ms / FADE_STEP_MS).max(1);
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            self.set_rgb(start.lerp(target, t));
+            delay.delay_ms(FADE_STEP_MS);
+        }
+    }
+}
+
+fn set_channel<C: SetDutyCycle>(channel: &mut C, value: u8, gamma: f32, polarity: Polarity) {
+    let max_duty = channel.max_duty_cycle();
+    let duty = gamma_correct(value, gamma, max_duty);
+    let duty = match polarity {
+        Polarity::CommonCathode => duty,
+        Polarity::CommonAnode => max_duty - duty,
+    };
+    let _ = channel.set_duty_cycle(duty);
+}
@@ -0,0 +1,225 @@
+//! # Robot Arm Example
+//!
+//! Moves four servos (base, shoulder, elbow and gripper) through a list of
+//! poses. Every move is planned so all joints start and stop together, with
+//! limited speed and acceleration instead of jumping to the target.
+//!
+//! The servos are on GPIO 8 to 11, which are both channels of PWM slices 4
+//! and 5. A timer interrupt steps the motion profiles at the 50 Hz servo
+//! frame rate. Pulse widths saved by the `calibrate` example for channels 0
+//! to 3 are used for the joints in the order above, if there are any.
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use critical_section::Mutex;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::pwm::SetDutyCycle;
+use hal::fugit::ExtU32;
+use hal::pac::interrupt;
+use hal::timer::{Alarm, Alarm0, CopyableTimer0};
+use hal::Clock;
+use panic_halt as _;
+use rp235x_hal as hal;
+use servo::flash::load_calibrations;
+use servo::motion::{MotionController, MotionLimits};
+use servo::{PwmSettings, Servo, ServoConfig, SERVO_FREQ_HZ};
+
+/// Tell the Boot ROM about our application
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
+
+/// External high-speed crystal on the Raspberry Pi Pico 2 board is 12 MHz.
+/// Adjust if your board has a different frequency
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+const JOINTS: usize = 4;
+
+/// Index of the elbow joint, whose servo is mounted upside down
+const ELBOW: usize = 2;
+
+/// How often the motion profiles are stepped, once per servo frame
+const UPDATE_PERIOD_US: u32 = 1_000_000 / SERVO_FREQ_HZ;
+
+/// Base, shoulder, elbow, gripper
+const LIMITS: [MotionLimits; JOINTS] = [
+    MotionLimits {
+        max_velocity: 90.0,
+        max_acceleration: 180.0,
+    },
+    MotionLimits {
+        max_velocity: 60.0,
+        max_acceleration: 120.0,
+    },
+    MotionLimits {
+        max_velocity: 90.0,
+        max_acceleration: 180.0,
+    },
+    MotionLimits {
+        max_velocity: 180.0,
+        max_acceleration: 720.0,
+    },
+];
+
+/// Joint angles in degrees, visited in order
+const POSES: [[f32; JOINTS]; 5] = [
+    [90.0, 90.0, 90.0, 30.0],   // Home, gripper open
+    [30.0, 60.0, 120.0, 30.0],  // Reach for the object
+    [30.0, 60.0, 120.0, 90.0],  // Close the gripper
+    [150.0, 100.0, 70.0, 90.0], // Carry it over
+    [150.0, 100.0, 70.0, 30.0], // Let go
+];
+
+type Channel = &'static mut (dyn SetDutyCycle<Error = Infallible> + Send);
+type ArmAndAlarm = (MotionController<Channel, JOINTS>, Alarm0<CopyableTimer0>);
+
+static ARM: Mutex<RefCell<Option<ArmAndAlarm>>> = Mutex::new(RefCell::new(None));
+
+#[hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // The single-cycle I/O block controls our GPIO pins
+    let sio = hal::Sio::new(pac.SIO);
+
+    // Set the pins up according to their function on this particular board
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let mut timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    // The channels end up in a static shared with the interrupt, so the
+    // slices they belong to have to live forever too
+    let pwm_slices = cortex_m::singleton!(
+        : hal::pwm::Slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS)
+    )
+    .unwrap();
+
+    let pwm_settings =
+        PwmSettings::for_frequency(clocks.system_clock.freq().to_Hz(), SERVO_FREQ_HZ);
+
+    pwm_slices.pwm4.set_div_int(pwm_settings.div_int);
+    pwm_slices.pwm4.set_div_frac(pwm_settings.div_frac);
+    pwm_slices.pwm4.set_top(pwm_settings.top);
+    pwm_slices.pwm4.enable();
+
+    pwm_slices.pwm5.set_div_int(pwm_settings.div_int);
+    pwm_slices.pwm5.set_div_frac(pwm_settings.div_frac);
+    pwm_slices.pwm5.set_top(pwm_settings.top);
+    pwm_slices.pwm5.enable();
+
+    let base = &mut pwm_slices.pwm4.channel_a;
+    base.output_to(pins.gpio8);
+    let shoulder = &mut pwm_slices.pwm4.channel_b;
+    shoulder.output_to(pins.gpio9);
+    let elbow = &mut pwm_slices.pwm5.channel_a;
+    elbow.output_to(pins.gpio10);
+    let gripper = &mut pwm_slices.pwm5.channel_b;
+    gripper.output_to(pins.gpio11);
+
+    // Prefer the pulse widths measured with the `calibrate` example, where
+    // each joint is the channel of the same number. Joints without a
+    // record keep the defaults.
+    let calibrations = load_calibrations();
+    let [base_config, shoulder_config, elbow_config, gripper_config] =
+        core::array::from_fn(|joint| {
+            let config = ServoConfig {
+                inverted: joint == ELBOW,
+                ..ServoConfig::default()
+            };
+            calibrations
+                .get(joint)
+                .map_or(config, |calibration| calibration.apply(config))
+        });
+
+    let servos: [Servo<Channel>; JOINTS] = [
+        Servo::new(base, base_config, pwm_settings),
+        Servo::new(shoulder, shoulder_config, pwm_settings),
+        Servo::new(elbow, elbow_config, pwm_settings),
+        Servo::new(gripper, gripper_config, pwm_settings),
+    ];
+    let arm = MotionController::new(servos, LIMITS);
+
+    let mut alarm = timer.alarm_0().unwrap();
+    alarm.schedule(UPDATE_PERIOD_US.micros()).unwrap();
+    alarm.enable_interrupt();
+
+    critical_section::with(|cs| {
+        ARM.borrow(cs).replace(Some((arm, alarm)));
+    });
+
+    // Safety: the interrupt handler only touches state behind the mutex
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(hal::pac::Interrupt::TIMER0_IRQ_0);
+    }
+
+    loop {
+        for pose in POSES {
+            critical_section::with(|cs| {
+                if let Some((arm, _)) = ARM.borrow_ref_mut(cs).as_mut() {
+                    arm.move_to(pose);
+                }
+            });
+
+            // Wait for the interrupt to finish the move
+            while critical_section::with(|cs| {
+                ARM.borrow_ref(cs)
+                    .as_ref()
+                    .is_some_and(|(arm, _)| arm.is_moving())
+            }) {
+                timer.delay_ms(10);
+            }
+
+            timer.delay_ms(500);
+        }
+    }
+}
+
+#[interrupt]
+fn TIMER0_IRQ_0() {
+    critical_section::with(|cs| {
+        if let Some((arm, alarm)) = ARM.borrow_ref_mut(cs).as_mut() {
+            alarm.clear_interrupt();
+            let _ = alarm.schedule(UPDATE_PERIOD_US.micros());
+            arm.update(UPDATE_PERIOD_US as f32 / 1_000_000.0);
+        }
+    });
+}
+
+/// Program metadata for `picotool info`
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"Servo Arm Example"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
@@ -0,0 +1,258 @@
+//! # Servo Calibration Example
+//!
+//! Finds the pulse widths at a servo's end stops and middle over USB serial,
+//! then saves them to flash where the other examples pick them up at boot.
+//! Each servo channel has its own record: plug the servos in one at a time
+//! and pick the channel it will be used as, 0 to 7, before marking.
+//!
+//! Open the serial port with any terminal and use these keys:
+//!
+//! - `+` / `-` nudge the pulse width by 10 µs, `]` / `[` by 1 µs
+//! - `1`, `2`, `3` mark the current pulse width as min, center and max
+//! - `c` moves on to the next channel, keeping the marks of this one
+//! - `t` sweeps between the marks to check them
+//! - `p` prints the channel, current pulse width and marks
+//! - `s` saves the marks of every channel to flash and restarts
+//!
+//! Go slowly near the end stops. Once the servo hums or stops moving it is
+//! pushing against them, so back off a little before marking.
+//!
+//! The servo is on GPIO 9, PWM slice 4 channel B.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use embedded_hal::delay::DelayNs;
+use hal::Clock;
+use heapless::String;
+use panic_halt as _;
+use rp235x_hal as hal;
+use servo::calibration::{Calibration, MAX_CHANNELS, MAX_SAFE_PULSE_US, MIN_SAFE_PULSE_US};
+use servo::flash::{load_calibrations, save_calibrations_and_reset};
+use servo::{PwmSettings, Servo, ServoConfig, SERVO_FREQ_HZ};
+use usb_device::{class_prelude::*, prelude::*};
+use usbd_serial::SerialPort;
+
+/// Tell the Boot ROM about our application
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
+
+/// External high-speed crystal on the Raspberry Pi Pico 2 board is 12 MHz.
+/// Adjust if your board has a different frequency
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Wide enough to reach the end stops of any servo while calibrating
+const CALIBRATION_CONFIG: ServoConfig = ServoConfig {
+    min_pulse_us: MIN_SAFE_PULSE_US,
+    center_pulse_us: 1500,
+    max_pulse_us: MAX_SAFE_PULSE_US,
+    range_degrees: 180.0,
+    inverted: false,
+};
+
+const COARSE_STEP_US: u16 = 10;
+const FINE_STEP_US: u16 = 1;
+
+const HELP: &str = "\r\n\
+    +/- : pulse width +/-10us\r\n\
+    ]/[ : pulse width +/-1us\r\n\
+    1 2 3 : mark min, center, max\r\n\
+    c : next channel\r\n\
+    t : test sweep\r\n\
+    p : print\r\n\
+    s : save and restart\r\n";
+
+#[hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // The single-cycle I/O block controls our GPIO pins
+    let sio = hal::Sio::new(pac.SIO);
+
+    // Set the pins up according to their function on this particular board
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let mut timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    // For USB Serial
+    let usb_bus = UsbBusAllocator::new(hal::usb::UsbBus::new(
+        pac.USB,
+        pac.USB_DPRAM,
+        clocks.usb_clock,
+        true,
+        &mut pac.RESETS,
+    ));
+
+    // Set up the USB Communications Class Device driver
+    let mut serial = SerialPort::new(&usb_bus);
+
+    // Create a USB device with a fake VID and PID
+    let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
+        .strings(&[StringDescriptors::default()
+            .manufacturer("Fake company")
+            .product("Servo calibration")
+            .serial_number("TEST")])
+        .unwrap()
+        .device_class(2) // from: https://www.usb.org/defined-class-codes
+        .build();
+
+    // Init PWMs
+    let mut pwm_slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
+
+    let pwm_settings =
+        PwmSettings::for_frequency(clocks.system_clock.freq().to_Hz(), SERVO_FREQ_HZ);
+
+    let pwm = &mut pwm_slices.pwm4;
+    pwm.set_div_int(pwm_settings.div_int);
+    pwm.set_div_frac(pwm_settings.div_frac);
+    pwm.set_top(pwm_settings.top);
+    pwm.enable();
+
+    let channel = &mut pwm.channel_b;
+    channel.output_to(pins.gpio9);
+
+    // Start from the saved calibrations if there are any
+    let mut table = load_calibrations();
+    let mut servo_channel = 0;
+    let mut marks = table
+        .get(servo_channel)
+        .unwrap_or(Calibration::from(ServoConfig::default()));
+
+    let mut servo = Servo::new(channel, CALIBRATION_CONFIG, pwm_settings);
+    servo.set_pulse_us(marks.center_pulse_us);
+
+    let mut out: String<128> = String::new();
+
+    loop {
+        if !usb_dev.poll(&mut [&mut serial]) {
+            continue;
+        }
+
+        let mut buf = [0u8; 64];
+        let Ok(count) = serial.read(&mut buf) else {
+            continue;
+        };
+
+        for &byte in &buf[..count] {
+            let pulse_us = servo.pulse_us();
+            out.clear();
+
+            match byte {
+                b'+' => servo.set_pulse_us(pulse_us.saturating_add(COARSE_STEP_US)),
+                b'-' => servo.set_pulse_us(pulse_us.saturating_sub(COARSE_STEP_US)),
+                b']' => servo.set_pulse_us(pulse_us.saturating_add(FINE_STEP_US)),
+                b'[' => servo.set_pulse_us(pulse_us.saturating_sub(FINE_STEP_US)),
+                b'1' => {
+                    marks.min_pulse_us = pulse_us;
+                    let _ = write!(out, "min = {}us\r\n", pulse_us);
+                }
+                b'2' => {
+                    marks.center_pulse_us = pulse_us;
+                    let _ = write!(out, "center = {}us\r\n", pulse_us);
+                }
+                b'3' => {
+                    marks.max_pulse_us = pulse_us;
+                    let _ = write!(out, "max = {}us\r\n", pulse_us);
+                }
+                b'c' => {
+                    if marks.is_valid() {
+                        table.set(servo_channel, marks);
+                    }
+                    servo_channel = (servo_channel + 1) % MAX_CHANNELS;
+                    marks = table
+                        .get(servo_channel)
+                        .unwrap_or(Calibration::from(ServoConfig::default()));
+                    servo.set_pulse_us(marks.center_pulse_us);
+                    let _ = write!(out, "channel {}\r\n", servo_channel);
+                }
+                b't' => {
+                    // Sweep min -> max -> center using the marks as the
+                    // real driver would, then hand control back
+                    if marks.is_valid() {
+                        let config = marks.apply(CALIBRATION_CONFIG);
+                        for angle in (0..=180).chain((90..180).rev()) {
+                            let pulse = config.angle_to_pulse_us(angle as f32);
+                            servo.set_pulse_us(pulse);
+                            timer.delay_ms(15);
+                            let _ = usb_dev.poll(&mut [&mut serial]);
+                        }
+                        let _ = write!(out, "sweep done\r\n");
+                    } else {
+                        let _ = write!(out, "marks must be min < center < max\r\n");
+                    }
+                }
+                b'p' => {
+                    let _ = write!(
+                        out,
+                        "channel {}: pulse = {}us, min = {}us, center = {}us, max = {}us\r\n",
+                        servo_channel,
+                        pulse_us,
+                        marks.min_pulse_us,
+                        marks.center_pulse_us,
+                        marks.max_pulse_us
+                    );
+                }
+                b's' => {
+                    if marks.is_valid() {
+                        let _ = serial.write(b"saving, restarting...\r\n");
+                        // Give the host a moment to collect the message
+                        for _ in 0..100 {
+                            let _ = usb_dev.poll(&mut [&mut serial]);
+                            timer.delay_ms(1);
+                        }
+                        table.set(servo_channel, marks);
+                        save_calibrations_and_reset(&table);
+                    }
+                    let _ = write!(out, "not saved, marks must be min < center < max\r\n");
+                }
+                b'h' | b'?' => {
+                    let _ = serial.write(HELP.as_bytes());
+                }
+                _ => {}
+            }
+
+            if servo.pulse_us() != pulse_us {
+                let _ = write!(out, "{}us\r\n", servo.pulse_us());
+            }
+            let _ = serial.write(out.as_bytes());
+        }
+    }
+}
+
+/// Program metadata for `picotool info`
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"Servo Calibration Example"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
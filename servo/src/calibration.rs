@@ -0,0 +1,254 @@
+//! Measured pulse widths for each servo, and how they are stored.
+//!
+//! The stored table holds one 16 byte record per servo channel, up to
+//! [`MAX_CHANNELS`], one after the other. A record is:
+//!
+//! ```text
+//!  0..4   magic "SRVC"
+//!  4      format version
+//!  5      channel, the same as the record's place in the table
+//!  6..8   min pulse width in µs, little endian
+//!  8..10  center pulse width in µs, little endian
+//!  10..12 max pulse width in µs, little endian
+//!  12..14 reserved (0)
+//!  14..16 CRC-16/CCITT of bytes 0..14, little endian
+//! ```
+
+use crate::ServoConfig;
+
+const MAGIC: [u8; 4] = *b"SRVC";
+const VERSION: u8 = 1;
+
+/// Shortest pulse any hobby servo is expected to accept
+pub const MIN_SAFE_PULSE_US: u16 = 400;
+
+/// Longest pulse any hobby servo is expected to accept
+pub const MAX_SAFE_PULSE_US: u16 = 2600;
+
+/// Number of servos the table has room for
+pub const MAX_CHANNELS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calibration {
+    pub min_pulse_us: u16,
+    pub center_pulse_us: u16,
+    pub max_pulse_us: u16,
+}
+
+impl Calibration {
+    /// Size of an encoded record in bytes
+    pub const SIZE: usize = 16;
+
+    /// Checks that the three points are in order and inside the safe range.
+    pub fn is_valid(&self) -> bool {
+        MIN_SAFE_PULSE_US <= self.min_pulse_us
+            && self.min_pulse_us < self.center_pulse_us
+            && self.center_pulse_us < self.max_pulse_us
+            && self.max_pulse_us <= MAX_SAFE_PULSE_US
+    }
+
+    /// Encodes the record for servo `channel`.
+    pub fn to_bytes(&self, channel: u8) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4] = VERSION;
+        bytes[5] = channel;
+        bytes[6..8].copy_from_slice(&self.min_pulse_us.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.center_pulse_us.to_le_bytes());
+        bytes[10..12].copy_from_slice(&self.max_pulse_us.to_le_bytes());
+        let crc = crc16(&bytes[..14]);
+        bytes[14..16].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes a record and the channel it belongs to, returning `None` for
+    /// blank flash, a different format version, a bad checksum or out of
+    /// range values.
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<(u8, Self)> {
+        if bytes[0..4] != MAGIC || bytes[4] != VERSION {
+            return None;
+        }
+        let crc = u16::from_le_bytes([bytes[14], bytes[15]]);
+        if crc != crc16(&bytes[..14]) {
+            return None;
+        }
+
+        let calibration = Self {
+            min_pulse_us: u16::from_le_bytes([bytes[6], bytes[7]]),
+            center_pulse_us: u16::from_le_bytes([bytes[8], bytes[9]]),
+            max_pulse_us: u16::from_le_bytes([bytes[10], bytes[11]]),
+        };
+        calibration.is_valid().then_some((bytes[5], calibration))
+    }
+
+    /// Returns `config` with its pulse widths replaced by the measured ones.
+    pub fn apply(&self, config: ServoConfig) -> ServoConfig {
+        ServoConfig {
+            min_pulse_us: self.min_pulse_us,
+            center_pulse_us: self.center_pulse_us,
+            max_pulse_us: self.max_pulse_us,
+            ..config
+        }
+    }
+}
+
+impl From<ServoConfig> for Calibration {
+    fn from(config: ServoConfig) -> Self {
+        Self {
+            min_pulse_us: config.min_pulse_us,
+            center_pulse_us: config.center_pulse_us,
+            max_pulse_us: config.max_pulse_us,
+        }
+    }
+}
+
+/// Calibrations of all servo channels, as stored in flash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CalibrationTable {
+    records: [Option<Calibration>; MAX_CHANNELS],
+}
+
+impl CalibrationTable {
+    /// Size of the encoded table in bytes
+    pub const SIZE: usize = MAX_CHANNELS * Calibration::SIZE;
+
+    /// The calibration of servo `channel`, if it has one
+    pub fn get(&self, channel: usize) -> Option<Calibration> {
+        self.records.get(channel).copied().flatten()
+    }
+
+    /// Replaces the calibration of servo `channel`. Channels past
+    /// [`MAX_CHANNELS`] are ignored.
+    pub fn set(&mut self, channel: usize, calibration: Calibration) {
+        if let Some(record) = self.records.get_mut(channel) {
+            *record = Some(calibration);
+        }
+    }
+
+    /// Encodes the table, leaving channels without a calibration erased.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0xFFu8; Self::SIZE];
+        for (channel, (record, out)) in self
+            .records
+            .iter()
+            .zip(bytes.chunks_exact_mut(Calibration::SIZE))
+            .enumerate()
+        {
+            if let Some(calibration) = record {
+                out.copy_from_slice(&calibration.to_bytes(channel as u8));
+            }
+        }
+        bytes
+    }
+
+    /// Decodes a table. Records that don't decode, or that are filed under
+    /// another channel than their own, are left out.
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        let mut table = Self::default();
+        for (channel, (record, chunk)) in table
+            .records
+            .iter_mut()
+            .zip(bytes.chunks_exact(Calibration::SIZE))
+            .enumerate()
+        {
+            let chunk = chunk.try_into().unwrap();
+            *record = Calibration::from_bytes(chunk)
+                .filter(|&(key, _)| key as usize == channel)
+                .map(|(_, calibration)| calibration);
+        }
+        table
+    }
+}
+
+/// CRC-16/CCITT-FALSE
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEASURED: Calibration = Calibration {
+        min_pulse_us: 560,
+        center_pulse_us: 1480,
+        max_pulse_us: 2410,
+    };
+
+    #[test]
+    fn round_trips_a_record() {
+        let bytes = MEASURED.to_bytes(3);
+        assert_eq!(&bytes[0..4], b"SRVC");
+        assert_eq!(Calibration::from_bytes(&bytes), Some((3, MEASURED)));
+    }
+
+    #[test]
+    fn rejects_corrupted_records() {
+        let bytes = MEASURED.to_bytes(0);
+        for i in 0..Calibration::SIZE {
+            let mut bytes = bytes;
+            bytes[i] ^= 0x01;
+            assert_eq!(Calibration::from_bytes(&bytes), None, "byte {i}");
+        }
+    }
+
+    #[test]
+    fn rejects_erased_flash() {
+        assert_eq!(Calibration::from_bytes(&[0xFF; Calibration::SIZE]), None);
+        assert_eq!(
+            CalibrationTable::from_bytes(&[0xFF; CalibrationTable::SIZE]),
+            CalibrationTable::default()
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_order_pulse_widths() {
+        let swapped = Calibration {
+            min_pulse_us: MEASURED.max_pulse_us,
+            max_pulse_us: MEASURED.min_pulse_us,
+            ..MEASURED
+        };
+        assert_eq!(Calibration::from_bytes(&swapped.to_bytes(0)), None);
+    }
+
+    #[test]
+    fn round_trips_a_table() {
+        let mut table = CalibrationTable::default();
+        table.set(1, MEASURED);
+        table.set(7, Calibration::from(ServoConfig::default()));
+        table.set(MAX_CHANNELS, MEASURED);
+
+        let decoded = CalibrationTable::from_bytes(&table.to_bytes());
+        assert_eq!(decoded, table);
+        assert_eq!(decoded.get(0), None);
+        assert_eq!(decoded.get(1), Some(MEASURED));
+        assert_eq!(decoded.get(MAX_CHANNELS), None);
+    }
+
+    #[test]
+    fn skips_records_filed_under_another_channel() {
+        let mut bytes = [0xFF; CalibrationTable::SIZE];
+        bytes[..Calibration::SIZE].copy_from_slice(&MEASURED.to_bytes(2));
+        assert_eq!(CalibrationTable::from_bytes(&bytes).get(0), None);
+        assert_eq!(CalibrationTable::from_bytes(&bytes).get(2), None);
+    }
+
+    #[test]
+    fn reads_a_single_record_as_channel_zero() {
+        // What earlier firmware saved: one record with a 0 reserved byte
+        let mut bytes = [0xFF; CalibrationTable::SIZE];
+        bytes[..Calibration::SIZE].copy_from_slice(&MEASURED.to_bytes(0));
+        assert_eq!(CalibrationTable::from_bytes(&bytes).get(0), Some(MEASURED));
+    }
+}
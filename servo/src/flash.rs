@@ -0,0 +1,95 @@
+//! Persists the servo calibrations in on-board flash.
+//!
+//! The table of records lives in the last 4 KiB sector of the 2 MiB program region.
+//! `memory.x` ends the `FLASH` region one sector early so the linker never
+//! puts code there.
+
+use rp235x_hal::rom_data;
+
+use crate::calibration::{Calibration, CalibrationTable};
+
+/// Offset of the calibration sector from the start of flash
+pub const CALIBRATION_OFFSET: u32 = 0x001F_F000;
+
+/// Flash is memory mapped (read-only) from here while XIP is active
+const XIP_BASE: u32 = 0x1000_0000;
+
+const SECTOR_SIZE: usize = 4096;
+const PAGE_SIZE: usize = 256;
+
+/// Erase granularity the boot ROM uses when a 64 KiB block fits in the range
+const BLOCK_SIZE: u32 = 1 << 16;
+const BLOCK_ERASE_CMD: u8 = 0xD8;
+
+/// Application Interrupt and Reset Control Register
+const AIRCR: *mut u32 = 0xE000_ED0C as *mut u32;
+const AIRCR_VECTKEY: u32 = 0x05FA << 16;
+const AIRCR_SYSRESETREQ: u32 = 1 << 2;
+
+/// Reads the calibrations saved by [`save_calibrations_and_reset`]. Channels
+/// without a valid record have none.
+pub fn load_calibrations() -> CalibrationTable {
+    let src = (XIP_BASE + CALIBRATION_OFFSET) as *const u8;
+    let mut bytes = [0u8; CalibrationTable::SIZE];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        // Safety: the sector is inside the memory mapped flash window
+        *byte = unsafe { core::ptr::read_volatile(src.add(i)) };
+    }
+    CalibrationTable::from_bytes(&bytes)
+}
+
+/// Reads the saved calibration of servo `channel`, if there is a valid one.
+pub fn load_calibration(channel: usize) -> Option<Calibration> {
+    load_calibrations().get(channel)
+}
+
+/// Boot ROM entry points needed to rewrite the sector
+struct RomFlashFunctions {
+    connect_internal_flash: unsafe extern "C" fn(),
+    flash_exit_xip: unsafe extern "C" fn(),
+    flash_range_erase: unsafe extern "C" fn(u32, usize, u32, u8),
+    flash_range_program: unsafe extern "C" fn(u32, *const u8, usize),
+    flash_flush_cache: unsafe extern "C" fn(),
+}
+
+/// Writes the calibrations of every channel to flash and resets the chip.
+///
+/// Code can't run from flash while it is being erased, so the actual
+/// work happens in a function placed in RAM with interrupts disabled.
+/// Afterwards the flash interface is left in plain serial mode, and getting
+/// it back to fast XIP is the boot path's job, so the chip is reset instead
+/// of returning. The new calibrations are picked up on the next boot.
+pub fn save_calibrations_and_reset(table: &CalibrationTable) -> ! {
+    let mut page = [0xFFu8; PAGE_SIZE];
+    page[..CalibrationTable::SIZE].copy_from_slice(&table.to_bytes());
+
+    // The lookup code lives in flash, so resolve everything up front
+    let rom = RomFlashFunctions {
+        connect_internal_flash: rom_data::connect_internal_flash::ptr(),
+        flash_exit_xip: rom_data::flash_exit_xip::ptr(),
+        flash_range_erase: rom_data::flash_range_erase::ptr(),
+        flash_range_program: rom_data::flash_range_program::ptr(),
+        flash_flush_cache: rom_data::flash_flush_cache::ptr(),
+    };
+
+    cortex_m::interrupt::disable();
+
+    // Safety: interrupts are off and nothing else touches flash until the
+    // reset
+    unsafe { write_and_reset(&rom, &page) }
+}
+
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn write_and_reset(rom: &RomFlashFunctions, page: &[u8; PAGE_SIZE]) -> ! {
+    (rom.connect_internal_flash)();
+    (rom.flash_exit_xip)();
+    (rom.flash_range_erase)(CALIBRATION_OFFSET, SECTOR_SIZE, BLOCK_SIZE, BLOCK_ERASE_CMD);
+    (rom.flash_range_program)(CALIBRATION_OFFSET, page.as_ptr(), PAGE_SIZE);
+    (rom.flash_flush_cache)();
+
+    core::ptr::write_volatile(AIRCR, AIRCR_VECTKEY | AIRCR_SYSRESETREQ);
+    loop {
+        core::hint::spin_loop();
+    }
+}
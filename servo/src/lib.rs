@@ -0,0 +1,374 @@
+//! # Servo Driver
+//!
+//! Drives a hobby servo from one PWM channel. The PWM slice runs at 50 Hz and
+//! the servo position is set by the high time of each pulse, normally
+//! somewhere between 0.5 ms and 2.5 ms.
+//!
+//! The maths is kept in plain functions so it can be checked without
+//! hardware. Only [`flash`] needs the HAL, and it is left out along with the
+//! `hal` feature to run the tests on the host.
+
+#![no_std]
+
+use embedded_hal::pwm::SetDutyCycle;
+
+pub mod calibration;
+#[cfg(feature = "hal")]
+pub mod flash;
+pub mod motion;
+
+/// Standard hobby servo frame rate
+pub const SERVO_FREQ_HZ: u32 = 50;
+
+/// Divider and wrap value for one PWM slice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PwmSettings {
+    pub div_int: u8,
+    pub div_frac: u8,
+    pub top: u16,
+}
+
+impl PwmSettings {
+    /// Picks the smallest integer divider that lets a slice clocked at
+    /// `sys_clk_hz` wrap at `freq_hz`, which gives the largest `top` and so
+    /// the finest pulse width steps.
+    ///
+    /// At 150 MHz and 50 Hz this is a divider of 46 and a `top` of 65216,
+    /// about 0.3 µs per step.
+    pub const fn for_frequency(sys_clk_hz: u32, freq_hz: u32) -> Self {
+        let counts = sys_clk_hz / freq_hz;
+        let mut div = counts.div_ceil(1 << 16);
+        if div == 0 {
+            div = 1;
+        }
+        if div > u8::MAX as u32 {
+            div = u8::MAX as u32;
+        }
+        let top = (counts / div).saturating_sub(1);
+        Self {
+            div_int: div as u8,
+            div_frac: 0,
+            top: if top > u16::MAX as u32 {
+                u16::MAX
+            } else {
+                top as u16
+            },
+        }
+    }
+
+    /// Number of counter steps in one PWM period
+    pub const fn period_counts(&self) -> u32 {
+        self.top as u32 + 1
+    }
+}
+
+/// Per-servo limits
+///
+/// Servos differ in the pulse widths that reach their end stops and their
+/// middle, so these should be measured for each one (see the `calibrate`
+/// example). Pulses outside `min_pulse_us` to `max_pulse_us` are never sent.
+///
+/// Angles map linearly from `min_pulse_us` to `center_pulse_us` for the
+/// first half of the range and from `center_pulse_us` to `max_pulse_us` for
+/// the second half.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServoConfig {
+    /// Pulse width at 0°
+    pub min_pulse_us: u16,
+    /// Pulse width at half of `range_degrees`
+    pub center_pulse_us: u16,
+    /// Pulse width at `range_degrees`
+    pub max_pulse_us: u16,
+    /// Angle covered between the two end stops. Anything but a positive
+    /// number leaves the servo at its center.
+    pub range_degrees: f32,
+    /// Swap the direction, for servos mounted the other way round
+    pub inverted: bool,
+}
+
+impl Default for ServoConfig {
+    fn default() -> Self {
+        Self {
+            min_pulse_us: 500,
+            center_pulse_us: 1450,
+            max_pulse_us: 2400,
+            range_degrees: 180.0,
+            inverted: false,
+        }
+    }
+}
+
+impl ServoConfig {
+    /// Pulse width for an angle, clamped to the servo's range.
+    pub fn angle_to_pulse_us(&self, degrees: f32) -> u16 {
+        let range = self.range();
+        let degrees = self.clamp_angle(degrees);
+        let degrees = if self.inverted {
+            range - degrees
+        } else {
+            degrees
+        };
+
+        let (min, center, max) = self.pulses();
+        if range == 0.0 {
+            return (center + 0.5) as u16;
+        }
+        let half = range / 2.0;
+        let pulse = if degrees <= half {
+            min + (center - min) * degrees / half
+        } else {
+            center + (max - center) * (degrees - half) / half
+        };
+        (pulse + 0.5) as u16
+    }
+
+    /// Angle for a pulse width, the inverse of
+    /// [`ServoConfig::angle_to_pulse_us`].
+    pub fn pulse_us_to_angle(&self, pulse_us: u16) -> f32 {
+        let pulse = self.clamp_pulse_us(pulse_us) as f32;
+
+        let (min, center, max) = self.pulses();
+        let half = self.range() / 2.0;
+        let degrees = if pulse <= center {
+            fraction(min, center, pulse) * half
+        } else {
+            half + fraction(center, max, pulse) * half
+        };
+
+        if self.inverted {
+            self.range() - degrees
+        } else {
+            degrees
+        }
+    }
+
+    /// Keeps an angle within the servo's range. NaN ends up at 0°.
+    pub fn clamp_angle(&self, degrees: f32) -> f32 {
+        degrees.max(0.0).min(self.range())
+    }
+
+    /// `range_degrees`, or 0 if it is negative or NaN
+    fn range(&self) -> f32 {
+        self.range_degrees.max(0.0)
+    }
+
+    /// Keeps a pulse width within the limits. With `max_pulse_us` below
+    /// `min_pulse_us`, which no working servo has, `max_pulse_us` wins.
+    pub fn clamp_pulse_us(&self, pulse_us: u16) -> u16 {
+        pulse_us.max(self.min_pulse_us).min(self.max_pulse_us)
+    }
+
+    /// The three pulse widths as floats, so spans between them can't
+    /// underflow whatever order the fields are in
+    fn pulses(&self) -> (f32, f32, f32) {
+        (
+            self.min_pulse_us as f32,
+            self.center_pulse_us as f32,
+            self.max_pulse_us as f32,
+        )
+    }
+}
+
+/// How far `value` is from `from` towards `to`, between 0 and 1
+fn fraction(from: f32, to: f32, value: f32) -> f32 {
+    if to > from {
+        ((value - from) / (to - from)).clamp(0.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+/// Duty cycle value that keeps the output high for `pulse_us` of every
+/// period.
+pub const fn pulse_us_to_duty(pulse_us: u16, pwm: PwmSettings, freq_hz: u32) -> u16 {
+    let period_us = 1_000_000 / freq_hz;
+    let duty = pulse_us as u32 * pwm.period_counts() / period_us;
+    if duty > pwm.top as u32 {
+        pwm.top
+    } else {
+        duty as u16
+    }
+}
+
+/// A servo on one PWM channel
+///
+/// The slice must already be set up with the same [`PwmSettings`] and
+/// enabled.
+pub struct Servo<C> {
+    channel: C,
+    config: ServoConfig,
+    pwm: PwmSettings,
+    pulse_us: u16,
+}
+
+impl<C: SetDutyCycle> Servo<C> {
+    /// Creates the servo and moves it to its center position.
+    pub fn new(channel: C, config: ServoConfig, pwm: PwmSettings) -> Self {
+        let mut servo = Self {
+            channel,
+            config,
+            pwm,
+            pulse_us: config.center_pulse_us,
+        };
+        servo.set_pulse_us(servo.pulse_us);
+        servo
+    }
+
+    pub fn config(&self) -> ServoConfig {
+        self.config
+    }
+
+    /// Replaces the limits, for example after calibration, and re-applies
+    /// the current angle within the new range.
+    pub fn set_config(&mut self, config: ServoConfig) {
+        let angle = self.angle();
+        self.config = config;
+        self.set_angle(angle);
+    }
+
+    /// Moves to an angle in degrees, clamped to the servo's range.
+    pub fn set_angle(&mut self, degrees: f32) {
+        let pulse_us = self.config.angle_to_pulse_us(degrees);
+        self.set_pulse_us(pulse_us);
+    }
+
+    pub fn angle(&self) -> f32 {
+        self.config.pulse_us_to_angle(self.pulse_us)
+    }
+
+    /// Sends a raw pulse width, clamped to the calibrated limits.
+    pub fn set_pulse_us(&mut self, pulse_us: u16) {
+        self.pulse_us = self.config.clamp_pulse_us(pulse_us);
+        let duty = pulse_us_to_duty(self.pulse_us, self.pwm, SERVO_FREQ_HZ);
+        let _ = self.channel.set_duty_cycle(duty);
+    }
+
+    pub fn pulse_us(&self) -> u16 {
+        self.pulse_us
+    }
+
+    /// Stops sending pulses so the servo goes limp.
+    pub fn disable(&mut self) {
+        let _ = self.channel.set_duty_cycle_fully_off();
+    }
+
+    /// Gives back the PWM channel.
+    pub fn release(self) -> C {
+        self.channel
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYS_CLK_HZ: u32 = 150_000_000;
+
+    #[test]
+    fn pwm_settings_at_50_hz() {
+        let pwm = PwmSettings::for_frequency(SYS_CLK_HZ, SERVO_FREQ_HZ);
+        assert_eq!(pwm.div_int, 46);
+        assert_eq!(pwm.div_frac, 0);
+        assert_eq!(pwm.top, 65216);
+    }
+
+    #[test]
+    fn pwm_settings_saturate() {
+        // Too slow for the largest divider, so top is as large as it gets
+        let pwm = PwmSettings::for_frequency(SYS_CLK_HZ, 1);
+        assert_eq!(pwm.div_int, u8::MAX);
+        assert_eq!(pwm.top, u16::MAX);
+        // Faster than the clock
+        let pwm = PwmSettings::for_frequency(1000, 2000);
+        assert_eq!(pwm.div_int, 1);
+        assert_eq!(pwm.top, 0);
+    }
+
+    #[test]
+    fn duty_for_pulse_widths() {
+        let pwm = PwmSettings::for_frequency(SYS_CLK_HZ, SERVO_FREQ_HZ);
+        assert_eq!(pulse_us_to_duty(0, pwm, SERVO_FREQ_HZ), 0);
+        // 1.5 ms of a 20 ms period
+        assert_eq!(pulse_us_to_duty(1500, pwm, SERVO_FREQ_HZ), 4891);
+        assert_eq!(pulse_us_to_duty(20_000, pwm, SERVO_FREQ_HZ), pwm.top);
+        assert_eq!(pulse_us_to_duty(u16::MAX, pwm, SERVO_FREQ_HZ), pwm.top);
+    }
+
+    #[test]
+    fn angles_to_pulse_widths() {
+        let config = ServoConfig::default();
+        assert_eq!(config.angle_to_pulse_us(0.0), 500);
+        assert_eq!(config.angle_to_pulse_us(45.0), 975);
+        assert_eq!(config.angle_to_pulse_us(90.0), 1450);
+        assert_eq!(config.angle_to_pulse_us(180.0), 2400);
+        assert_eq!(config.angle_to_pulse_us(-10.0), 500);
+        assert_eq!(config.angle_to_pulse_us(270.0), 2400);
+    }
+
+    #[test]
+    fn inverted() {
+        let config = ServoConfig {
+            inverted: true,
+            ..ServoConfig::default()
+        };
+        assert_eq!(config.angle_to_pulse_us(0.0), 2400);
+        assert_eq!(config.angle_to_pulse_us(180.0), 500);
+        assert_eq!(config.pulse_us_to_angle(2400), 0.0);
+    }
+
+    #[test]
+    fn pulse_widths_to_angles() {
+        let config = ServoConfig::default();
+        assert_eq!(config.pulse_us_to_angle(500), 0.0);
+        assert_eq!(config.pulse_us_to_angle(1450), 90.0);
+        assert_eq!(config.pulse_us_to_angle(2400), 180.0);
+        assert_eq!(config.pulse_us_to_angle(100), 0.0);
+        assert_eq!(config.pulse_us_to_angle(3000), 180.0);
+        for angle in [10.0, 60.0, 100.0, 170.0] {
+            let pulse = config.angle_to_pulse_us(angle);
+            assert!((config.pulse_us_to_angle(pulse) - angle).abs() < 0.2);
+        }
+    }
+
+    #[test]
+    fn misordered_limits_dont_panic() {
+        let config = ServoConfig {
+            min_pulse_us: 1500,
+            center_pulse_us: 1000,
+            max_pulse_us: 1200,
+            ..ServoConfig::default()
+        };
+        for angle in [0.0, 45.0, 90.0, 135.0, 180.0] {
+            let pulse = config.angle_to_pulse_us(angle);
+            assert!(config.pulse_us_to_angle(pulse).is_finite());
+        }
+        assert_eq!(config.clamp_pulse_us(500), 1200);
+        assert!(config.pulse_us_to_angle(0).is_finite());
+
+        let flat = ServoConfig {
+            min_pulse_us: 1500,
+            center_pulse_us: 1500,
+            max_pulse_us: 1500,
+            ..ServoConfig::default()
+        };
+        assert_eq!(flat.angle_to_pulse_us(30.0), 1500);
+        assert_eq!(flat.pulse_us_to_angle(1500), 0.0);
+    }
+
+    #[test]
+    fn bad_ranges_dont_panic() {
+        for range_degrees in [-90.0, 0.0, f32::NAN] {
+            let config = ServoConfig {
+                range_degrees,
+                ..ServoConfig::default()
+            };
+            assert_eq!(config.angle_to_pulse_us(45.0), 1450);
+            assert_eq!(config.angle_to_pulse_us(f32::NAN), 1450);
+            assert_eq!(config.clamp_angle(45.0), 0.0);
+            assert_eq!(config.pulse_us_to_angle(2400), 0.0);
+        }
+        let config = ServoConfig::default();
+        assert_eq!(config.angle_to_pulse_us(f32::NAN), 500);
+        assert_eq!(config.clamp_angle(f32::NAN), 0.0);
+    }
+}
@@ -10,7 +10,9 @@ use rp235x_hal as hal;
 
 // Some things we need
 use embedded_hal::delay::DelayNs;
-use embedded_hal::pwm::SetDutyCycle;
+use hal::Clock;
+use servo::flash::load_calibration;
+use servo::{PwmSettings, Servo, ServoConfig, SERVO_FREQ_HZ};
 
 /// Tell the Boot ROM about our application
 #[link_section = ".start_block"]
@@ -21,16 +23,16 @@ pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
 /// Adjust if your board has a different frequency
 const XTAL_FREQ_HZ: u32 = 12_000_000u32;
 
-// const PWM_DIV_INT: u8 = 128;
-// const PWM_TOP: u16 = 23_436;
-
-const PWM_DIV_INT: u8 = 64;
-const PWM_TOP: u16 = 46_874;
-
-const TOP: u16 = PWM_TOP + 1;
-const MIN_DUTY: u16 = (TOP as f64 * (2.5 / 100.)) as u16;
-const HALF_DUTY: u16 = (TOP as f64 * (7.5 / 100.)) as u16;
-const MAX_DUTY: u16 = (TOP as f64 * (12. / 100.)) as u16;
+/// Pulse widths at the end stops of our servo, used until the `calibrate`
+/// example has saved measured ones. Pushing past the end stops makes the
+/// servo stall and heat up.
+const SERVO_CONFIG: ServoConfig = ServoConfig {
+    min_pulse_us: 500,
+    center_pulse_us: 1450,
+    max_pulse_us: 2400,
+    range_degrees: 180.0,
+    inverted: false,
+};
 
 #[hal::entry]
 fn main() -> ! {
@@ -72,24 +74,42 @@ fn main() -> ! {
     // Init PWMs
     let mut pwm_slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
 
+    // Work out the divider and TOP for 50 Hz from the actual system clock
+    let pwm_settings =
+        PwmSettings::for_frequency(clocks.system_clock.freq().to_Hz(), SERVO_FREQ_HZ);
+
     // Configure PWM4
     let pwm = &mut pwm_slices.pwm4;
 
-    pwm.set_div_int(PWM_DIV_INT);
-    pwm.set_div_frac(0);
+    pwm.set_div_int(pwm_settings.div_int);
+    pwm.set_div_frac(pwm_settings.div_frac);
 
-    pwm.set_top(PWM_TOP);
+    pwm.set_top(pwm_settings.top);
     pwm.enable();
 
-    let servo = &mut pwm.channel_b;
-    servo.output_to(pins.gpio9);
+    let channel = &mut pwm.channel_b;
+    channel.output_to(pins.gpio9);
+
+    // Prefer the pulse widths measured with the `calibrate` example for
+    // channel 0
+    let config = load_calibration(0)
+        .map(|calibration| calibration.apply(SERVO_CONFIG))
+        .unwrap_or(SERVO_CONFIG);
+
+    let mut servo = Servo::new(channel, config, pwm_settings);
 
     loop {
-        servo.set_duty_cycle(MIN_DUTY).unwrap();
-        timer.delay_ms(1000);
-        servo.set_duty_cycle(HALF_DUTY).unwrap();
-        timer.delay_ms(1000);
-        servo.set_duty_cycle(MAX_DUTY).unwrap();
+        // Jump between the end stops and the middle
+        for angle in [0.0, 90.0, 180.0] {
+            servo.set_angle(angle);
+            timer.delay_ms(1000);
+        }
+
+        // Sweep back slowly, one degree at a time
+        for angle in (0..=180).rev() {
+            servo.set_angle(angle as f32);
+            timer.delay_ms(15);
+        }
         timer.delay_ms(1000);
     }
 }
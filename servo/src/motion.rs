@@ -0,0 +1,376 @@
+//! Coordinated moves for several servos.
+//!
+//! Each joint follows a trapezoidal velocity profile: it speeds up at a
+//! constant acceleration, cruises, then slows down at the same rate. When a
+//! move is planned, the slowest joint sets the duration and every other
+//! joint is stretched to take exactly as long, so they all start and stop
+//! together.
+
+use embedded_hal::pwm::SetDutyCycle;
+
+use crate::Servo;
+
+/// Largest number of servos one controller drives. Two servos share each
+/// PWM slice, so this takes half of the RP2350's slices.
+pub const MAX_SERVOS: usize = 8;
+
+/// How fast a joint is allowed to move
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionLimits {
+    /// Degrees per second
+    pub max_velocity: f32,
+    /// Degrees per second squared
+    pub max_acceleration: f32,
+}
+
+impl Default for MotionLimits {
+    fn default() -> Self {
+        Self {
+            max_velocity: 120.0,
+            max_acceleration: 360.0,
+        }
+    }
+}
+
+/// A single-axis move from rest to rest
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrapezoidProfile {
+    start: f32,
+    /// Signed distance to travel
+    distance: f32,
+    acceleration: f32,
+    peak_velocity: f32,
+    accel_time: f32,
+    cruise_time: f32,
+}
+
+impl TrapezoidProfile {
+    /// Plans the fastest move allowed by `limits`.
+    ///
+    /// Short moves never reach `max_velocity` and end up triangular.
+    pub fn fastest(start: f32, end: f32, limits: MotionLimits) -> Self {
+        let distance = end - start;
+        let length = libm::fabsf(distance);
+        let a = limits.max_acceleration;
+
+        // Distance covered while speeding up to max velocity and back down
+        let ramp_length = limits.max_velocity * limits.max_velocity / a;
+
+        let (peak_velocity, accel_time, cruise_time) = if length >= ramp_length {
+            let accel_time = limits.max_velocity / a;
+            let cruise_time = (length - ramp_length) / limits.max_velocity;
+            (limits.max_velocity, accel_time, cruise_time)
+        } else {
+            let accel_time = libm::sqrtf(length / a);
+            (a * accel_time, accel_time, 0.0)
+        };
+
+        Self {
+            start,
+            distance,
+            acceleration: a,
+            peak_velocity,
+            accel_time,
+            cruise_time,
+        }
+    }
+
+    /// Plans a move that takes exactly `duration` seconds, using the given
+    /// acceleration.
+    ///
+    /// `duration` must be at least as long as the fastest move for the
+    /// same limits, which is what [`MotionController::move_to`] makes sure
+    /// of.
+    pub fn with_duration(start: f32, end: f32, duration: f32, acceleration: f32) -> Self {
+        let distance = end - start;
+        let length = libm::fabsf(distance);
+
+        if length == 0.0 || duration <= 0.0 {
+            return Self {
+                start,
+                distance,
+                acceleration,
+                peak_velocity: 0.0,
+                accel_time: 0.0,
+                cruise_time: duration.max(0.0),
+            };
+        }
+
+        // length = v * (duration - v / a), solved for the smaller v
+        let a = acceleration;
+        let discriminant = (a * duration * a * duration - 4.0 * a * length).max(0.0);
+        let peak_velocity = (a * duration - libm::sqrtf(discriminant)) / 2.0;
+        let accel_time = peak_velocity / a;
+        let cruise_time = (duration - 2.0 * accel_time).max(0.0);
+
+        Self {
+            start,
+            distance,
+            acceleration,
+            peak_velocity,
+            accel_time,
+            cruise_time,
+        }
+    }
+
+    /// Total time of the move in seconds
+    pub fn duration(&self) -> f32 {
+        2.0 * self.accel_time + self.cruise_time
+    }
+
+    pub fn end(&self) -> f32 {
+        self.start + self.distance
+    }
+
+    /// Position `t` seconds after the start of the move
+    pub fn position_at(&self, t: f32) -> f32 {
+        let length = libm::fabsf(self.distance);
+        let ta = self.accel_time;
+        let tc = self.cruise_time;
+
+        let travelled = if t <= 0.0 {
+            0.0
+        } else if t < ta {
+            0.5 * self.acceleration * t * t
+        } else if t < ta + tc {
+            0.5 * self.acceleration * ta * ta + self.peak_velocity * (t - ta)
+        } else if t < self.duration() {
+            let remaining = self.duration() - t;
+            length - 0.5 * self.acceleration * remaining * remaining
+        } else {
+            length
+        };
+
+        let travelled = travelled.min(length);
+        if self.distance < 0.0 {
+            self.start - travelled
+        } else {
+            self.start + travelled
+        }
+    }
+}
+
+/// Drives `N` servos through synchronised moves
+///
+/// Call [`MotionController::update`] at a fixed rate, ideally from a timer
+/// interrupt at the 50 Hz servo frame rate, to step the profiles.
+pub struct MotionController<C, const N: usize> {
+    servos: [Servo<C>; N],
+    limits: [MotionLimits; N],
+    profiles: [Option<TrapezoidProfile>; N],
+    elapsed: f32,
+}
+
+impl<C: SetDutyCycle, const N: usize> MotionController<C, N> {
+    pub fn new(servos: [Servo<C>; N], limits: [MotionLimits; N]) -> Self {
+        const { assert!(N <= MAX_SERVOS) };
+        Self {
+            servos,
+            limits,
+            profiles: [None; N],
+            elapsed: 0.0,
+        }
+    }
+
+    /// Starts a coordinated move so every joint arrives at its target at
+    /// the same time.
+    ///
+    /// Joints start from rest at their current angle, so planning a new move
+    /// while one is running makes the joints jump in speed. Wait for
+    /// [`MotionController::is_moving`] to return `false` first.
+    ///
+    /// Returns the duration of the move in seconds.
+    pub fn move_to(&mut self, targets: [f32; N]) -> f32 {
+        let mut duration: f32 = 0.0;
+        for (i, servo) in self.servos.iter().enumerate() {
+            let target = servo.config().clamp_angle(targets[i]);
+            let fastest = TrapezoidProfile::fastest(servo.angle(), target, self.limits[i]);
+            duration = duration.max(fastest.duration());
+        }
+
+        for (i, servo) in self.servos.iter().enumerate() {
+            let target = servo.config().clamp_angle(targets[i]);
+            self.profiles[i] = Some(TrapezoidProfile::with_duration(
+                servo.angle(),
+                target,
+                duration,
+                self.limits[i].max_acceleration,
+            ));
+        }
+        self.elapsed = 0.0;
+
+        duration
+    }
+
+    /// Advances all profiles by `dt` seconds and updates the servo outputs.
+    pub fn update(&mut self, dt: f32) {
+        if !self.is_moving() {
+            return;
+        }
+
+        self.elapsed += dt;
+        for (servo, profile) in self.servos.iter_mut().zip(self.profiles.iter_mut()) {
+            if let Some(p) = profile {
+                servo.set_angle(p.position_at(self.elapsed));
+                if self.elapsed >= p.duration() {
+                    *profile = None;
+                }
+            }
+        }
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.profiles.iter().any(Option::is_some)
+    }
+
+    /// Stops all joints where they are.
+    pub fn stop(&mut self) {
+        self.profiles = [None; N];
+    }
+
+    pub fn servo(&self, index: usize) -> &Servo<C> {
+        &self.servos[index]
+    }
+
+    pub fn servo_mut(&mut self, index: usize) -> &mut Servo<C> {
+        &mut self.servos[index]
+    }
+
+    pub fn set_limits(&mut self, index: usize, limits: MotionLimits) {
+        self.limits[index] = limits;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use embedded_hal::pwm::ErrorType;
+
+    use super::*;
+    use crate::{PwmSettings, ServoConfig, SERVO_FREQ_HZ};
+
+    const LIMITS: MotionLimits = MotionLimits {
+        max_velocity: 90.0,
+        max_acceleration: 180.0,
+    };
+
+    /// PWM channel that goes nowhere
+    struct Channel;
+
+    impl ErrorType for Channel {
+        type Error = Infallible;
+    }
+
+    impl SetDutyCycle for Channel {
+        fn max_duty_cycle(&self) -> u16 {
+            u16::MAX
+        }
+
+        fn set_duty_cycle(&mut self, _duty: u16) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    fn servo() -> Servo<Channel> {
+        let pwm = PwmSettings::for_frequency(150_000_000, SERVO_FREQ_HZ);
+        Servo::new(Channel, ServoConfig::default(), pwm)
+    }
+
+    fn assert_near(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn short_moves_are_triangular() {
+        // Reaching 90°/s and stopping again takes 45°
+        let profile = TrapezoidProfile::fastest(0.0, 20.0, LIMITS);
+        let duration = 2.0 * libm::sqrtf(20.0 / 180.0);
+        assert_near(profile.duration(), duration, 1e-5);
+        assert_near(profile.position_at(duration / 2.0), 10.0, 1e-4);
+        assert_eq!(profile.position_at(duration), 20.0);
+    }
+
+    #[test]
+    fn long_moves_cruise_at_max_velocity() {
+        let profile = TrapezoidProfile::fastest(0.0, 135.0, LIMITS);
+        // 0.5 s each way to ramp, 1 s at 90°/s in between
+        assert_near(profile.duration(), 2.0, 1e-5);
+        assert_near(profile.position_at(0.5), 22.5, 1e-4);
+        assert_near(profile.position_at(1.0), 67.5, 1e-4);
+        assert_near(profile.position_at(1.5), 112.5, 1e-4);
+        assert_eq!(profile.position_at(3.0), 135.0);
+
+        let back = TrapezoidProfile::fastest(135.0, 0.0, LIMITS);
+        assert_near(back.duration(), 2.0, 1e-5);
+        assert_near(back.position_at(0.5), 112.5, 1e-4);
+        assert_eq!(back.position_at(2.0), 0.0);
+    }
+
+    #[test]
+    fn stretched_moves_take_the_given_time() {
+        let profile = TrapezoidProfile::with_duration(10.0, 55.0, 2.0, 180.0);
+        assert_near(profile.duration(), 2.0, 1e-5);
+        assert_near(profile.position_at(1.0), 32.5, 1e-4);
+        assert_eq!(profile.position_at(2.0), 55.0);
+
+        let mut last = 10.0;
+        for step in 0..=40 {
+            let position = profile.position_at(step as f32 * 0.05);
+            assert!(position >= last, "went back at step {step}");
+            last = position;
+        }
+    }
+
+    #[test]
+    fn joints_finish_together() {
+        let mut arm = MotionController::new(
+            [servo(), servo()],
+            [
+                LIMITS,
+                MotionLimits {
+                    max_velocity: 30.0,
+                    max_acceleration: 60.0,
+                },
+            ],
+        );
+        // Both start at 90°. The second joint is slower but has less to do.
+        let duration = arm.move_to([180.0, 50.0]);
+        assert!(duration > TrapezoidProfile::fastest(90.0, 180.0, LIMITS).duration());
+
+        // Profiles are symmetric, so halfway in time is halfway there
+        let dt = duration / 50.0;
+        for _ in 0..25 {
+            arm.update(dt);
+        }
+        assert_near(arm.servo(0).angle(), 135.0, 0.5);
+        assert_near(arm.servo(1).angle(), 70.0, 0.5);
+
+        let mut steps = 0;
+        while arm.is_moving() && steps < 100 {
+            arm.update(dt);
+            steps += 1;
+        }
+        assert!(!arm.is_moving());
+        assert!(steps <= 26, "took {steps} more steps");
+        assert_near(arm.servo(0).angle(), 180.0, 0.2);
+        assert_near(arm.servo(1).angle(), 50.0, 0.2);
+    }
+
+    #[test]
+    fn zero_distance_moves_finish_at_once() {
+        let profile = TrapezoidProfile::fastest(30.0, 30.0, LIMITS);
+        assert_eq!(profile.duration(), 0.0);
+        assert_eq!(profile.position_at(1.0), 30.0);
+
+        let mut arm = MotionController::new([servo()], [LIMITS]);
+        let start = arm.servo(0).angle();
+        assert_eq!(arm.move_to([start]), 0.0);
+        arm.update(0.02);
+        assert!(!arm.is_moving());
+        assert_eq!(arm.servo(0).angle(), start);
+    }
+}
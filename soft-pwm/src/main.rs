@@ -0,0 +1,165 @@
+//! # Software PWM Example
+//!
+//! Dims four LEDs on GPIO 10 to 13 without using the PWM peripheral. A timer
+//! alarm interrupt steps a shared counter and switches each pin on and off,
+//! so any free GPIO can be used even when its hardware PWM slice is already
+//! taken.
+//!
+//! The LEDs "breathe" one after the other.
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use embedded_hal::delay::DelayNs;
+use hal::block::ImageDef;
+use hal::fugit::ExtU32;
+use hal::gpio::{DynPinId, FunctionSioOutput, Pin, PullDown};
+use hal::pac::interrupt;
+use hal::timer::{Alarm, Alarm0, CopyableTimer0};
+use panic_halt as _;
+use rp235x_hal as hal;
+use soft_pwm::{SoftPwm, SoftPwmConfig};
+
+mod soft_pwm;
+
+/// Tell the Boot ROM about our application
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
+
+/// External high-speed crystal on the Raspberry Pi Pico 2 board is 12 MHz.
+/// Adjust if your board has a different frequency
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Number of software PWM channels
+const CHANNELS: usize = 4;
+
+/// 100 brightness steps at 100 Hz, so the interrupt fires every 100 µs
+const PWM_CONFIG: SoftPwmConfig = SoftPwmConfig {
+    resolution: 100,
+    frequency_hz: 100,
+};
+
+const TICK_US: u32 = match PWM_CONFIG.tick_period_us() {
+    Some(tick_us) => tick_us,
+    None => panic!("PWM_CONFIG ticks faster than once per µs or not at all"),
+};
+
+type LedPin = Pin<DynPinId, FunctionSioOutput, PullDown>;
+type PwmAndAlarm = (SoftPwm<LedPin, CHANNELS>, Alarm0<CopyableTimer0>);
+
+/// Shared between the main loop (which sets the duty) and the interrupt
+/// (which drives the pins)
+static SOFT_PWM: Mutex<RefCell<Option<PwmAndAlarm>>> = Mutex::new(RefCell::new(None));
+
+#[hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // The single-cycle I/O block controls our GPIO pins
+    let sio = hal::Sio::new(pac.SIO);
+
+    // Set the pins up according to their function on this particular board
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let mut timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    // Erase the pin numbers from the types so they fit in one array
+    let leds: [LedPin; CHANNELS] = [
+        pins.gpio10.into_push_pull_output().into_dyn_pin(),
+        pins.gpio11.into_push_pull_output().into_dyn_pin(),
+        pins.gpio12.into_push_pull_output().into_dyn_pin(),
+        pins.gpio13.into_push_pull_output().into_dyn_pin(),
+    ];
+    let soft_pwm = SoftPwm::new(leds, PWM_CONFIG);
+
+    let mut alarm = timer.alarm_0().unwrap();
+    alarm.schedule(TICK_US.micros()).unwrap();
+    alarm.enable_interrupt();
+
+    critical_section::with(|cs| {
+        SOFT_PWM.borrow(cs).replace(Some((soft_pwm, alarm)));
+    });
+
+    // Safety: the interrupt handler only touches state behind the mutex
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(hal::pac::Interrupt::TIMER0_IRQ_0);
+    }
+
+    let resolution = PWM_CONFIG.resolution as u32;
+    // One period of the triangle wave: up the ramp and back down
+    let period = 2 * resolution;
+    let mut step: u32 = 0;
+    loop {
+        critical_section::with(|cs| {
+            if let Some((soft_pwm, _)) = SOFT_PWM.borrow_ref_mut(cs).as_mut() {
+                for channel in 0..CHANNELS {
+                    // Triangle wave, each channel a quarter period behind
+                    // the last
+                    let offset = channel as u32 * period / 4;
+                    let phase = step.wrapping_add(offset) % period;
+                    let duty = if phase < resolution {
+                        phase
+                    } else {
+                        period - phase
+                    };
+                    soft_pwm.set_duty(channel, duty as u16);
+                }
+            }
+        });
+
+        // Kept within one period, so the wave has no jump when it wraps
+        step = (step + 1) % period;
+        timer.delay_ms(10);
+    }
+}
+
+#[interrupt]
+fn TIMER0_IRQ_0() {
+    critical_section::with(|cs| {
+        if let Some((soft_pwm, alarm)) = SOFT_PWM.borrow_ref_mut(cs).as_mut() {
+            alarm.clear_interrupt();
+            let _ = alarm.schedule(TICK_US.micros());
+            soft_pwm.tick();
+        }
+    });
+}
+
+// Program metadata for `picotool info`.
+// This isn't needed, but it's recomended to have these minimal entries.
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"Software PWM"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
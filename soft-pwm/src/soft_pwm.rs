@@ -0,0 +1,116 @@
+use embedded_hal::digital::OutputPin;
+
+/// Settings shared by all channels of a [`SoftPwm`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoftPwmConfig {
+    /// Number of brightness steps. A duty of `resolution` is fully on.
+    pub resolution: u16,
+    /// How often each channel goes through one on/off cycle
+    pub frequency_hz: u32,
+}
+
+impl SoftPwmConfig {
+    /// Time between two calls to [`SoftPwm::tick`], in microseconds
+    ///
+    /// Every PWM period is split into `resolution` ticks, so 100 Hz with 100
+    /// steps needs a tick every 100 µs. Keep the tick well above ~20 µs or
+    /// the interrupt will eat most of the CPU.
+    ///
+    /// Returns `None` if the frequency or resolution is 0, or if they ask
+    /// for ticks less than 1 µs apart.
+    pub const fn tick_period_us(&self) -> Option<u32> {
+        let Some(ticks_per_second) = self.frequency_hz.checked_mul(self.resolution as u32) else {
+            return None;
+        };
+        if ticks_per_second == 0 || ticks_per_second > 1_000_000 {
+            return None;
+        }
+        Some(1_000_000 / ticks_per_second)
+    }
+}
+
+impl Default for SoftPwmConfig {
+    fn default() -> Self {
+        Self {
+            resolution: 100,
+            frequency_hz: 100,
+        }
+    }
+}
+
+/// PWM on plain GPIO outputs, driven by a periodic tick
+///
+/// Each channel goes high at the start of the period and low once the tick
+/// counter reaches its duty, so all channels share one counter and one
+/// timer interrupt.
+pub struct SoftPwm<P, const N: usize> {
+    pins: [P; N],
+    duty: [u16; N],
+    /// Duty values latched at the start of the current period
+    active: [u16; N],
+    config: SoftPwmConfig,
+    counter: u16,
+}
+
+impl<P: OutputPin, const N: usize> SoftPwm<P, N> {
+    /// Takes ownership of the pins and switches them all off.
+    pub fn new(mut pins: [P; N], config: SoftPwmConfig) -> Self {
+        for pin in pins.iter_mut() {
+            let _ = pin.set_low();
+        }
+        Self {
+            pins,
+            duty: [0; N],
+            active: [0; N],
+            config,
+            counter: 0,
+        }
+    }
+
+    pub fn config(&self) -> SoftPwmConfig {
+        self.config
+    }
+
+    /// Sets a channel's duty, from `0` (off) to `resolution` (fully on).
+    ///
+    /// The new value takes effect at the start of the next period.
+    pub fn set_duty(&mut self, channel: usize, duty: u16) {
+        self.duty[channel] = duty.min(self.config.resolution);
+    }
+
+    pub fn duty(&self, channel: usize) -> u16 {
+        self.duty[channel]
+    }
+
+    /// Sets a channel's duty as a percentage of `resolution`.
+    pub fn set_duty_percent(&mut self, channel: usize, percent: u8) {
+        let duty = self.config.resolution as u32 * percent.min(100) as u32 / 100;
+        self.set_duty(channel, duty as u16);
+    }
+
+    /// Advances the counter by one step and updates the pins.
+    ///
+    /// Call this every [`SoftPwmConfig::tick_period_us`], normally from a
+    /// timer interrupt.
+    pub fn tick(&mut self) {
+        if self.counter == 0 {
+            self.active = self.duty;
+            for (pin, &duty) in self.pins.iter_mut().zip(self.active.iter()) {
+                if duty > 0 {
+                    let _ = pin.set_high();
+                }
+            }
+        }
+
+        for (pin, &duty) in self.pins.iter_mut().zip(self.active.iter()) {
+            if duty == self.counter && duty < self.config.resolution {
+                let _ = pin.set_low();
+            }
+        }
+
+        self.counter += 1;
+        if self.counter >= self.config.resolution {
+            self.counter = 0;
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! # 28BYJ-48 Stepper Example
+//!
+//! Half-steps a 28BYJ-48 through its ULN2003 driver board. The motor turns
+//! one full revolution each way, ramping up and down, and its coils are
+//! switched off while it waits in between.
+//!
+//! Connect IN1 to IN4 of the driver board to GPIO 10 to 13, in that order.
+//! The motor needs 5 V, so power the board from VBUS rather than 3V3.
+
+#![no_std]
+#![no_main]
+
+use embedded_hal::delay::DelayNs;
+use hal::pio::PIOExt;
+use hal::Clock;
+use panic_halt as _;
+use rp235x_hal as hal;
+use stepper::ramp::RampConfig;
+use stepper::Stepper;
+
+/// Tell the Boot ROM about our application
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
+
+/// External high-speed crystal on the Raspberry Pi Pico 2 board is 12 MHz.
+/// Adjust if your board has a different frequency
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Half-steps per turn of the output shaft, through the 1:64 gearbox
+const STEPS_PER_REV: i32 = 4096;
+
+/// The 28BYJ-48 loses steps above roughly 1000 half-steps per second
+const RAMP: RampConfig = RampConfig {
+    start_speed: 200.0,
+    max_speed: 900.0,
+    acceleration: 800.0,
+};
+
+#[hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // The single-cycle I/O block controls our GPIO pins
+    let sio = hal::Sio::new(pac.SIO);
+
+    // Set the pins up according to their function on this particular board
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let mut timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    // The four coil outputs have to be consecutive for the PIO program
+    let in1 = pins.gpio10.into_function::<hal::gpio::FunctionPio0>();
+    let _in2 = pins.gpio11.into_function::<hal::gpio::FunctionPio0>();
+    let _in3 = pins.gpio12.into_function::<hal::gpio::FunctionPio0>();
+    let _in4 = pins.gpio13.into_function::<hal::gpio::FunctionPio0>();
+
+    let (mut pio, sm0, _, _, _) = pac.PIO0.split(&mut pac.RESETS);
+    let mut stepper = Stepper::new_half_step(
+        &mut pio,
+        sm0,
+        in1.id().num,
+        clocks.system_clock.freq().to_Hz(),
+        RAMP,
+    )
+    .unwrap();
+
+    loop {
+        for target in [STEPS_PER_REV, 0] {
+            stepper.run_to(target);
+            stepper.release_coils();
+            timer.delay_ms(1000);
+        }
+    }
+}
+
+/// Program metadata for `picotool info`
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"28BYJ-48 Stepper Example"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
@@ -0,0 +1,351 @@
+//! # Stepper Driver
+//!
+//! Drives a stepper motor from a PIO state machine. The CPU works out the
+//! time between steps and queues them in the state machine's FIFO, and the
+//! PIO produces the pin changes on its own clock, so the step timing doesn't
+//! jitter with interrupts or other work.
+//!
+//! Two kinds of hardware are supported:
+//!
+//! - [`StepDir`] for driver boards with STEP and DIR inputs, like the A4988
+//!   and DRV8825
+//! - [`HalfStep`] for unipolar motors switched directly through a ULN2003,
+//!   like the 28BYJ-48
+//!
+//! The state machine runs at 1 MHz, so step times are whole microseconds.
+
+#![no_std]
+
+use embedded_hal::digital::InputPin;
+use rp235x_hal::pac;
+use rp235x_hal::pio::{
+    Buffers, InstallError, PIOBuilder, PIOExt, PinDir, Running, ShiftDirection, StateMachine,
+    StateMachineIndex, Tx, UninitStateMachine, PIO,
+};
+
+pub mod ramp;
+
+use ramp::{interval_us, Ramp, RampConfig};
+
+/// Clock of the stepping state machine
+pub const PIO_CLOCK_HZ: u32 = 1_000_000;
+
+/// Integer divider that brings the system clock down to [`PIO_CLOCK_HZ`]
+pub const fn pio_clock_divisor(sys_clk_hz: u32) -> u16 {
+    (sys_clk_hz / PIO_CLOCK_HZ) as u16
+}
+
+/// How one step is turned into a FIFO word for the state machine
+pub trait Drive {
+    /// PIO cycles each step takes on top of its delay loop
+    const OVERHEAD_CYCLES: u32;
+
+    /// Encodes one step in the given direction, followed by a delay loop of
+    /// `delay` cycles.
+    fn step_word(&mut self, forward: bool, delay: u32) -> u32;
+}
+
+/// STEP/DIR driver boards
+///
+/// Each FIFO word holds the DIR level in bit 0 and the delay above it. The
+/// program sets DIR, then sends a 5 µs STEP pulse, which covers the timing
+/// needs of both the A4988 and the DRV8825.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StepDir;
+
+impl Drive for StepDir {
+    const OVERHEAD_CYCLES: u32 = 9;
+
+    fn step_word(&mut self, forward: bool, delay: u32) -> u32 {
+        (delay << 1) | forward as u32
+    }
+}
+
+/// Coil patterns for half-stepping, IN1 in bit 0 to IN4 in bit 3
+const HALF_STEP_SEQUENCE: [u8; 8] = [
+    0b0001, 0b0011, 0b0010, 0b0110, 0b0100, 0b1100, 0b1000, 0b1001,
+];
+
+/// Four coil outputs driven in the half-step sequence
+///
+/// Each FIFO word holds the coil pattern in bits 0 to 3 and the delay above
+/// it. One step here is a half-step, 4096 of which turn the 28BYJ-48's
+/// output shaft once.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HalfStep {
+    phase: usize,
+}
+
+impl Drive for HalfStep {
+    const OVERHEAD_CYCLES: u32 = 4;
+
+    fn step_word(&mut self, forward: bool, delay: u32) -> u32 {
+        let len = HALF_STEP_SEQUENCE.len();
+        self.phase = if forward {
+            (self.phase + 1) % len
+        } else {
+            (self.phase + len - 1) % len
+        };
+        (delay << 4) | HALF_STEP_SEQUENCE[self.phase] as u32
+    }
+}
+
+/// Why homing failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HomeError<E> {
+    /// Reading the limit switch failed
+    Pin(E),
+    /// The switch didn't close within the allowed travel
+    SwitchNotFound,
+}
+
+/// A stepper motor on one PIO state machine
+///
+/// Steps are counted as they are queued, so [`Stepper::position`] runs a few
+/// steps ahead of the shaft while a move is in progress. Call
+/// [`Stepper::poll`] often enough to keep the FIFO from running dry, or use
+/// the blocking [`Stepper::run_to`].
+pub struct Stepper<P: PIOExt, SM: StateMachineIndex, D> {
+    _sm: StateMachine<(P, SM), Running>,
+    tx: Tx<(P, SM)>,
+    drive: D,
+    config: RampConfig,
+    ramp: Ramp,
+    forward: bool,
+    position: i32,
+}
+
+impl<P: PIOExt, SM: StateMachineIndex> Stepper<P, SM, StepDir> {
+    /// Sets up a STEP/DIR driver. Both pins must already be switched to
+    /// the PIO function.
+    pub fn new_step_dir(
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        step_pin: u8,
+        dir_pin: u8,
+        sys_clk_hz: u32,
+        config: RampConfig,
+    ) -> Result<Self, InstallError> {
+        let program = pio_proc::pio_asm!(
+            ".side_set 1",
+            ".wrap_target",
+            "    pull block      side 0",
+            "    out pins, 1     side 0", // DIR
+            "    mov x, osr      side 0",
+            "    nop             side 1 [4]", // STEP pulse
+            "delay:",
+            "    jmp x-- delay   side 0",
+            ".wrap",
+        );
+        let installed = pio.install(&program.program)?;
+
+        let (mut sm, _, tx) = PIOBuilder::from_installed_program(installed)
+            .side_set_pin_base(step_pin)
+            .out_pins(dir_pin, 1)
+            .out_shift_direction(ShiftDirection::Right)
+            .buffers(Buffers::OnlyTx)
+            .clock_divisor_fixed_point(pio_clock_divisor(sys_clk_hz), 0)
+            .build(sm);
+        sm.set_pindirs([(step_pin, PinDir::Output), (dir_pin, PinDir::Output)]);
+
+        Ok(Self::from_parts(sm.start(), tx, StepDir, config))
+    }
+}
+
+impl<P: PIOExt, SM: StateMachineIndex> Stepper<P, SM, HalfStep> {
+    /// Sets up four coil outputs on consecutive pins starting at
+    /// `first_pin`, which is IN1. The pins must already be switched to the
+    /// PIO function.
+    pub fn new_half_step(
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        first_pin: u8,
+        sys_clk_hz: u32,
+        config: RampConfig,
+    ) -> Result<Self, InstallError> {
+        let program = pio_proc::pio_asm!(
+            ".wrap_target",
+            "    pull block",
+            "    out pins, 4", // Coil pattern
+            "    mov x, osr",
+            "delay:",
+            "    jmp x-- delay",
+            ".wrap",
+        );
+        let installed = pio.install(&program.program)?;
+
+        let (mut sm, _, tx) = PIOBuilder::from_installed_program(installed)
+            .out_pins(first_pin, 4)
+            .out_shift_direction(ShiftDirection::Right)
+            .buffers(Buffers::OnlyTx)
+            .clock_divisor_fixed_point(pio_clock_divisor(sys_clk_hz), 0)
+            .build(sm);
+        sm.set_pindirs((first_pin..first_pin + 4).map(|pin| (pin, PinDir::Output)));
+
+        Ok(Self::from_parts(
+            sm.start(),
+            tx,
+            HalfStep::default(),
+            config,
+        ))
+    }
+
+    /// Switches all coils off once the queued steps are done, so the motor
+    /// and the ULN2003 don't heat up while standing still.
+    ///
+    /// The motor no longer holds its position, so a load on the shaft can
+    /// turn it without the position noticing.
+    pub fn release_coils(&mut self) {
+        while !self.tx.write(0) {}
+    }
+}
+
+impl<P: PIOExt, SM: StateMachineIndex, D: Drive> Stepper<P, SM, D> {
+    fn from_parts(
+        sm: StateMachine<(P, SM), Running>,
+        tx: Tx<(P, SM)>,
+        drive: D,
+        config: RampConfig,
+    ) -> Self {
+        Self {
+            _sm: sm,
+            tx,
+            drive,
+            config,
+            ramp: Ramp::idle(config),
+            forward: true,
+            position: 0,
+        }
+    }
+
+    /// Absolute position in steps
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Redefines the current position, for example to zero after homing.
+    pub fn set_position(&mut self, position: i32) {
+        self.position = position;
+    }
+
+    pub fn config(&self) -> RampConfig {
+        self.config
+    }
+
+    /// Changes the speed limits used from the next move on.
+    pub fn set_config(&mut self, config: RampConfig) {
+        self.config = config;
+    }
+
+    /// Starts a move to an absolute position.
+    ///
+    /// The move starts from `start_speed`, so wait for the previous one to
+    /// finish, or [`Stepper::stop`] it, before starting the next.
+    pub fn move_to(&mut self, target: i32) {
+        self.move_by(target - self.position);
+    }
+
+    /// Starts a move relative to the current position.
+    pub fn move_by(&mut self, steps: i32) {
+        self.forward = steps >= 0;
+        self.ramp = Ramp::new(steps.unsigned_abs(), self.config);
+    }
+
+    /// Decelerates to a stop as quickly as the acceleration allows.
+    pub fn stop(&mut self) {
+        self.ramp.stop();
+    }
+
+    /// Queues as many steps as fit in the FIFO. Returns `true` while the
+    /// motor is still moving.
+    pub fn poll(&mut self) -> bool {
+        while !self.ramp.is_done() && !self.tx.is_full() {
+            let interval_us = self.ramp.next().unwrap_or(0);
+            self.queue_step(interval_us);
+        }
+        self.is_moving()
+    }
+
+    /// Whether steps are queued or being output
+    pub fn is_moving(&self) -> bool {
+        // The program stalls on `pull` once it has nothing left to do
+        !self.ramp.is_done() || !self.tx.is_empty() || !self.tx_stalled()
+    }
+
+    /// Moves to an absolute position and waits until it gets there.
+    pub fn run_to(&mut self, target: i32) {
+        self.move_to(target);
+        while self.poll() {}
+    }
+
+    /// Finds the limit switch and makes its position zero.
+    ///
+    /// Steps towards the switch at `speed` steps per second until it closes
+    /// (reads low, for a switch to ground with a pull-up), then backs off at
+    /// a quarter of the speed until it opens again. Gives up after
+    /// `max_steps` without reaching the switch. Speeds below
+    /// [`ramp::MIN_SPEED`] are raised to it.
+    pub fn home<L: InputPin>(
+        &mut self,
+        limit: &mut L,
+        forward: bool,
+        speed: f32,
+        max_steps: u32,
+    ) -> Result<(), HomeError<L::Error>> {
+        self.ramp = Ramp::idle(self.config);
+        let interval_us = interval_us(speed);
+
+        let mut steps = 0;
+        while limit.is_high().map_err(HomeError::Pin)? {
+            if steps == max_steps {
+                return Err(HomeError::SwitchNotFound);
+            }
+            self.forward = forward;
+            self.step_and_wait(interval_us);
+            steps += 1;
+        }
+
+        while limit.is_low().map_err(HomeError::Pin)? {
+            self.forward = !forward;
+            self.step_and_wait(interval_us.saturating_mul(4));
+        }
+
+        self.position = 0;
+        Ok(())
+    }
+
+    fn step_and_wait(&mut self, interval_us: u32) {
+        while self.tx.is_full() {}
+        self.queue_step(interval_us);
+        while self.is_moving() {}
+    }
+
+    fn queue_step(&mut self, interval_us: u32) {
+        let delay = interval_us.saturating_sub(D::OVERHEAD_CYCLES).max(1);
+        let word = self.drive.step_word(self.forward, delay);
+        self.tx.write(word);
+        // Only after the write: the state machine can't stall again until
+        // it has taken this word and worked through its delay
+        self.clear_tx_stall();
+        self.position += if self.forward { 1 } else { -1 };
+    }
+
+    /// The FDEBUG register of this state machine's PIO block
+    fn fdebug() -> &'static pac::pio0::FDEBUG {
+        let block = [pac::PIO0::ptr(), pac::PIO1::ptr(), pac::PIO2::ptr()][P::id()];
+        // SAFETY: FDEBUG is only written with this state machine's bit set,
+        // which leaves the flags of the others alone
+        unsafe { (*block).fdebug() }
+    }
+
+    /// Whether the state machine has stalled on an empty FIFO since the
+    /// last step was queued. The flag is sticky, unlike
+    /// `StateMachine::stalled`, which only covers forced instructions.
+    fn tx_stalled(&self) -> bool {
+        Self::fdebug().read().txstall().bits() & (1 << SM::id()) != 0
+    }
+
+    fn clear_tx_stall(&mut self) {
+        Self::fdebug().write(|w| unsafe { w.txstall().bits(1 << SM::id()) });
+    }
+}
@@ -0,0 +1,148 @@
+//! # Stepper Example
+//!
+//! Drives a stepper through an A4988 or DRV8825 driver board. At start-up
+//! the motor is homed against a limit switch, then it moves between a few
+//! positions with acceleration and deceleration ramps.
+//!
+//! Wiring:
+//!
+//! - STEP on GPIO 2, DIR on GPIO 3
+//! - Limit switch between GPIO 4 and GND
+//!
+//! Tie the driver's ENABLE input low and SLEEP and RESET high. With no
+//! microstepping, a 1.8° motor takes 200 steps per revolution.
+
+#![no_std]
+#![no_main]
+
+// Ensure we halt the program on panic (if we don't mention this crate it won't
+// be linked)
+use panic_halt as _;
+
+// Alias for our HAL crate
+use rp235x_hal as hal;
+
+// Some things we need
+use embedded_hal::delay::DelayNs;
+use hal::pio::PIOExt;
+use hal::Clock;
+use stepper::ramp::RampConfig;
+use stepper::Stepper;
+
+/// Tell the Boot ROM about our application
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
+
+/// External high-speed crystal on the Raspberry Pi Pico 2 board is 12 MHz.
+/// Adjust if your board has a different frequency
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+const STEPS_PER_REV: i32 = 200;
+
+const RAMP: RampConfig = RampConfig {
+    start_speed: 100.0,
+    max_speed: 800.0,
+    acceleration: 1600.0,
+};
+
+/// Slow enough to stop right at the switch without losing steps
+const HOMING_SPEED: f32 = 100.0;
+
+/// Give up homing after this much travel
+const HOMING_MAX_STEPS: u32 = 10 * STEPS_PER_REV as u32;
+
+/// Positions in steps from the limit switch, visited in order
+const POSITIONS: [i32; 4] = [
+    STEPS_PER_REV,
+    STEPS_PER_REV / 4,
+    STEPS_PER_REV / 2,
+    4 * STEPS_PER_REV,
+];
+
+#[hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // The single-cycle I/O block controls our GPIO pins
+    let sio = hal::Sio::new(pac.SIO);
+
+    // Set the pins up according to their function on this particular board
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let mut timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    // Hand the STEP and DIR pins over to PIO0
+    let step_pin = pins.gpio2.into_function::<hal::gpio::FunctionPio0>();
+    let dir_pin = pins.gpio3.into_function::<hal::gpio::FunctionPio0>();
+    let mut limit = pins.gpio4.into_pull_up_input();
+
+    let (mut pio, sm0, _, _, _) = pac.PIO0.split(&mut pac.RESETS);
+    let mut stepper = Stepper::new_step_dir(
+        &mut pio,
+        sm0,
+        step_pin.id().num,
+        dir_pin.id().num,
+        clocks.system_clock.freq().to_Hz(),
+        RAMP,
+    )
+    .unwrap();
+
+    // Home towards the switch, which sits at the negative end
+    stepper
+        .home(&mut limit, false, HOMING_SPEED, HOMING_MAX_STEPS)
+        .unwrap();
+    timer.delay_ms(500);
+
+    loop {
+        for position in POSITIONS {
+            stepper.run_to(position);
+            timer.delay_ms(500);
+        }
+
+        // Head back to the switch but cut the move short half-way. The
+        // motor still slows down instead of stopping dead.
+        stepper.move_to(0);
+        while stepper.poll() {
+            if stepper.position() < 2 * STEPS_PER_REV {
+                stepper.stop();
+            }
+        }
+        timer.delay_ms(1000);
+    }
+}
+
+/// Program metadata for `picotool info`
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"Stepper Example"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
@@ -0,0 +1,127 @@
+//! Step timing for moves that speed up and slow down smoothly.
+//!
+//! A stepper can't jump straight to full speed, it stalls and loses steps.
+//! Each move starts at `start_speed`, accelerates at a constant rate up to
+//! `max_speed` and decelerates at the same rate so the last step is taken
+//! at `start_speed` again. Short moves never reach `max_speed`.
+
+/// Slowest speed a step is timed for, in steps per second. Lower speeds,
+/// including zero, are raised to it so step intervals stay finite.
+pub const MIN_SPEED: f32 = 1.0;
+
+/// Time from one step to the next at `speed` steps per second, in µs
+pub fn interval_us(speed: f32) -> u32 {
+    (1_000_000.0 / speed.max(MIN_SPEED)) as u32
+}
+
+/// Speed limits for one motor, in steps per second
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RampConfig {
+    /// Speed of the first and last step. The motor must be able to start
+    /// at this speed without ramping.
+    pub start_speed: f32,
+    /// Cruise speed
+    pub max_speed: f32,
+    /// Steps per second squared
+    pub acceleration: f32,
+}
+
+impl Default for RampConfig {
+    fn default() -> Self {
+        Self {
+            start_speed: 100.0,
+            max_speed: 1000.0,
+            acceleration: 2000.0,
+        }
+    }
+}
+
+impl RampConfig {
+    /// Number of steps needed to slow from `speed` down to `start_speed`
+    pub fn steps_to_stop(&self, speed: f32) -> u32 {
+        let v0 = self.start_speed;
+        if speed <= v0 {
+            return 0;
+        }
+        libm::ceilf((speed * speed - v0 * v0) / (2.0 * self.acceleration)) as u32
+    }
+}
+
+/// The steps of one move, yielded as the time in µs from each step to the
+/// next
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ramp {
+    config: RampConfig,
+    total: u32,
+    done: u32,
+}
+
+impl Ramp {
+    pub fn new(steps: u32, config: RampConfig) -> Self {
+        Self {
+            config,
+            total: steps,
+            done: 0,
+        }
+    }
+
+    /// A ramp with no steps left
+    pub fn idle(config: RampConfig) -> Self {
+        Self::new(0, config)
+    }
+
+    pub fn remaining(&self) -> u32 {
+        self.total - self.done
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done >= self.total
+    }
+
+    /// Speed of the given step of the move, in steps per second
+    pub fn speed_at(&self, step: u32) -> f32 {
+        let v0 = self.config.start_speed;
+        let a = self.config.acceleration;
+        let after = self.total.saturating_sub(step + 1);
+
+        let accelerating = libm::sqrtf(v0 * v0 + 2.0 * a * step as f32);
+        let decelerating = libm::sqrtf(v0 * v0 + 2.0 * a * after as f32);
+        accelerating.min(decelerating).min(self.config.max_speed)
+    }
+
+    /// Speed of the step taken last, or zero before the first one
+    pub fn current_speed(&self) -> f32 {
+        if self.done == 0 {
+            0.0
+        } else {
+            self.speed_at(self.done - 1)
+        }
+    }
+
+    /// Shortens the move so it decelerates to a stop as soon as possible.
+    pub fn stop(&mut self) {
+        if self.is_done() {
+            return;
+        }
+        let stopping = self.config.steps_to_stop(self.current_speed());
+        self.total = self.total.min(self.done + stopping + 1);
+    }
+}
+
+impl Iterator for Ramp {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.is_done() {
+            return None;
+        }
+        let speed = self.speed_at(self.done);
+        self.done += 1;
+        Some(interval_us(speed))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining() as usize;
+        (remaining, Some(remaining))
+    }
+}
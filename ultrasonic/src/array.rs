@@ -0,0 +1,109 @@
+//! Several sensors sharing the same space.
+//!
+//! Two HC-SR04s fired at once can each pick up the other's burst and report
+//! a distance that isn't there. [`SensorArray`] fires them one at a time in
+//! a fixed order, with a guard interval in between for the previous burst
+//! to die down. Echoes are still timed by every sensor at once, so a slow
+//! echo on one doesn't hold up the next.
+
+use core::convert::Infallible;
+
+use crate::{Distance, Error, SpeedOfSound};
+
+/// Result of one measurement
+pub type Reading = Result<Distance, Error<Infallible>>;
+
+/// A sensor that measures in the background
+pub trait Rangefinder {
+    /// Triggers a measurement without waiting for it.
+    fn start(&mut self);
+
+    /// Returns the result of the running measurement once it is done.
+    fn poll(&mut self) -> Option<Reading>;
+
+    fn set_speed_of_sound(&mut self, speed: SpeedOfSound);
+}
+
+/// Round-robin scheduler for `N` sensors
+pub struct SensorArray<'a, const N: usize> {
+    sensors: [&'a mut dyn Rangefinder; N],
+    readings: [Option<Reading>; N],
+    order: [usize; N],
+    guard_us: u32,
+    /// Position in `order` of the sensor fired next
+    next: usize,
+    next_fire_us: u64,
+}
+
+impl<'a, const N: usize> SensorArray<'a, N> {
+    /// Fires the sensors in index order, waiting `guard_us` after each
+    /// one before firing the next.
+    ///
+    /// The guard should cover the echo time of the longest range any
+    /// sensor can hear, about 25 ms for 4 m. Sensors facing away from each
+    /// other can get away with less.
+    pub fn new(sensors: [&'a mut dyn Rangefinder; N], guard_us: u32) -> Self {
+        // Updates are reported as a u32 bit mask
+        const { assert!(N > 0 && N <= 32) };
+        Self {
+            sensors,
+            readings: [None; N],
+            order: core::array::from_fn(|i| i),
+            guard_us,
+            next: 0,
+            next_fire_us: 0,
+        }
+    }
+
+    /// Changes the firing order. Alternating between sensors on opposite
+    /// sides lets a shorter guard interval do.
+    ///
+    /// Every sensor index should appear once, otherwise some are fired
+    /// more often than others.
+    pub fn set_order(&mut self, order: [usize; N]) {
+        self.order = order.map(|index| index.min(N - 1));
+        self.next = 0;
+    }
+
+    pub fn set_guard_us(&mut self, guard_us: u32) {
+        self.guard_us = guard_us;
+    }
+
+    pub fn set_speed_of_sound(&mut self, speed: SpeedOfSound) {
+        for sensor in self.sensors.iter_mut() {
+            sensor.set_speed_of_sound(speed);
+        }
+    }
+
+    /// Collects finished measurements and fires the next sensor once its
+    /// turn has come. Call it often, with the current time in µs.
+    ///
+    /// Returns the index of each sensor with a new reading, as a bit mask.
+    pub fn poll(&mut self, now_us: u64) -> u32 {
+        let mut updated = 0;
+        for (i, sensor) in self.sensors.iter_mut().enumerate() {
+            if let Some(reading) = sensor.poll() {
+                self.readings[i] = Some(reading);
+                updated |= 1 << i;
+            }
+        }
+
+        if now_us >= self.next_fire_us {
+            self.sensors[self.order[self.next]].start();
+            self.next = (self.next + 1) % N;
+            self.next_fire_us = now_us + self.guard_us as u64;
+        }
+
+        updated
+    }
+
+    /// Latest reading of every sensor, `None` until it has measured once
+    pub fn readings(&self) -> &[Option<Reading>; N] {
+        &self.readings
+    }
+
+    /// Time for every sensor to be fired once
+    pub fn cycle_us(&self) -> u32 {
+        self.guard_us * N as u32
+    }
+}
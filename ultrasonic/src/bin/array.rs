@@ -0,0 +1,217 @@
+//! # Ultrasonic Array Example
+//!
+//! Four HC-SR04s around a rover, fired one after another so they don't hear
+//! each other's bursts. Each sensor has its own PIO0 state machine timing
+//! its echo. A table of the latest distances is printed over USB serial
+//! after every round.
+//!
+//! | Sensor | TRIG    | ECHO    |
+//! |--------|---------|---------|
+//! | Front  | GPIO 2  | GPIO 3  |
+//! | Right  | GPIO 4  | GPIO 5  |
+//! | Back   | GPIO 6  | GPIO 7  |
+//! | Left   | GPIO 8  | GPIO 9  |
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use hal::pio::PIOExt;
+use hal::Clock;
+use heapless::String;
+use panic_halt as _;
+use rp235x_hal as hal;
+use ultrasonic::array::{Rangefinder, SensorArray};
+use ultrasonic::pio_echo::{install_program, PioHcsr04};
+use ultrasonic::{Config, Error};
+use usb_device::{class_prelude::*, prelude::*};
+use usbd_serial::SerialPort;
+
+/// Tell the Boot ROM about our application
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
+
+/// External high-speed crystal on the Raspberry Pi Pico 2 board is 12 MHz.
+/// Adjust if your board has a different frequency
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+const NAMES: [&str; 4] = ["front", "right", "back", "left"];
+
+/// Alternate between opposite sides, which hear each other least
+const FIRING_ORDER: [usize; 4] = [0, 2, 1, 3];
+
+/// Time between firing two sensors
+const GUARD_US: u32 = 15_000;
+
+#[hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // The single-cycle I/O block controls our GPIO pins
+    let sio = hal::Sio::new(pac.SIO);
+
+    // Set the pins up according to their function on this particular board
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    // For USB Serial
+    let usb_bus = UsbBusAllocator::new(hal::usb::UsbBus::new(
+        pac.USB,
+        pac.USB_DPRAM,
+        clocks.usb_clock,
+        true,
+        &mut pac.RESETS,
+    ));
+
+    // Set up the USB Communications Class Device driver
+    let mut serial = SerialPort::new(&usb_bus);
+
+    // Create a USB device with a fake VID and PID
+    let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
+        .strings(&[StringDescriptors::default()
+            .manufacturer("Fake company")
+            .product("Ultrasonic array")
+            .serial_number("TEST")])
+        .unwrap()
+        .device_class(2) // from: https://www.usb.org/defined-class-codes
+        .build();
+
+    let sys_clk_hz = clocks.system_clock.freq().to_Hz();
+    let config = Config::default();
+
+    // One copy of the program runs on all four state machines
+    let (mut pio, sm0, sm1, sm2, sm3) = pac.PIO0.split(&mut pac.RESETS);
+    let program = install_program(&mut pio).unwrap();
+
+    // ECHO pins are pulled down so an unplugged sensor reads as no echo,
+    // not stuck
+    let trig = pins.gpio2.into_function::<hal::gpio::FunctionPio0>();
+    let echo = pins
+        .gpio3
+        .into_pull_down_input()
+        .into_function::<hal::gpio::FunctionPio0>();
+    // Safety: the program is never uninstalled
+    let shared = unsafe { program.share() };
+    let mut front = PioHcsr04::with_program(
+        shared,
+        sm0,
+        trig.id().num,
+        echo.id().num,
+        sys_clk_hz,
+        config,
+    );
+
+    let trig = pins.gpio4.into_function::<hal::gpio::FunctionPio0>();
+    let echo = pins
+        .gpio5
+        .into_pull_down_input()
+        .into_function::<hal::gpio::FunctionPio0>();
+    let shared = unsafe { program.share() };
+    let mut right = PioHcsr04::with_program(
+        shared,
+        sm1,
+        trig.id().num,
+        echo.id().num,
+        sys_clk_hz,
+        config,
+    );
+
+    let trig = pins.gpio6.into_function::<hal::gpio::FunctionPio0>();
+    let echo = pins
+        .gpio7
+        .into_pull_down_input()
+        .into_function::<hal::gpio::FunctionPio0>();
+    let shared = unsafe { program.share() };
+    let mut back = PioHcsr04::with_program(
+        shared,
+        sm2,
+        trig.id().num,
+        echo.id().num,
+        sys_clk_hz,
+        config,
+    );
+
+    let trig = pins.gpio8.into_function::<hal::gpio::FunctionPio0>();
+    let echo = pins
+        .gpio9
+        .into_pull_down_input()
+        .into_function::<hal::gpio::FunctionPio0>();
+    let mut left = PioHcsr04::with_program(
+        program,
+        sm3,
+        trig.id().num,
+        echo.id().num,
+        sys_clk_hz,
+        config,
+    );
+
+    let sensors: [&mut dyn Rangefinder; 4] = [&mut front, &mut right, &mut back, &mut left];
+    let mut array = SensorArray::new(sensors, GUARD_US);
+    array.set_order(FIRING_ORDER);
+
+    let mut seen = 0u32;
+    let mut out: String<128> = String::new();
+    loop {
+        let _ = usb_dev.poll(&mut [&mut serial]);
+
+        seen |= array.poll(timer.get_counter().ticks());
+
+        // Print once every sensor has reported since the last table
+        if seen == 0b1111 {
+            seen = 0;
+            for (name, reading) in NAMES.iter().zip(array.readings()) {
+                out.clear();
+                let _ = match reading {
+                    Some(Ok(distance)) => {
+                        write!(out, "{:>5}: {:6.1} cm\r\n", name, distance.as_cm())
+                    }
+                    Some(Err(Error::OutOfRange)) => write!(out, "{:>5}:  clear\r\n", name),
+                    Some(Err(Error::TooClose)) => write!(out, "{:>5}: too close\r\n", name),
+                    Some(Err(Error::NoEcho)) => write!(out, "{:>5}: no sensor\r\n", name),
+                    Some(Err(_)) => write!(out, "{:>5}: error\r\n", name),
+                    None => write!(out, "{:>5}: -\r\n", name),
+                };
+                let _ = serial.write(out.as_bytes());
+            }
+            let _ = serial.write(b"\r\n");
+        }
+    }
+}
+
+/// Program metadata for `picotool info`
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"Ultrasonic Array Example"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
@@ -0,0 +1,173 @@
+//! # Parking Sensor Example
+//!
+//! Beeps a passive buzzer like a car's parking sensor: silent when the way
+//! is clear, beeping faster and higher as an object gets closer, and a
+//! steady tone when it is very close.
+//!
+//! The HC-SR04 has TRIG on GPIO 17 and ECHO on GPIO 16, timed by PIO0. The
+//! buzzer is on GPIO 15, PWM slice 7 channel B. Nothing in the loop blocks,
+//! so ranging carries on while the buzzer sounds.
+
+#![no_std]
+#![no_main]
+
+use embedded_hal::pwm::SetDutyCycle;
+use filters::{Filter, Median};
+use hal::pio::PIOExt;
+use hal::Clock;
+use panic_halt as _;
+use rp235x_hal as hal;
+use ultrasonic::parking::{ParkingConfig, ParkingSensor, Tone};
+use ultrasonic::pio_echo::PioHcsr04;
+use ultrasonic::{Config, Distance, Error};
+
+/// Tell the Boot ROM about our application
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
+
+/// External high-speed crystal on the Raspberry Pi Pico 2 board is 12 MHz.
+/// Adjust if your board has a different frequency
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Time between measurements, long enough for old echoes to die away
+const MEASURE_INTERVAL_US: u64 = 60_000;
+
+/// Slows the PWM counter enough for audio frequencies to fit in 16 bits
+const PWM_DIV_INT: u8 = 64;
+
+const PARKING_CONFIG: ParkingConfig = ParkingConfig {
+    max_range: Distance::from_cm(100.0),
+    continuous_range: Distance::from_cm(20.0),
+    hysteresis: Distance::from_cm(2.0),
+    slowest_period_ms: 1000,
+    fastest_period_ms: 150,
+    beep_ms: 60,
+    far_pitch_hz: 1000,
+    near_pitch_hz: 2500,
+};
+
+#[hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // The single-cycle I/O block controls our GPIO pins
+    let sio = hal::Sio::new(pac.SIO);
+
+    // Set the pins up according to their function on this particular board
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    let sys_clk_hz = clocks.system_clock.freq().to_Hz();
+
+    // Buzzer
+    let mut pwm_slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
+    let pwm = &mut pwm_slices.pwm7;
+    pwm.set_div_int(PWM_DIV_INT);
+    pwm.enable();
+    pwm.channel_b.output_to(pins.gpio15);
+    pwm.channel_b.set_duty_cycle(0).unwrap();
+
+    // Sensor
+    // Pulled down so an unplugged sensor reads as no echo, not stuck
+    let echo = pins
+        .gpio16
+        .into_pull_down_input()
+        .into_function::<hal::gpio::FunctionPio0>();
+    let trigger = pins.gpio17.into_function::<hal::gpio::FunctionPio0>();
+
+    let (mut pio, sm0, _, _, _) = pac.PIO0.split(&mut pac.RESETS);
+    let mut sensor = PioHcsr04::new(
+        &mut pio,
+        sm0,
+        trigger.id().num,
+        echo.id().num,
+        sys_clk_hz,
+        Config::default(),
+    )
+    .unwrap();
+
+    // Distances in millimetres
+    let mut filter = Median::<f32, 3>::new();
+    let mut parking = ParkingSensor::new(PARKING_CONFIG);
+
+    let mut next_measurement = 0;
+    let mut playing = Tone::Off;
+    loop {
+        let now = timer.get_counter().ticks();
+        if now >= next_measurement {
+            sensor.start();
+            next_measurement = now + MEASURE_INTERVAL_US;
+        }
+
+        if let Some(result) = sensor.poll() {
+            match result {
+                Ok(distance) => {
+                    let mm = filter.update(distance.as_mm());
+                    parking.set_distance(Some(Distance::from_mm(mm)));
+                }
+                // Too close to measure is as close as it gets
+                Err(Error::TooClose) => {
+                    let mm = filter.update(Distance::ZERO.as_mm());
+                    parking.set_distance(Some(Distance::from_mm(mm)));
+                }
+                // Only no echo, or none in range, counts as clear
+                Err(Error::NoEcho | Error::OutOfRange) => parking.set_distance(None),
+                // A stuck echo says nothing about the distance
+                Err(_) => {}
+            }
+        }
+
+        // Only touch the PWM when the tone changes
+        let tone = parking.tone(now);
+        if tone != playing {
+            playing = tone;
+            match tone {
+                Tone::Off => {
+                    pwm.channel_b.set_duty_cycle(0).unwrap();
+                }
+                Tone::On { freq_hz } => {
+                    let top = sys_clk_hz / (PWM_DIV_INT as u32 * freq_hz) - 1;
+                    pwm.set_top(top as u16);
+                    pwm.channel_b.set_duty_cycle_percent(50).unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// Program metadata for `picotool info`
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"Parking Sensor Example"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
@@ -0,0 +1,202 @@
+//! # Ultrasonic Theremin Example
+//!
+//! Play a passive buzzer by waving your hands over two HC-SR04s pointing
+//! up. The right hand sets the pitch, higher the closer it is, and snaps to
+//! the notes of a C major pentatonic scale. The left hand sets the volume,
+//! quieter the closer it is.
+//!
+//! | Sensor | TRIG    | ECHO    |
+//! |--------|---------|---------|
+//! | Pitch  | GPIO 17 | GPIO 16 |
+//! | Volume | GPIO 19 | GPIO 18 |
+//!
+//! The buzzer is on GPIO 15, PWM slice 7 channel B.
+
+#![no_std]
+#![no_main]
+
+use embedded_hal::pwm::SetDutyCycle;
+use filters::{Filter, Median};
+use hal::pio::PIOExt;
+use hal::Clock;
+use panic_halt as _;
+use rp235x_hal as hal;
+use ultrasonic::array::{Rangefinder, SensorArray};
+use ultrasonic::pio_echo::{install_program, PioHcsr04};
+use ultrasonic::theremin::{Theremin, ThereminConfig, Voice, STEP_MS};
+use ultrasonic::{Config, Distance};
+
+/// Tell the Boot ROM about our application
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
+
+/// External high-speed crystal on the Raspberry Pi Pico 2 board is 12 MHz.
+/// Adjust if your board has a different frequency
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Nothing further than this is played, which keeps echoes short
+const MAX_RANGE: Distance = Distance::from_cm(60.0);
+
+/// Time between firing the two sensors. Both point up and away from each
+/// other, so this only has to cover echoes from `MAX_RANGE`.
+const GUARD_US: u32 = 10_000;
+
+/// Slows the PWM counter enough for audio frequencies to fit in 16 bits
+const PWM_DIV_INT: u8 = 64;
+
+/// Loudest duty cycle, a passive buzzer is loudest at a square wave
+const MAX_DUTY: f32 = 0.5;
+
+const PITCH: usize = 0;
+const VOLUME: usize = 1;
+
+#[hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = hal::pac::Peripherals::take().unwrap();
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // The single-cycle I/O block controls our GPIO pins
+    let sio = hal::Sio::new(pac.SIO);
+
+    // Set the pins up according to their function on this particular board
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+
+    let sys_clk_hz = clocks.system_clock.freq().to_Hz();
+
+    // Buzzer
+    let mut pwm_slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
+    let pwm = &mut pwm_slices.pwm7;
+    pwm.set_div_int(PWM_DIV_INT);
+    pwm.enable();
+    pwm.channel_b.output_to(pins.gpio15);
+    pwm.channel_b.set_duty_cycle(0).unwrap();
+
+    // Sensors, both running the same copy of the program
+    let config = Config {
+        max_range: MAX_RANGE,
+        ..Config::default()
+    };
+    let (mut pio, sm0, sm1, _, _) = pac.PIO0.split(&mut pac.RESETS);
+    let program = install_program(&mut pio).unwrap();
+
+    // Pulled down so an unplugged sensor reads as no echo, not stuck
+    let echo = pins
+        .gpio16
+        .into_pull_down_input()
+        .into_function::<hal::gpio::FunctionPio0>();
+    let trig = pins.gpio17.into_function::<hal::gpio::FunctionPio0>();
+    // Safety: the program is never uninstalled
+    let shared = unsafe { program.share() };
+    let mut pitch_sensor = PioHcsr04::with_program(
+        shared,
+        sm0,
+        trig.id().num,
+        echo.id().num,
+        sys_clk_hz,
+        config,
+    );
+
+    let echo = pins
+        .gpio18
+        .into_pull_down_input()
+        .into_function::<hal::gpio::FunctionPio0>();
+    let trig = pins.gpio19.into_function::<hal::gpio::FunctionPio0>();
+    let mut volume_sensor = PioHcsr04::with_program(
+        program,
+        sm1,
+        trig.id().num,
+        echo.id().num,
+        sys_clk_hz,
+        config,
+    );
+
+    let sensors: [&mut dyn Rangefinder; 2] = [&mut pitch_sensor, &mut volume_sensor];
+    let mut array = SensorArray::new(sensors, GUARD_US);
+
+    // Distances in millimetres
+    let mut filters = [Median::<f32, 3>::new(), Median::<f32, 3>::new()];
+    let mut theremin = Theremin::new(ThereminConfig::default());
+
+    let mut top = 0;
+    let mut next_step = 0;
+    loop {
+        let now = timer.get_counter().ticks();
+
+        let updated = array.poll(now);
+        for (i, reading) in array.readings().iter().enumerate() {
+            if updated & (1 << i) == 0 {
+                continue;
+            }
+            // Errors, including nothing in range, mean no hand
+            let distance = (*reading)
+                .and_then(Result::ok)
+                .map(|distance| Distance::from_mm(filters[i].update(distance.as_mm())));
+            match i {
+                PITCH => theremin.set_pitch_distance(distance),
+                VOLUME => theremin.set_volume_distance(distance),
+                _ => {}
+            }
+        }
+
+        if now >= next_step {
+            next_step = now + STEP_MS as u64 * 1000;
+            let Voice { freq_hz, volume } = theremin.step();
+
+            let new_top = (sys_clk_hz as f32 / (PWM_DIV_INT as f32 * freq_hz)) as u32 - 1;
+            let new_top = new_top.min(u16::MAX as u32) as u16;
+            // Loudness follows the square of the duty cycle more naturally
+            let duty = (volume * volume * MAX_DUTY * (new_top as f32 + 1.0)) as u16;
+
+            // TOP and the compare value are both latched at the end of a
+            // period, so the wave never breaks off halfway. They can still
+            // be latched one period apart, so write them in the order that
+            // never leaves the compare value above TOP, which would hold
+            // the output high for a whole period and click.
+            if new_top < top {
+                pwm.channel_b.set_duty_cycle(duty).unwrap();
+                pwm.set_top(new_top);
+            } else {
+                pwm.set_top(new_top);
+                pwm.channel_b.set_duty_cycle(duty).unwrap();
+            }
+            top = new_top;
+        }
+    }
+}
+
+/// Program metadata for `picotool info`
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
+    hal::binary_info::rp_cargo_bin_name!(),
+    hal::binary_info::rp_cargo_version!(),
+    hal::binary_info::rp_program_description!(c"Ultrasonic Theremin Example"),
+    hal::binary_info::rp_cargo_homepage_url!(),
+    hal::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
@@ -0,0 +1,142 @@
+//! Distances that carry their unit with them, and the speed of sound used
+//! to turn echo times into distances.
+
+/// Speed of sound in air
+///
+/// Sound travels about 0.6 m/s faster for every degree warmer, so a fixed
+/// value is off by around 1 cm per metre between a cold and a warm room.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct SpeedOfSound {
+    m_per_s: f32,
+}
+
+impl SpeedOfSound {
+    /// Dry air at 20 °C
+    pub const DEFAULT: Self = Self::from_m_per_s(343.2);
+
+    pub const fn from_m_per_s(m_per_s: f32) -> Self {
+        Self { m_per_s }
+    }
+
+    /// Speed of sound in air at `temperature_c`, with the relative humidity
+    /// in percent if it is known. Humid air is slightly faster, by up to
+    /// about 1.2 m/s.
+    pub fn in_air(temperature_c: f32, relative_humidity: Option<f32>) -> Self {
+        let dry = 331.3 * libm::sqrtf(1.0 + temperature_c / 273.15);
+        let humidity = relative_humidity.unwrap_or(0.0).clamp(0.0, 100.0);
+        Self::from_m_per_s(dry + 0.0124 * humidity)
+    }
+
+    pub const fn as_m_per_s(self) -> f32 {
+        self.m_per_s
+    }
+
+    const fn as_mm_per_us(self) -> f32 {
+        self.m_per_s / 1000.0
+    }
+}
+
+impl Default for SpeedOfSound {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// A distance, stored in millimetres
+///
+/// Build one with the `from_*` constructor for the unit you have and read it
+/// back with the matching `as_*` method, so centimetres never get mixed up
+/// with metres.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Distance {
+    mm: f32,
+}
+
+impl Distance {
+    pub const ZERO: Self = Self::from_mm(0.0);
+
+    pub const fn from_mm(mm: f32) -> Self {
+        Self { mm }
+    }
+
+    pub const fn from_cm(cm: f32) -> Self {
+        Self::from_mm(cm * 10.0)
+    }
+
+    pub const fn from_m(m: f32) -> Self {
+        Self::from_mm(m * 1000.0)
+    }
+
+    /// Distance to an object whose echo took `echo_us` to come back. The
+    /// sound travels there and back, so only half the time counts.
+    pub fn from_echo_us(echo_us: u32, speed: SpeedOfSound) -> Self {
+        Self::from_mm(echo_us as f32 * speed.as_mm_per_us() / 2.0)
+    }
+
+    /// Round trip time of an echo from an object this far away
+    pub fn echo_us(self, speed: SpeedOfSound) -> u32 {
+        (self.mm * 2.0 / speed.as_mm_per_us()) as u32
+    }
+
+    pub const fn as_mm(self) -> f32 {
+        self.mm
+    }
+
+    pub const fn as_cm(self) -> f32 {
+        self.mm / 10.0
+    }
+
+    pub const fn as_m(self) -> f32 {
+        self.mm / 1000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn dry_air_at_20_c_is_the_default() {
+        let speed = SpeedOfSound::in_air(20.0, None).as_m_per_s();
+        assert_close(speed, SpeedOfSound::DEFAULT.as_m_per_s(), 0.05);
+        assert_eq!(SpeedOfSound::in_air(20.0, Some(0.0)).as_m_per_s(), speed);
+    }
+
+    #[test]
+    fn warmer_air_is_faster() {
+        assert_close(SpeedOfSound::in_air(0.0, None).as_m_per_s(), 331.3, 0.001);
+        let cold = SpeedOfSound::in_air(10.0, None).as_m_per_s();
+        let warm = SpeedOfSound::in_air(30.0, None).as_m_per_s();
+        // About 0.6 m/s per degree
+        assert_close((warm - cold) / 20.0, 0.6, 0.02);
+    }
+
+    #[test]
+    fn humid_air_is_faster_by_at_most_1_24() {
+        let dry = SpeedOfSound::in_air(20.0, None).as_m_per_s();
+        let half = SpeedOfSound::in_air(20.0, Some(50.0)).as_m_per_s();
+        let saturated = SpeedOfSound::in_air(20.0, Some(100.0)).as_m_per_s();
+        assert_close(half - dry, 0.62, 0.001);
+        assert_close(saturated - dry, 1.24, 0.001);
+        assert_eq!(
+            SpeedOfSound::in_air(20.0, Some(150.0)).as_m_per_s(),
+            saturated
+        );
+        assert_eq!(SpeedOfSound::in_air(20.0, Some(-10.0)).as_m_per_s(), dry);
+    }
+
+    #[test]
+    fn echo_time_counts_the_way_there_and_back() {
+        let speed = SpeedOfSound::from_m_per_s(340.0);
+        // 1 m there and back at 0.34 mm/µs
+        assert_close(Distance::from_echo_us(5882, speed).as_m(), 1.0, 0.001);
+        assert_eq!(Distance::from_m(1.0).echo_us(speed), 5882);
+    }
+}
@@ -0,0 +1,328 @@
+//! # HC-SR04 Driver
+//!
+//! The HC-SR04 measures distance by sending an ultrasonic burst when its
+//! TRIG pin is pulsed, then holding ECHO high for as long as the sound took
+//! to reach an object and come back.
+//!
+//! Every wait in [`Hcsr04::measure`] has a timeout, so a missing or faulty
+//! sensor returns an error instead of hanging the firmware. The driver only
+//! needs embedded-hal pins plus a [`TimeSource`], so it can be exercised
+//! with mocks off the target.
+//!
+//! [`pio_echo::PioHcsr04`] does the same job with a PIO state machine doing
+//! the timing, which is more accurate and doesn't tie up the CPU. It needs
+//! the HAL, and is left out along with the `hal` feature to run the tests
+//! on the host.
+
+#![no_std]
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+#[cfg(feature = "hal")]
+use rp235x_hal::timer::{Timer, TimerDevice};
+
+pub mod array;
+pub mod distance;
+pub mod parking;
+#[cfg(feature = "hal")]
+pub mod pio_echo;
+pub mod temperature;
+pub mod theremin;
+
+pub use distance::{Distance, SpeedOfSound};
+
+/// A free-running microsecond counter
+pub trait TimeSource {
+    fn now_us(&mut self) -> u64;
+}
+
+#[cfg(feature = "hal")]
+impl<D: TimerDevice> TimeSource for Timer<D> {
+    fn now_us(&mut self) -> u64 {
+        self.get_counter().ticks()
+    }
+}
+
+/// Why a measurement failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    /// Reading or driving a pin failed
+    Pin(E),
+    /// ECHO never went high after the trigger, the sensor is probably not
+    /// connected
+    NoEcho,
+    /// The echo was longer than the maximum range, usually because there
+    /// is nothing in front of the sensor
+    OutOfRange,
+    /// The echo was shorter than the minimum range: something is closer
+    /// than the sensor can measure
+    TooClose,
+    /// ECHO was already high before the trigger, either from an earlier
+    /// echo that hasn't finished or a wiring fault
+    Stuck,
+}
+
+/// Measurement limits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    /// Closest distance the sensor reports reliably
+    pub min_range: Distance,
+    /// Echoes from further away count as out of range. Waiting stops once
+    /// this distance has passed, rather than at the sensor's own 38 ms
+    /// timeout.
+    pub max_range: Distance,
+    /// How long to wait for ECHO to go high after the trigger
+    pub echo_start_timeout_us: u32,
+    /// Update this from a temperature reading for accurate distances
+    pub speed_of_sound: SpeedOfSound,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            min_range: Distance::from_cm(2.0),
+            max_range: Distance::from_cm(400.0),
+            echo_start_timeout_us: 5_000,
+            speed_of_sound: SpeedOfSound::DEFAULT,
+        }
+    }
+}
+
+/// An HC-SR04 on two GPIOs
+///
+/// Leave at least 60 ms between measurements so echoes from one don't get
+/// picked up by the next.
+pub struct Hcsr04<T, E, C> {
+    trigger: T,
+    echo: E,
+    clock: C,
+    config: Config,
+}
+
+impl<T, E, C, PinError> Hcsr04<T, E, C>
+where
+    T: OutputPin<Error = PinError>,
+    E: InputPin<Error = PinError>,
+    C: TimeSource + DelayNs,
+{
+    pub fn new(trigger: T, echo: E, clock: C, config: Config) -> Self {
+        Self {
+            trigger,
+            echo,
+            clock,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    pub fn set_speed_of_sound(&mut self, speed: SpeedOfSound) {
+        self.config.speed_of_sound = speed;
+    }
+
+    /// Triggers the sensor and waits for the echo.
+    ///
+    /// Blocks for at most `echo_start_timeout_us` plus the echo time of
+    /// `max_range`.
+    pub fn measure(&mut self) -> Result<Distance, Error<PinError>> {
+        if self.echo.is_high().map_err(Error::Pin)? {
+            return Err(Error::Stuck);
+        }
+
+        self.trigger.set_low().map_err(Error::Pin)?;
+        self.clock.delay_us(2);
+        self.trigger.set_high().map_err(Error::Pin)?;
+        self.clock.delay_us(10);
+        self.trigger.set_low().map_err(Error::Pin)?;
+
+        let triggered = self.clock.now_us();
+        let rise = loop {
+            let now = self.clock.now_us();
+            if self.echo.is_high().map_err(Error::Pin)? {
+                break now;
+            }
+            if now.saturating_sub(triggered) > self.config.echo_start_timeout_us as u64 {
+                return Err(Error::NoEcho);
+            }
+        };
+
+        let speed = self.config.speed_of_sound;
+        let max_echo_us = self.config.max_range.echo_us(speed) as u64;
+        let fall = loop {
+            let now = self.clock.now_us();
+            if self.echo.is_low().map_err(Error::Pin)? {
+                break now;
+            }
+            if now.saturating_sub(rise) > max_echo_us {
+                return Err(Error::OutOfRange);
+            }
+        };
+
+        let echo_us = fall.saturating_sub(rise);
+        let distance = Distance::from_echo_us(echo_us as u32, speed);
+        if distance < self.config.min_range {
+            return Err(Error::TooClose);
+        }
+        Ok(distance)
+    }
+
+    /// Gives back the pins and the clock.
+    pub fn release(self) -> (T, E, C) {
+        (self.trigger, self.echo, self.clock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::convert::Infallible;
+
+    use embedded_hal::digital::ErrorType;
+
+    use super::*;
+
+    /// A simulated sensor: time moves on by a microsecond every time it is
+    /// read, and ECHO goes high `echo_delay_us` after the trigger pulse ends
+    /// for `echo_us`, if at all.
+    #[derive(Default)]
+    struct Bench {
+        now: Cell<u64>,
+        echo_delay_us: u64,
+        echo_us: Option<u64>,
+        stuck: bool,
+        pulse_started: Cell<Option<u64>>,
+        pulse_us: Cell<Option<u64>>,
+        triggered: Cell<Option<u64>>,
+    }
+
+    impl Bench {
+        fn echo(echo_us: u64) -> Self {
+            Self {
+                echo_delay_us: 400,
+                echo_us: Some(echo_us),
+                ..Self::default()
+            }
+        }
+
+        fn sensor(&self) -> Hcsr04<Trigger<'_>, Echo<'_>, Clock<'_>> {
+            Hcsr04::new(Trigger(self), Echo(self), Clock(self), Config::default())
+        }
+    }
+
+    struct Trigger<'a>(&'a Bench);
+    struct Echo<'a>(&'a Bench);
+    struct Clock<'a>(&'a Bench);
+
+    impl ErrorType for Trigger<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Trigger<'_> {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            let bench = self.0;
+            if let Some(started) = bench.pulse_started.take() {
+                bench.pulse_us.set(Some(bench.now.get() - started));
+                bench.triggered.set(Some(bench.now.get()));
+            }
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0.pulse_started.set(Some(self.0.now.get()));
+            Ok(())
+        }
+    }
+
+    impl ErrorType for Echo<'_> {
+        type Error = Infallible;
+    }
+
+    impl InputPin for Echo<'_> {
+        fn is_high(&mut self) -> Result<bool, Infallible> {
+            let bench = self.0;
+            if bench.stuck {
+                return Ok(true);
+            }
+            let (Some(triggered), Some(echo_us)) = (bench.triggered.get(), bench.echo_us) else {
+                return Ok(false);
+            };
+            let rise = triggered + bench.echo_delay_us;
+            let now = bench.now.get();
+            Ok(now >= rise && now < rise + echo_us)
+        }
+
+        fn is_low(&mut self) -> Result<bool, Infallible> {
+            self.is_high().map(|high| !high)
+        }
+    }
+
+    impl TimeSource for Clock<'_> {
+        fn now_us(&mut self) -> u64 {
+            let now = self.0.now.get() + 1;
+            self.0.now.set(now);
+            now
+        }
+    }
+
+    impl DelayNs for Clock<'_> {
+        fn delay_ns(&mut self, ns: u32) {
+            let now = self.0.now.get() + ns.div_ceil(1000) as u64;
+            self.0.now.set(now);
+        }
+    }
+
+    #[test]
+    fn triggers_with_a_10_us_pulse() {
+        let bench = Bench::echo(1000);
+        bench.sensor().measure().unwrap();
+        assert_eq!(bench.pulse_us.get(), Some(10));
+    }
+
+    #[test]
+    fn converts_the_echo_to_a_distance() {
+        // 1 m there and back at 343.2 m/s
+        let bench = Bench::echo(5828);
+        let distance = bench.sensor().measure().unwrap();
+        assert!((distance.as_mm() - 1000.0).abs() < 1.0, "{:?}", distance);
+    }
+
+    #[test]
+    fn gives_up_without_an_echo() {
+        let bench = Bench::default();
+        assert_eq!(bench.sensor().measure(), Err(Error::NoEcho));
+        let waited = bench.now.get() - bench.triggered.get().unwrap();
+        let timeout = Config::default().echo_start_timeout_us as u64;
+        assert!((timeout..timeout + 10).contains(&waited), "{}", waited);
+    }
+
+    #[test]
+    fn stops_waiting_past_the_maximum_range() {
+        let bench = Bench::echo(38_000);
+        assert_eq!(bench.sensor().measure(), Err(Error::OutOfRange));
+        let max_echo_us = Config::default().max_range.echo_us(SpeedOfSound::DEFAULT) as u64;
+        assert!(bench.now.get() < bench.triggered.get().unwrap() + 400 + max_echo_us + 10);
+    }
+
+    #[test]
+    fn reports_echoes_under_the_minimum_range_as_too_close() {
+        // About 9 mm
+        let bench = Bench::echo(50);
+        assert_eq!(bench.sensor().measure(), Err(Error::TooClose));
+    }
+
+    #[test]
+    fn refuses_to_trigger_while_echo_is_high() {
+        let bench = Bench {
+            stuck: true,
+            ..Bench::default()
+        };
+        assert_eq!(bench.sensor().measure(), Err(Error::Stuck));
+        assert_eq!(bench.pulse_us.get(), None);
+    }
+}
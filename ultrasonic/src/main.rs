@@ -1,13 +1,29 @@
+//! # Ultrasonic Example
+//!
+//! Measures distance with an HC-SR04 (TRIG on GPIO 17, ECHO on GPIO 16) and
+//! lights an LED on GPIO 3 brighter the closer an object gets. The LED stays
+//! off when nothing is within 30 cm or the sensor reports an error.
+//!
+//! The echo is timed by PIO0, so the main loop only starts measurements and
+//! collects the results. The speed of sound is corrected for temperature
+//! using the RP2350's internal sensor, read once a second. Readings go
+//! through a median filter, which drops stray echoes, and a moving average.
+
 #![no_std]
 #![no_main]
 
-use embedded_hal::digital::{InputPin, OutputPin};
 use panic_halt as _;
 
 use rp235x_hal as hal;
 
-use embedded_hal::delay::DelayNs;
 use embedded_hal::pwm::SetDutyCycle;
+use embedded_hal_0_2::adc::OneShot;
+use filters::{Ema, Filter, Median};
+use hal::pio::PIOExt;
+use hal::Clock;
+use ultrasonic::pio_echo::PioHcsr04;
+use ultrasonic::temperature::internal_sensor_celsius;
+use ultrasonic::{Config, Distance, SpeedOfSound};
 
 #[link_section = ".start_block"]
 #[used]
@@ -15,6 +31,12 @@ pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
 
 const XTAL_FREQ_HZ: u32 = 12_000_000u32;
 
+/// Time between measurements, long enough for old echoes to die away
+const MEASURE_INTERVAL_US: u64 = 60_000;
+
+/// Time between temperature readings
+const TEMPERATURE_INTERVAL_US: u64 = 1_000_000;
+
 #[hal::entry]
 fn main() -> ! {
     let mut pac = hal::pac::Peripherals::take().unwrap();
@@ -40,7 +62,7 @@ fn main() -> ! {
         &mut pac.RESETS,
     );
 
-    let mut timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+    let timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
 
     let mut pwm_silces = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
 
@@ -50,38 +72,63 @@ fn main() -> ! {
     let led = &mut pwm.channel_b;
     led.output_to(pins.gpio3);
 
-    let mut echo = pins.gpio16.into_pull_down_input();
-    let mut trigger = pins.gpio17.into_push_pull_output();
+    // Pulled down so an unplugged sensor reads as no echo, not stuck
+    let echo = pins
+        .gpio16
+        .into_pull_down_input()
+        .into_function::<hal::gpio::FunctionPio0>();
+    let trigger = pins.gpio17.into_function::<hal::gpio::FunctionPio0>();
+
+    let (mut pio, sm0, _, _, _) = pac.PIO0.split(&mut pac.RESETS);
+    let mut sensor = PioHcsr04::new(
+        &mut pio,
+        sm0,
+        trigger.id().num,
+        echo.id().num,
+        clocks.system_clock.freq().to_Hz(),
+        Config::default(),
+    )
+    .unwrap();
+
+    let mut adc = hal::Adc::new(pac.ADC, &mut pac.RESETS);
+    let mut temperature_sensor = adc.take_temp_sensor().unwrap();
+
+    // Distances in millimetres
+    let mut filter = Median::<f32, 5>::new().then(Ema::new(0.4));
 
     led.set_duty_cycle(0).unwrap();
+    let mut next_measurement = 0;
+    let mut next_temperature = 0;
     loop {
-        timer.delay_ms(5);
-
-        trigger.set_low().ok().unwrap();
-        timer.delay_us(2);
-        trigger.set_high().ok().unwrap();
-        timer.delay_us(10);
-        trigger.set_low().ok().unwrap();
-
-        let mut time_low = 0;
-        let mut time_high = 0;
-        while echo.is_low().ok().unwrap() {
-            time_low = timer.get_counter().ticks();
+        let now = timer.get_counter().ticks();
+        if now >= next_temperature {
+            let reading: Result<u16, _> = adc.read(&mut temperature_sensor);
+            if let Ok(counts) = reading {
+                let celsius = internal_sensor_celsius(counts);
+                sensor.set_speed_of_sound(SpeedOfSound::in_air(celsius, None));
+            }
+            next_temperature = now + TEMPERATURE_INTERVAL_US;
         }
-        while echo.is_high().ok().unwrap() {
-            time_high = timer.get_counter().ticks();
+
+        if now >= next_measurement {
+            sensor.start();
+            next_measurement = now + MEASURE_INTERVAL_US;
         }
-        let time_passed = time_high - time_low;
 
-        let distance = time_passed as f64 * 0.0343 / 2.0;
+        if let Some(result) = sensor.poll() {
+            let filtered =
+                result.map(|distance| Distance::from_mm(filter.update(distance.as_mm())));
+            let duty_cycle = match filtered {
+                Ok(distance) if distance.as_cm() < 30.0 => {
+                    let step = 30.0 - distance.as_cm();
+                    (step * 1500.) as u16 + 1000
+                }
+                _ => 0,
+            };
+            led.set_duty_cycle(duty_cycle).unwrap();
+        }
 
-        let duty_cycle = if distance < 30.0 {
-            let step = 30.0 - distance;
-            (step * 1500.) as u16 + 1000
-        } else {
-            0
-        };
-        led.set_duty_cycle(duty_cycle).unwrap();
+        // Anything else the program has to do goes here
     }
 }
 
@@ -95,4 +142,3 @@ pub static PICOTOOL_ENTRIES: [hal::binary_info::EntryAddr; 5] = [
     hal::binary_info::rp_cargo_homepage_url!(),
     hal::binary_info::rp_program_build_attribute!(),
 ];
-
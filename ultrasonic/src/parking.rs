@@ -0,0 +1,247 @@
+//! Parking sensor beeps.
+//!
+//! Nothing sounds while the way is clear. Once an object comes within
+//! `max_range` the buzzer beeps, faster and higher the closer it gets, and
+//! below `continuous_range` it holds a steady tone. Both thresholds have a
+//! hysteresis band so a reading hovering on the boundary doesn't make the
+//! sound stutter.
+//!
+//! [`ParkingSensor::tone`] only works out what the buzzer should be doing
+//! at a given moment, so the main loop can keep ranging while it beeps.
+
+use filters::Schmitt;
+
+use crate::Distance;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParkingConfig {
+    /// Start beeping closer than this
+    pub max_range: Distance,
+    /// Steady tone closer than this
+    pub continuous_range: Distance,
+    /// How far past a threshold a reading has to go to switch back
+    pub hysteresis: Distance,
+    /// Time from one beep to the next at `max_range`
+    pub slowest_period_ms: u32,
+    /// Time from one beep to the next just outside `continuous_range`
+    pub fastest_period_ms: u32,
+    /// Length of each beep
+    pub beep_ms: u32,
+    /// Pitch at `max_range`
+    pub far_pitch_hz: u32,
+    /// Pitch at `continuous_range` and closer
+    pub near_pitch_hz: u32,
+}
+
+impl Default for ParkingConfig {
+    fn default() -> Self {
+        Self {
+            max_range: Distance::from_cm(100.0),
+            continuous_range: Distance::from_cm(20.0),
+            hysteresis: Distance::from_cm(2.0),
+            slowest_period_ms: 1000,
+            fastest_period_ms: 150,
+            beep_ms: 60,
+            far_pitch_hz: 1000,
+            near_pitch_hz: 2500,
+        }
+    }
+}
+
+/// What the buzzer should be doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    Off,
+    On { freq_hz: u32 },
+}
+
+pub struct ParkingSensor {
+    config: ParkingConfig,
+    distance: Option<Distance>,
+    /// On while further than `max_range`
+    clear: Schmitt<f32>,
+    /// On while further than `continuous_range`
+    beeping: Schmitt<f32>,
+    cycle_start_us: u64,
+}
+
+impl ParkingSensor {
+    pub fn new(config: ParkingConfig) -> Self {
+        let (clear, beeping) = Self::thresholds(&config);
+        Self {
+            config,
+            distance: None,
+            clear,
+            beeping,
+            cycle_start_us: 0,
+        }
+    }
+
+    fn thresholds(config: &ParkingConfig) -> (Schmitt<f32>, Schmitt<f32>) {
+        let hysteresis = config.hysteresis.as_mm();
+        let max = config.max_range.as_mm();
+        let continuous = config.continuous_range.as_mm();
+        (
+            Schmitt::new(max, max + hysteresis),
+            Schmitt::new(continuous, continuous + hysteresis),
+        )
+    }
+
+    pub fn config(&self) -> ParkingConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: ParkingConfig) {
+        self.config = config;
+        (self.clear, self.beeping) = Self::thresholds(&config);
+        if let Some(distance) = self.distance {
+            self.set_distance(Some(distance));
+        }
+    }
+
+    /// Feeds in the latest reading, `None` when nothing is in range.
+    pub fn set_distance(&mut self, distance: Option<Distance>) {
+        // Nothing in range reads as infinitely far away
+        let mm = distance.map_or(f32::MAX, Distance::as_mm);
+        self.clear.update(mm);
+        self.beeping.update(mm);
+        self.distance = distance;
+    }
+
+    /// What the buzzer should be doing at `now_us`.
+    pub fn tone(&mut self, now_us: u64) -> Tone {
+        let Some(distance) = self.distance else {
+            return Tone::Off;
+        };
+        if self.clear.is_on() {
+            return Tone::Off;
+        }
+
+        let config = &self.config;
+        if !self.beeping.is_on() {
+            return Tone::On {
+                freq_hz: config.near_pitch_hz,
+            };
+        }
+
+        // 0.0 at the continuous threshold, 1.0 at the maximum range
+        let span = config.max_range.as_mm() - config.continuous_range.as_mm();
+        let t = ((distance.as_mm() - config.continuous_range.as_mm()) / span).clamp(0.0, 1.0);
+
+        let period_us = lerp(config.fastest_period_ms, config.slowest_period_ms, t) as u64 * 1000;
+        let freq_hz = lerp(config.near_pitch_hz, config.far_pitch_hz, t);
+
+        // Finish the current beep cycle before a new period takes effect
+        let elapsed = now_us.saturating_sub(self.cycle_start_us);
+        if elapsed >= period_us {
+            self.cycle_start_us = now_us;
+            return Tone::On { freq_hz };
+        }
+
+        if elapsed < config.beep_ms as u64 * 1000 {
+            Tone::On { freq_hz }
+        } else {
+            Tone::Off
+        }
+    }
+}
+
+fn lerp(from: u32, to: u32, t: f32) -> u32 {
+    (from as f32 + (to as f32 - from as f32) * t) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_cm(cm: f32) -> ParkingSensor {
+        let mut sensor = ParkingSensor::new(ParkingConfig::default());
+        sensor.set_distance(Some(Distance::from_cm(cm)));
+        sensor
+    }
+
+    /// Whether the tone stays on for a whole second
+    fn is_steady(sensor: &mut ParkingSensor) -> bool {
+        (0..1000).all(|ms| matches!(sensor.tone(ms * 1000), Tone::On { .. }))
+    }
+
+    /// Start of the second beep and its pitch, stepping a millisecond at a
+    /// time from the first one at 0
+    fn second_beep(sensor: &mut ParkingSensor) -> (u64, Tone) {
+        let mut was_on = true;
+        for ms in 0..5000 {
+            let tone = sensor.tone(ms * 1000);
+            let on = tone != Tone::Off;
+            if on && !was_on {
+                return (ms, tone);
+            }
+            was_on = on;
+        }
+        panic!("no second beep");
+    }
+
+    #[test]
+    fn silent_without_a_reading() {
+        let mut sensor = at_cm(50.0);
+        sensor.set_distance(None);
+        assert_eq!(sensor.tone(0), Tone::Off);
+        assert_eq!(sensor.tone(5_000_000), Tone::Off);
+    }
+
+    #[test]
+    fn silent_beyond_max_range() {
+        let mut sensor = at_cm(150.0);
+        assert_eq!(sensor.tone(0), Tone::Off);
+
+        // Coming closer, the clear band ends at 100 cm
+        sensor.set_distance(Some(Distance::from_cm(100.5)));
+        assert_eq!(sensor.tone(0), Tone::Off);
+        sensor.set_distance(Some(Distance::from_cm(99.5)));
+        assert_ne!(sensor.tone(0), Tone::Off);
+
+        // and only starts again past 102 cm
+        sensor.set_distance(Some(Distance::from_cm(101.5)));
+        assert_ne!(sensor.tone(1_000_000), Tone::Off);
+        sensor.set_distance(Some(Distance::from_cm(102.5)));
+        assert_eq!(sensor.tone(2_000_000), Tone::Off);
+    }
+
+    #[test]
+    fn continuous_band_has_hysteresis() {
+        let mut sensor = at_cm(30.0);
+        assert!(!is_steady(&mut sensor));
+
+        // Entered below 20 cm
+        sensor.set_distance(Some(Distance::from_cm(20.5)));
+        assert!(!is_steady(&mut sensor));
+        sensor.set_distance(Some(Distance::from_cm(19.5)));
+        assert!(is_steady(&mut sensor));
+        assert_eq!(sensor.tone(0), Tone::On { freq_hz: 2500 });
+
+        // Left above 22 cm
+        sensor.set_distance(Some(Distance::from_cm(21.5)));
+        assert!(is_steady(&mut sensor));
+        sensor.set_distance(Some(Distance::from_cm(22.5)));
+        assert!(!is_steady(&mut sensor));
+    }
+
+    #[test]
+    fn period_and_pitch_follow_the_distance() {
+        // At the far end, the slowest beeps at the lowest pitch
+        let mut sensor = at_cm(100.0);
+        assert_eq!(sensor.tone(0), Tone::On { freq_hz: 1000 });
+        assert_eq!(sensor.tone(59_000), Tone::On { freq_hz: 1000 });
+        assert_eq!(sensor.tone(61_000), Tone::Off);
+        assert_eq!(second_beep(&mut sensor), (1000, Tone::On { freq_hz: 1000 }));
+
+        // Halfway between 20 cm and 100 cm, halfway between the two
+        let mut sensor = at_cm(60.0);
+        assert_eq!(second_beep(&mut sensor), (575, Tone::On { freq_hz: 1750 }));
+
+        // Just outside the continuous band, coming from further away, the
+        // fastest
+        let mut sensor = at_cm(30.0);
+        sensor.set_distance(Some(Distance::from_cm(20.0)));
+        assert_eq!(second_beep(&mut sensor), (150, Tone::On { freq_hz: 2500 }));
+    }
+}
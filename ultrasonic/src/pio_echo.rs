@@ -0,0 +1,217 @@
+//! HC-SR04 measurements timed by a PIO state machine.
+//!
+//! The state machine sends the trigger pulse and counts microseconds while
+//! ECHO is high, so the result doesn't depend on how fast the CPU polls a
+//! pin. The CPU only starts a measurement and picks up the count later,
+//! leaving the main loop free in between.
+
+use core::convert::Infallible;
+
+use rp235x_hal::pio::{
+    InstallError, InstalledProgram, PIOBuilder, PIOExt, PinDir, Running, Rx, StateMachine,
+    StateMachineIndex, Tx, UninitStateMachine, PIO,
+};
+
+use crate::array::Rangefinder;
+use crate::{Config, Distance, Error, SpeedOfSound};
+
+/// Clock of the echo state machine. Both of its loops take two cycles, so
+/// they count whole microseconds.
+pub const PIO_CLOCK_HZ: u32 = 2_000_000;
+
+/// Reported when ECHO never went high
+const NO_ECHO: u32 = 0xFFFF_FFFF;
+/// Reported when ECHO was high before the trigger
+const STUCK: u32 = 0xFFFF_FFFE;
+/// Reported when ECHO stayed high for the whole allowed time. A count
+/// that ran down to 0 is an echo of exactly the longest allowed time.
+const TOO_LONG: u32 = 0xFFFF_FFFD;
+
+/// An HC-SR04 timed by one PIO state machine
+pub struct PioHcsr04<P: PIOExt, SM: StateMachineIndex> {
+    _sm: StateMachine<(P, SM), Running>,
+    rx: Rx<(P, SM)>,
+    tx: Tx<(P, SM)>,
+    config: Config,
+    /// Longest echo the running measurement waits for
+    max_echo_us: u32,
+    /// Speed of sound when the running measurement started
+    speed: SpeedOfSound,
+    pending: bool,
+}
+
+/// Loads the echo timing program into `pio`.
+///
+/// Every state machine of the block can run the same copy, see
+/// [`PioHcsr04::with_program`].
+pub fn install_program<P: PIOExt>(pio: &mut PIO<P>) -> Result<InstalledProgram<P>, InstallError> {
+    // Each measurement is requested with two words: how long to wait for
+    // the echo to start, then how long it may last, both in µs. The reply
+    // is what is left of the second count, or a marker.
+    let program = pio_proc::pio_asm!(
+        ".wrap_target",
+        "    pull block",
+        "    mov y, osr",
+        "    pull block",
+        "    mov x, osr",
+        "    jmp pin stuck",
+        "    set pins, 1 [19]", // 10 µs trigger pulse
+        "    set pins, 0",
+        "wait_rise:",
+        "    jmp pin measure",
+        "    jmp y-- wait_rise",
+        "    mov isr, ~null", // NO_ECHO
+        "    jmp report",
+        "measure:",
+        "    jmp pin still_high",
+        "    jmp report_count",
+        "still_high:",
+        "    jmp x-- measure",
+        "    set x, 2",
+        "    mov isr, ~x", // TOO_LONG
+        "    jmp report",
+        "stuck:",
+        "    set x, 1",
+        "    mov isr, ~x", // STUCK
+        "    jmp report",
+        "report_count:",
+        "    mov isr, x",
+        "report:",
+        "    push block",
+        ".wrap",
+    );
+    pio.install(&program.program)
+}
+
+impl<P: PIOExt, SM: StateMachineIndex> PioHcsr04<P, SM> {
+    /// Loads the program and sets up the state machine. Both pins must
+    /// already be switched to the PIO function.
+    pub fn new(
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        trigger_pin: u8,
+        echo_pin: u8,
+        sys_clk_hz: u32,
+        config: Config,
+    ) -> Result<Self, InstallError> {
+        let program = install_program(pio)?;
+        Ok(Self::with_program(
+            program,
+            sm,
+            trigger_pin,
+            echo_pin,
+            sys_clk_hz,
+            config,
+        ))
+    }
+
+    /// Sets up the state machine to run an already installed copy of the
+    /// program.
+    ///
+    /// To run several sensors from one PIO block, install the program once
+    /// with [`install_program`] and hand each sensor a copy from
+    /// `InstalledProgram::share`.
+    pub fn with_program(
+        program: InstalledProgram<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        trigger_pin: u8,
+        echo_pin: u8,
+        sys_clk_hz: u32,
+        config: Config,
+    ) -> Self {
+        let div = sys_clk_hz as u64 * 256 / PIO_CLOCK_HZ as u64;
+        let (mut sm, rx, tx) = PIOBuilder::from_installed_program(program)
+            .set_pins(trigger_pin, 1)
+            .jmp_pin(echo_pin)
+            .clock_divisor_fixed_point((div >> 8) as u16, div as u8)
+            .build(sm);
+        sm.set_pindirs([(trigger_pin, PinDir::Output), (echo_pin, PinDir::Input)]);
+
+        Self {
+            _sm: sm.start(),
+            rx,
+            tx,
+            config,
+            max_echo_us: config.max_range.echo_us(config.speed_of_sound),
+            speed: config.speed_of_sound,
+            pending: false,
+        }
+    }
+
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// Changes the limits from the next measurement on.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// Changes the speed of sound from the next measurement on.
+    pub fn set_speed_of_sound(&mut self, speed: SpeedOfSound) {
+        self.config.speed_of_sound = speed;
+    }
+
+    /// Triggers a measurement without waiting for it. Does nothing if one
+    /// is already running.
+    pub fn start(&mut self) {
+        if self.pending {
+            return;
+        }
+        self.speed = self.config.speed_of_sound;
+        self.max_echo_us = self.config.max_range.echo_us(self.speed);
+        self.tx.write(self.config.echo_start_timeout_us);
+        self.tx.write(self.max_echo_us);
+        self.pending = true;
+    }
+
+    /// Whether a measurement has been started and not yet collected
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Returns the result of the running measurement once it is done.
+    pub fn poll(&mut self) -> Option<Result<Distance, Error<Infallible>>> {
+        let reply = self.rx.read()?;
+        self.pending = false;
+
+        Some(match reply {
+            NO_ECHO => Err(Error::NoEcho),
+            STUCK => Err(Error::Stuck),
+            TOO_LONG => Err(Error::OutOfRange),
+            remaining => {
+                let echo_us = self.max_echo_us.saturating_sub(remaining);
+                let distance = Distance::from_echo_us(echo_us, self.speed);
+                if distance < self.config.min_range {
+                    Err(Error::TooClose)
+                } else {
+                    Ok(distance)
+                }
+            }
+        })
+    }
+
+    /// Triggers a measurement and waits for the result.
+    pub fn measure(&mut self) -> Result<Distance, Error<Infallible>> {
+        self.start();
+        loop {
+            if let Some(result) = self.poll() {
+                return result;
+            }
+        }
+    }
+}
+
+impl<P: PIOExt, SM: StateMachineIndex> Rangefinder for PioHcsr04<P, SM> {
+    fn start(&mut self) {
+        PioHcsr04::start(self);
+    }
+
+    fn poll(&mut self) -> Option<Result<Distance, Error<Infallible>>> {
+        PioHcsr04::poll(self)
+    }
+
+    fn set_speed_of_sound(&mut self, speed: SpeedOfSound) {
+        PioHcsr04::set_speed_of_sound(self, speed);
+    }
+}
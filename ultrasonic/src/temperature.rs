@@ -0,0 +1,18 @@
+//! Air temperature for the speed of sound calculation.
+//!
+//! Any thermometer works, pass its reading to
+//! [`SpeedOfSound::in_air`](crate::SpeedOfSound::in_air). Without one, the
+//! RP2350's own temperature sensor on ADC channel 8 is a fair stand-in. It
+//! measures the chip rather than the air, so it reads a few degrees high
+//! once the chip has warmed up.
+
+/// ADC reference voltage on the Pico 2
+const ADC_VREF: f32 = 3.3;
+const ADC_MAX_COUNTS: f32 = 4096.0;
+
+/// Converts a reading of the internal temperature sensor to °C, using the
+/// formula from the RP2350 datasheet.
+pub fn internal_sensor_celsius(adc_counts: u16) -> f32 {
+    let voltage = adc_counts as f32 * ADC_VREF / ADC_MAX_COUNTS;
+    27.0 - (voltage - 0.706) / 0.001721
+}
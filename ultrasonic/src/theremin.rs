@@ -0,0 +1,174 @@
+//! A theremin played with two hands over two sensors.
+//!
+//! The hand over the pitch sensor sets the note, higher the closer it gets.
+//! The hand over the volume sensor sets the loudness, quieter the closer it
+//! gets, the same way round as the antennas of a real theremin. Pitch can
+//! slide freely or snap to the notes of a scale.
+//!
+//! Jumping straight to a new frequency or duty cycle makes the buzzer
+//! click, so both glide towards their targets a little on every
+//! [`Theremin::step`].
+
+use filters::{Ema, Filter};
+use music::scale::{Key, Scale};
+
+use crate::Distance;
+
+/// Time between two calls to [`Theremin::step`]
+pub const STEP_MS: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThereminConfig {
+    /// Hand position for the highest note
+    pub pitch_near: Distance,
+    /// Hand position for the lowest note. Further away than this, the
+    /// theremin falls silent.
+    pub pitch_far: Distance,
+    pub low_hz: f32,
+    pub high_hz: f32,
+    /// Notes to snap to, `None` to slide through every frequency
+    pub scale: Option<(Scale, Key)>,
+    /// Hand position for silence
+    pub volume_near: Distance,
+    /// Hand position for full volume, and for no hand at all
+    pub volume_far: Distance,
+    /// Roughly how long a change of note takes to settle
+    pub glide_ms: u32,
+    /// Roughly how long a change of volume takes to settle
+    pub fade_ms: u32,
+}
+
+impl Default for ThereminConfig {
+    fn default() -> Self {
+        Self {
+            pitch_near: Distance::from_cm(5.0),
+            pitch_far: Distance::from_cm(50.0),
+            low_hz: music::NOTE_C4 as f32,
+            high_hz: music::NOTE_C6 as f32,
+            scale: Some((Scale::MajorPentatonic, Key::C)),
+            volume_near: Distance::from_cm(5.0),
+            volume_far: Distance::from_cm(30.0),
+            glide_ms: 30,
+            fade_ms: 40,
+        }
+    }
+}
+
+/// What the buzzer should be playing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Voice {
+    pub freq_hz: f32,
+    /// 0.0 for silence to 1.0 for full volume
+    pub volume: f32,
+}
+
+pub struct Theremin {
+    config: ThereminConfig,
+    target_hz: f32,
+    /// Volume set by the volume hand, before muting
+    target_volume: f32,
+    playing: bool,
+    freq: Ema<f32>,
+    volume: Ema<f32>,
+}
+
+impl Theremin {
+    /// Creates the theremin, silent until a hand is over the pitch sensor.
+    pub fn new(config: ThereminConfig) -> Self {
+        let mut volume = Ema::new(smoothing(config.fade_ms));
+        volume.update(0.0);
+        Self {
+            config,
+            target_hz: config.low_hz,
+            target_volume: 1.0,
+            playing: false,
+            freq: Ema::new(smoothing(config.glide_ms)),
+            volume,
+        }
+    }
+
+    pub fn config(&self) -> ThereminConfig {
+        self.config
+    }
+
+    /// Changes the settings, carrying on from the current sound.
+    pub fn set_config(&mut self, config: ThereminConfig) {
+        let voice = self.voice();
+        self.config = config;
+        self.freq = Ema::new(smoothing(config.glide_ms));
+        self.freq.update(voice.freq_hz);
+        self.volume = Ema::new(smoothing(config.fade_ms));
+        self.volume.update(voice.volume);
+    }
+
+    /// Feeds in the latest reading of the pitch sensor, `None` when no hand
+    /// is in range.
+    pub fn set_pitch_distance(&mut self, distance: Option<Distance>) {
+        let config = &self.config;
+        let Some(distance) = distance.filter(|distance| *distance <= config.pitch_far) else {
+            self.playing = false;
+            return;
+        };
+
+        // 0.0 at the near end, 1.0 at the far end
+        let t = position(distance, config.pitch_near, config.pitch_far);
+        // Equal hand movements give equal musical steps
+        let freq = config.high_hz * libm::powf(config.low_hz / config.high_hz, t);
+        self.target_hz = match config.scale {
+            Some((scale, key)) => scale.quantize(key, freq as f64) as f32,
+            None => freq,
+        };
+
+        if !self.playing {
+            // Start on the note instead of sliding up from the last one
+            self.freq.reset();
+            self.playing = true;
+        }
+    }
+
+    /// Feeds in the latest reading of the volume sensor, `None` when no
+    /// hand is in range.
+    pub fn set_volume_distance(&mut self, distance: Option<Distance>) {
+        let config = &self.config;
+        self.target_volume = match distance {
+            Some(distance) => position(distance, config.volume_near, config.volume_far),
+            None => 1.0,
+        };
+    }
+
+    /// Moves the sound one step towards the targets. Call it every
+    /// [`STEP_MS`].
+    pub fn step(&mut self) -> Voice {
+        self.freq.update(self.target_hz);
+        let target = if self.playing {
+            self.target_volume
+        } else {
+            0.0
+        };
+        self.volume.update(target);
+        self.voice()
+    }
+
+    /// What is playing right now
+    pub fn voice(&self) -> Voice {
+        Voice {
+            freq_hz: self.freq.value().unwrap_or(self.target_hz),
+            volume: self.volume.value().unwrap_or(0.0),
+        }
+    }
+}
+
+/// Where `distance` is between `near` (0.0) and `far` (1.0)
+fn position(distance: Distance, near: Distance, far: Distance) -> f32 {
+    let span = far.as_mm() - near.as_mm();
+    if span <= 0.0 {
+        return 1.0;
+    }
+    ((distance.as_mm() - near.as_mm()) / span).clamp(0.0, 1.0)
+}
+
+/// EMA weight that settles in roughly `settle_ms` at one update per
+/// [`STEP_MS`]
+fn smoothing(settle_ms: u32) -> f32 {
+    STEP_MS as f32 / (settle_ms + STEP_MS) as f32
+}